@@ -0,0 +1,32 @@
+// Discovery file telling the injector which port the command server of a game bound.
+// It's `darkclient_<pid>.port` in the temporary directory and holds the port number in
+// decimal on a single line, so several game instances can each have their own agent
+use log::{info, warn};
+use std::path::PathBuf;
+
+// Ports tried in order, the first free one is used
+pub const FIRST_PORT: u16 = 7878;
+pub const LAST_PORT: u16 = 7888;
+
+fn discovery_file_path() -> PathBuf {
+    std::env::temp_dir().join(format!("darkclient_{}.port", std::process::id()))
+}
+
+// Publishes the bound port for the injector
+pub fn write(port: u16) {
+    let path = discovery_file_path();
+    match std::fs::write(&path, format!("{}\n", port)) {
+        Ok(_) => info!("Wrote port {} to {:?}", port, path),
+        Err(e) => warn!("Unable to write the discovery file {:?}: {}", path, e),
+    }
+}
+
+// Removes the discovery file of this process, if any
+pub fn remove() {
+    let path = discovery_file_path();
+    match std::fs::remove_file(&path) {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Unable to delete the discovery file {:?}: {}", path, e),
+    }
+}
@@ -2,25 +2,72 @@ extern crate ctor;
 extern crate log;
 extern crate simplelog;
 
+mod discovery;
+mod log_file;
+#[cfg(windows)]
+mod pipe;
+mod reload_env;
+mod temp;
+
 use ctor::*;
+use jni::sys::{
+    jint, jsize, JNI_GetCreatedJavaVMs, JNI_EDETACHED, JNI_ERR, JNI_OK, JNI_VERSION_1_8,
+};
+use jni::JavaVM;
 use libloading::{Library, Symbol};
-use log::{error, info, LevelFilter};
+use log::{error, info, warn, LevelFilter};
+use log_file::RotatingFile;
+use reload_env::RELOAD_TIMESTAMP_VAR;
 use simplelog::{Config, WriteLogger};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::net::TcpListener;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Mutex, OnceLock};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{path, thread};
-use jni::JavaVM;
-use jni::sys::{jsize, JNI_GetCreatedJavaVMs, JNI_OK};
+
+// Environment variable selecting the command transport, `tcp` (default) or `pipe` on Windows
+const TRANSPORT_VAR: &str = "DARKCLIENT_TRANSPORT";
+
+// Environment variable selecting how reloaded libraries are loaded, `copy` (default) loads
+// a temporary copy, `memory` loads the bytes without leaving a file (Linux only)
+const LOAD_MODE_VAR: &str = "DARKCLIENT_LOAD_MODE";
+
+// Environment variable with the path of a client library to load as soon as the agent is
+// loaded, without waiting for a reload command
+const AUTOLOAD_VAR: &str = "DARKCLIENT_AUTOLOAD";
+
+// Environment variables to tune the JVM monitor
+const MONITOR_MAX_FAILURES_VAR: &str = "DARKCLIENT_MONITOR_MAX_FAILURES";
+const MONITOR_INTERVAL_VAR: &str = "DARKCLIENT_MONITOR_INTERVAL_MS";
+const DEFAULT_MONITOR_MAX_FAILURES: u32 = 3;
+const DEFAULT_MONITOR_INTERVAL_MS: u64 = 500;
+// Size cap of the log file in megabytes, 0 for no cap. Shared with the client
+const LOG_MAX_MB_VAR: &str = "DARKCLIENT_LOG_MAX_MB";
+const DEFAULT_LOG_MAX_MB: u64 = 10;
+// How long a client has to send its command after connecting
+const COMMAND_READ_TIMEOUT: Duration = Duration::from_secs(5);
+// A socket left by a previous session can hold a port for a while, when every port of the
+// range is taken binding is retried this often for up to BIND_RETRY_ATTEMPTS times
+const BIND_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+const BIND_RETRY_ATTEMPTS: u32 = 30;
 
 // Global variable to keep track of the loaded library
 static CLIENT_LIBRARY: OnceLock<Mutex<Option<Library>>> = OnceLock::new();
 static RUNNING: AtomicBool = AtomicBool::new(true);
 static JVM_MONITOR: OnceLock<thread::JoinHandle<()>> = OnceLock::new();
+// Library file the loaded client comes from, not its temporary copy
+static LOADED_SOURCE: Mutex<Option<LoadedSource>> = Mutex::new(None);
+
+// Source file of the loaded client, as it was when loaded
+struct LoadedSource {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
 
 // Function called when the agent is loaded
 #[no_mangle]
@@ -30,7 +77,11 @@ fn agent_onload() {
     WriteLogger::init(
         LevelFilter::Debug,
         Config::default(),
-        File::create("agent_loader.log").unwrap(),
+        RotatingFile::create(
+            "agent_loader.log",
+            env_or_default(LOG_MAX_MB_VAR, DEFAULT_LOG_MAX_MB).saturating_mul(1024 * 1024),
+        )
+        .unwrap(),
     )
     .unwrap();
 
@@ -39,6 +90,9 @@ fn agent_onload() {
     // Initialize the global variable for the library
     CLIENT_LIBRARY.get_or_init(|| Mutex::new(None));
 
+    // Copies left by a previous session that didn't unload cleanly
+    temp::remove_orphans();
+
     // Setup signal handlers for clean shutdown
     setup_signal_handlers();
 
@@ -47,6 +101,24 @@ fn agent_onload() {
 
     // Start the socket server for commands
     start_command_server();
+
+    autoload_client_library();
+}
+
+// Loads the library from DARKCLIENT_AUTOLOAD like a reload command would, so it can still be
+// hot swapped afterwards. Done on its own thread as the loader lock is held during agent_onload
+fn autoload_client_library() {
+    let Some(lib_path) = std::env::var_os(AUTOLOAD_VAR).filter(|path| !path.is_empty()) else {
+        return;
+    };
+    let lib_path = lib_path.to_string_lossy().into_owned();
+
+    thread::spawn(move || {
+        info!("Autoloading client library: {}", lib_path);
+        if let Err(e) = reload_client_library(&lib_path) {
+            error!("Error during autoload: {}", e);
+        }
+    });
 }
 
 // Function called when the agent is unloaded
@@ -63,6 +135,10 @@ fn agent_onunload() {
     if let Some(mut guard) = CLIENT_LIBRARY.get().and_then(|m| m.lock().ok()) {
         *guard = None;
     }
+
+    // Nothing is loaded anymore, every copy can go
+    temp::cleanup(None);
+    discovery::remove();
 }
 
 // Setup signal handlers to detect process termination
@@ -84,8 +160,14 @@ fn setup_signal_handlers() {
         }
 
         unsafe {
-            libc::signal(libc::SIGTERM, handle_signal as libc::sighandler_t);
-            libc::signal(libc::SIGINT, handle_signal as libc::sighandler_t);
+            libc::signal(
+                libc::SIGTERM,
+                handle_signal as *const () as libc::sighandler_t,
+            );
+            libc::signal(
+                libc::SIGINT,
+                handle_signal as *const () as libc::sighandler_t,
+            );
         }
 
         info!("Signal handlers installed");
@@ -111,40 +193,68 @@ fn start_jvm_monitor() {
             }
         };
 
+        // Attached once, the checks then only ask the JVM whether it still knows this thread
+        let mut attached = match jvm.attach_current_thread_as_daemon() {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Failed to attach the JVM monitor thread, retrying: {}", e);
+                false
+            }
+        };
         info!("JVM detected, monitoring started");
 
         // Monitor JVM health with multiple checks
         let mut consecutive_failures = 0;
-        let max_failures = 3;
+        let max_failures =
+            positive_env_or_default(MONITOR_MAX_FAILURES_VAR, DEFAULT_MONITOR_MAX_FAILURES);
+        let interval = Duration::from_millis(positive_env_or_default(
+            MONITOR_INTERVAL_VAR,
+            DEFAULT_MONITOR_INTERVAL_MS,
+        ));
+        info!(
+            "JVM monitor configured with max_failures={} and interval={:?}",
+            max_failures, interval
+        );
 
         while RUNNING.load(Ordering::SeqCst) {
-            thread::sleep(Duration::from_millis(500));
-
-            // Method 1: Try to attach to the JVM
-            let attach_ok = jvm.attach_current_thread_as_daemon().is_ok();
+            thread::sleep(interval);
 
-            // Method 2: Check if we can access Java classes
-            let classes_ok = if attach_ok {
-                if let Ok(mut env) = jvm.attach_current_thread_as_daemon() {
-                    env.find_class("java/lang/System").is_ok()
-                } else {
-                    false
-                }
-            } else {
-                false
-            };
-
-            // Method 3: Check if the JVM pointer is still valid
+            // Method 1: Check if the JVM pointer is still valid
             let jvm_valid = {
                 let jvm_ptr = jvm.get_java_vm_pointer();
                 !jvm_ptr.is_null()
             };
 
-            if !attach_ok || !classes_ok || !jvm_valid {
+            // A null JVM pointer can't recover, no need to wait for more failures
+            if !jvm_valid {
+                info!("JVM pointer is null, the JVM is dead");
+                on_vm_death();
+                break;
+            }
+
+            // Unattached, every probe would report the thread as detached and unload the
+            // client, so the health checks wait until an attach succeeds
+            if !attached {
+                if jvm.attach_current_thread_as_daemon().is_err() {
+                    continue;
+                }
+                info!("JVM monitor thread attached");
+                attached = true;
+            }
+
+            // Method 2: Ask the JVM for the environment of this thread. Only a detached
+            // thread counts as a failure, any other answer means the JVM still responds
+            let status = probe_vm(&jvm);
+            if status != JNI_OK && status != JNI_EDETACHED {
+                warn!("Unexpected JVM probe status {}, ignoring", status);
+            }
+
+            // Only declare the JVM dead after several consecutive failures
+            if status == JNI_EDETACHED {
                 consecutive_failures += 1;
                 info!(
-                    "JVM health check failed ({}/{}): attach={}, classes={}, valid={}",
-                    consecutive_failures, max_failures, attach_ok, classes_ok, jvm_valid
+                    "JVM health check failed ({}/{}): monitor thread detached",
+                    consecutive_failures, max_failures
                 );
 
                 if consecutive_failures >= max_failures {
@@ -163,6 +273,30 @@ fn start_jvm_monitor() {
     JVM_MONITOR.set(handle).ok();
 }
 
+// Reads a value from an environment variable, falling back to the default if missing or invalid
+fn env_or_default<T: FromStr + Display>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            error!("Invalid value {:?} for {}, using {}", value, name, default);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+// Like env_or_default for counts and durations, where 0 would break the monitor
+fn positive_env_or_default<T: FromStr + Display + PartialOrd + From<u8> + Copy>(
+    name: &str,
+    default: T,
+) -> T {
+    let value = env_or_default(name, default);
+    if value < T::from(1) {
+        error!("{} must be at least 1, using {}", name, default);
+        return default;
+    }
+    value
+}
+
 fn get_jvm() -> Option<JavaVM> {
     unsafe {
         let mut java_vm: *mut jni::sys::JavaVM = std::ptr::null_mut();
@@ -176,6 +310,19 @@ fn get_jvm() -> Option<JavaVM> {
     }
 }
 
+// GetEnv neither attaches the thread nor loads anything, unlike the attach and find_class
+// calls used before, so it keeps answering during long GC pauses instead of failing
+fn probe_vm(jvm: &JavaVM) -> jint {
+    let vm = jvm.get_java_vm_pointer();
+    let mut env: *mut c_void = std::ptr::null_mut();
+    unsafe {
+        match (**vm).GetEnv {
+            Some(get_env) => get_env(vm, &mut env, JNI_VERSION_1_8),
+            None => JNI_ERR,
+        }
+    }
+}
+
 fn on_vm_death() {
     info!("VM death detected - initiating cleanup");
     agent_onunload();
@@ -212,19 +359,27 @@ fn load_client_library(lib_path: &str) -> Result<(), Box<dyn std::error::Error>>
     // Load the new library
     let lib = unsafe { Library::new(&client_path)? };
 
-    // Find and call the initialization function
-    unsafe {
-        if let Ok(init_fn) = lib.get::<Symbol<extern "C" fn()>>(b"initialize_client") {
+    // Find and call the initialization function. It's declared "C-unwind" on both sides so
+    // a panic in the client can be caught here instead of aborting the JVM
+    let initialized = unsafe {
+        if let Ok(init_fn) = lib.get::<Symbol<extern "C-unwind" fn()>>(b"initialize_client") {
             info!("Calling initialization function");
-            init_fn();
+            std::panic::catch_unwind(|| init_fn()).is_ok()
         } else {
             info!("Initialization function not found, assuming self-initialization");
+            true
         }
-    }
+    };
 
-    // Store the library
+    // Store the library, even after a failed initialization the client may have started
+    // threads running its code, and it can still be cleaned up by an unload
     *lib_guard = Some(lib);
 
+    if !initialized {
+        error!("Client initialization panicked");
+        return Err("Client initialization panicked".into());
+    }
+
     info!("Client library loaded successfully");
     Ok(())
 }
@@ -237,11 +392,21 @@ fn unload_client_library() -> Result<(), Box<dyn std::error::Error>> {
 
     if let Some(lib) = lib_guard.as_ref() {
         // Call the cleanup function if present
-        unsafe {
-            if let Ok(cleanup_fn) = lib.get::<Symbol<extern "C" fn()>>(b"cleanup_client") {
-                info!("Calling cleanup function");
-                cleanup_fn();
+        let cleaned_up = unsafe {
+            match lib.get::<Symbol<extern "C-unwind" fn()>>(b"cleanup_client") {
+                Ok(cleanup_fn) => {
+                    info!("Calling cleanup function");
+                    std::panic::catch_unwind(|| cleanup_fn()).is_ok()
+                }
+                Err(_) => true,
             }
+        };
+
+        // The client threads may still be running after a failed cleanup, unloading the
+        // library under them would crash the JVM, keep it loaded
+        if !cleaned_up {
+            error!("Client cleanup panicked, keeping the library loaded");
+            return Err("Client cleanup panicked".into());
         }
 
         drop(lib_guard.take());
@@ -275,14 +440,50 @@ fn reload_client_library(lib_path: &str) -> Result<(), Box<dyn std::error::Error
         .unwrap()
         .as_secs();
 
-    // Temporary file name
-    let temp_filename = format!("temp_{}_{}", timestamp, filename);
-    let mut temp_path = std::env::temp_dir();
-    temp_path.push(&temp_filename);
+    // Let the client know which reload is in progress, so it only restores its own state
+    std::env::set_var(RELOAD_TIMESTAMP_VAR, timestamp.to_string());
+
+    // Recorded first, a library whose initialization fails stays loaded
+    *LOADED_SOURCE.lock().unwrap() = Some(LoadedSource {
+        path: path::absolute(&client_path).unwrap_or_else(|_| client_path.clone()),
+        modified: std::fs::metadata(&client_path)
+            .and_then(|metadata| metadata.modified())
+            .ok(),
+    });
+
+    if load_mode_is_memory() {
+        load_client_library_from_bytes(&std::fs::read(&client_path)?)?;
+    } else {
+        let temp_path = temp::temp_library_path(timestamp, filename);
+
+        // Copy the file
+        std::fs::copy(&client_path, &temp_path)?;
+        info!("Library copied to: {:?}", temp_path);
+        load_temp_library(temp_path)?;
+    }
+
+    // Wait a bit to ensure all resources are released
+    thread::sleep(Duration::from_millis(100));
+
+    info!("Client library reloaded successfully");
+    Ok(())
+}
+
+// Whether DARKCLIENT_LOAD_MODE asks to load reloaded libraries from memory
+fn load_mode_is_memory() -> bool {
+    match std::env::var(LOAD_MODE_VAR) {
+        Ok(mode) if mode.eq_ignore_ascii_case("memory") => true,
+        Ok(mode) if !mode.is_empty() && !mode.eq_ignore_ascii_case("copy") => {
+            error!("Unsupported load mode {}, using copy", mode);
+            false
+        }
+        _ => false,
+    }
+}
 
-    // Copy the file
-    std::fs::copy(&client_path, &temp_path)?;
-    info!("Library copied to: {:?}", temp_path);
+// Loads a temporary copy of the client library, deleting the copies no longer loaded
+fn load_temp_library(temp_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    temp::register(temp_path.clone());
 
     let temp_client =
         path::absolute(&temp_path).map_err(|e| format!("Unable to get absolute path: {:?}", e))?;
@@ -290,37 +491,183 @@ fn reload_client_library(lib_path: &str) -> Result<(), Box<dyn std::error::Error
     let temp_client = temp_client.as_str();
     let temp_client = temp_client.trim_matches(|c| c == '"' || c == '\'');
 
-    // Cleanup of temporary files (optional, can be executed in a separate thread)
-    thread::spawn(move || {
-        thread::sleep(Duration::from_secs(5)); // Wait a bit before deleting
-        if let Err(e) = std::fs::remove_file(&temp_path) {
-            error!("Unable to delete temporary file: {:?}", e);
-        }
-    });
-
     // Load the new copy
-    load_client_library(temp_client)?;
+    let result = load_client_library(temp_client);
 
-    // Wait a bit to ensure all resources are released
-    thread::sleep(Duration::from_millis(100));
+    // The previous copies are unloaded now, and so is this one if loading failed
+    temp::cleanup(result.as_ref().ok().map(|_| temp_path.as_path()));
+    result
+}
 
-    info!("Client library reloaded successfully");
+// Loads the client library from an anonymous memory file, so no copy is written to disk
+// and there is no file to lock or clean up
+#[cfg(target_os = "linux")]
+fn load_client_library_from_bytes(bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    use std::fs::File;
+    use std::os::fd::FromRawFd;
+
+    let name = std::ffi::CString::new("darkclient")?;
+    let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(bytes)?;
+
+    info!("Library loaded in memory ({} bytes)", bytes.len());
+    load_client_library(&format!("/proc/self/fd/{}", fd))?;
+
+    // dlopen matches already loaded libraries by path, so a reused fd number could return
+    // a previous library that wasn't fully unloaded. Keeping the files open prevents reuse
+    static MEMORY_LIBRARIES: Mutex<Vec<File>> = Mutex::new(Vec::new());
+    MEMORY_LIBRARIES.lock().unwrap().push(file);
+
+    // Copies made by reloads in copy mode are no longer loaded
+    temp::cleanup(None);
     Ok(())
 }
 
-// Start a socket server to listen for commands
+// Without anonymous memory files the library still needs a file, a tracked temporary copy
+#[cfg(not(target_os = "linux"))]
+fn load_client_library_from_bytes(bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis() as u64;
+    let filename = format!("client.{}", std::env::consts::DLL_EXTENSION);
+    let temp_path = temp::temp_library_path(timestamp, &filename);
+
+    std::fs::write(&temp_path, bytes)?;
+    info!("Library written to: {:?}", temp_path);
+    load_temp_library(temp_path)
+}
+
+// Function to get the resolved mapping from the loaded client library
+fn dump_client_mapping() -> Result<String, Box<dyn std::error::Error>> {
+    read_client_string(b"dump_mapping")
+}
+
+// Turns module tick metrics on or off and returns the current report
+fn client_metrics(enable: Option<bool>) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(enable) = enable {
+        let lib_guard = CLIENT_LIBRARY.get().unwrap().lock().unwrap();
+        let lib = lib_guard.as_ref().ok_or("No client library loaded")?;
+
+        unsafe {
+            let set_fn = lib.get::<Symbol<extern "C" fn(bool)>>(b"set_metrics_enabled")?;
+            set_fn(enable);
+        }
+    }
+
+    read_client_string(b"dump_metrics")
+}
+
+// Describes the loaded client: its source file, when the file was modified and its build
+fn client_info() -> Result<String, Box<dyn std::error::Error>> {
+    let lib_guard = CLIENT_LIBRARY.get().unwrap().lock().unwrap();
+    let Some(lib) = lib_guard.as_ref() else {
+        return Ok("No library loaded\n".to_string());
+    };
+
+    let version = unsafe {
+        match lib.get::<Symbol<extern "C" fn() -> *const c_char>>(b"client_version") {
+            Ok(version_fn) => {
+                let version_ptr = version_fn();
+                if version_ptr.is_null() {
+                    "unknown".to_string()
+                } else {
+                    CStr::from_ptr(version_ptr).to_string_lossy().into_owned()
+                }
+            }
+            // Built before client_version was exported
+            Err(_) => "unknown".to_string(),
+        }
+    };
+
+    let source = LOADED_SOURCE.lock().unwrap();
+    let (path, modified) = match source.as_ref() {
+        Some(source) => (
+            source.path.display().to_string(),
+            source
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| format!("{} (unix time)", modified.as_secs()))
+                .unwrap_or_else(|| "unknown".to_string()),
+        ),
+        None => ("unknown".to_string(), "unknown".to_string()),
+    };
+
+    Ok(format!(
+        "Library: {}\nModified: {}\nVersion: {}\n",
+        path, modified, version
+    ))
+}
+
+// Toggles a client module by name and returns the client reply
+fn toggle_client_module(name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let name = CString::new(name)?;
+    let lib_guard = CLIENT_LIBRARY.get().unwrap().lock().unwrap();
+    let lib = lib_guard.as_ref().ok_or("No client library loaded")?;
+
+    unsafe {
+        let toggle_fn = lib
+            .get::<Symbol<unsafe extern "C" fn(*const c_char) -> *mut c_char>>(b"toggle_module")?;
+        let free_fn =
+            lib.get::<Symbol<unsafe extern "C" fn(*mut c_char)>>(b"free_client_string")?;
+
+        let reply_ptr = toggle_fn(name.as_ptr());
+        if reply_ptr.is_null() {
+            return Err("Client returned no value".into());
+        }
+
+        let reply = CStr::from_ptr(reply_ptr).to_string_lossy().into_owned();
+        free_fn(reply_ptr);
+        Ok(reply)
+    }
+}
+
+// Calls a client function returning a string, freeing it with free_client_string
+fn read_client_string(symbol: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let lib_guard = CLIENT_LIBRARY.get().unwrap().lock().unwrap();
+    let lib = lib_guard.as_ref().ok_or("No client library loaded")?;
+
+    unsafe {
+        let string_fn = lib.get::<Symbol<extern "C" fn() -> *mut c_char>>(symbol)?;
+        let free_fn =
+            lib.get::<Symbol<unsafe extern "C" fn(*mut c_char)>>(b"free_client_string")?;
+
+        let string_ptr = string_fn();
+        if string_ptr.is_null() {
+            return Err("Client returned no value".into());
+        }
+
+        let value = CStr::from_ptr(string_ptr).to_string_lossy().into_owned();
+        free_fn(string_ptr);
+        Ok(value)
+    }
+}
+
+// Start the command server on the configured transport
 fn start_command_server() {
+    let transport = std::env::var(TRANSPORT_VAR).unwrap_or_default();
+
+    #[cfg(windows)]
+    if transport.eq_ignore_ascii_case("pipe") {
+        pipe::start_pipe_server();
+        return;
+    }
+
+    if !transport.is_empty() && !transport.eq_ignore_ascii_case("tcp") {
+        error!("Unsupported transport {}, using tcp", transport);
+    }
+
+    start_tcp_server();
+}
+
+// Start a socket server to listen for commands
+fn start_tcp_server() {
     thread::spawn(move || {
-        let addr = "127.0.0.1:7878";
-        let listener = match TcpListener::bind(addr) {
-            Ok(listener) => {
-                info!("Listening on {}", addr);
-                listener
-            }
-            Err(e) => {
-                error!("Unable to bind to {}: {}", addr, e);
-                return;
-            }
+        let Some(listener) = bind_with_retry() else {
+            return;
         };
 
         // Set the socket to non-blocking mode
@@ -330,33 +677,16 @@ fn start_command_server() {
             // Check for incoming connections
             match listener.accept() {
                 Ok((stream, _)) => {
-                    let mut reader = BufReader::new(stream);
-                    let mut line = String::new();
-
-                    if reader.read_line(&mut line).is_ok() {
-                        let line = line.trim();
-                        let parts: Vec<&str> = line.splitn(2, ' ').collect();
-
-                        match parts.first() {
-                            Some(&"reload") => {
-                                if let Some(path) = parts.get(1) {
-                                    info!("Reload command received with path: {}", path);
-
-                                    if let Err(e) = reload_client_library(path) {
-                                        error!("Error during reload: {}", e);
-                                    }
-                                } else {
-                                    error!("Reload command received without path!");
-                                }
-                            }
-                            Some(other) => {
-                                error!("Unknown command: {}", other);
-                            }
-                            None => {
-                                error!("Empty command received");
-                            }
-                        }
+                    // The accepted stream inherits non-blocking mode from the listener, so
+                    // reading right after connect could fail before the command arrives
+                    if let Err(e) = stream
+                        .set_nonblocking(false)
+                        .and_then(|_| stream.set_read_timeout(Some(COMMAND_READ_TIMEOUT)))
+                    {
+                        error!("Unable to configure the connection: {}", e);
+                        continue;
                     }
+                    handle_connection(stream)
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     // No connection available, wait a bit
@@ -371,3 +701,155 @@ fn start_command_server() {
         }
     });
 }
+
+// Binds the command socket on the first free port of the range and publishes it in the
+// discovery file. Binding is retried while every port is taken so the agent recovers once
+// one is freed instead of staying unreachable until the game restarts
+fn bind_with_retry() -> Option<TcpListener> {
+    let mut attempt = 1;
+    loop {
+        let mut last_error = None;
+        for port in discovery::FIRST_PORT..=discovery::LAST_PORT {
+            match TcpListener::bind(("127.0.0.1", port)) {
+                Ok(listener) => {
+                    info!("Listening on 127.0.0.1:{}", port);
+                    discovery::write(port);
+                    return Some(listener);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let ports = format!("{}-{}", discovery::FIRST_PORT, discovery::LAST_PORT);
+        let e = last_error.expect("the port range is not empty");
+        if attempt < BIND_RETRY_ATTEMPTS {
+            warn!(
+                "Unable to bind to any port in {} ({}), retrying in {:?}",
+                ports, e, BIND_RETRY_INTERVAL
+            );
+        } else {
+            error!(
+                "Unable to bind to any port in {} after {} attempts: {}",
+                ports, attempt, e
+            );
+            return None;
+        }
+
+        thread::sleep(BIND_RETRY_INTERVAL);
+        if !RUNNING.load(Ordering::SeqCst) {
+            return None;
+        }
+        attempt += 1;
+    }
+}
+
+// Reads a single command from a connection of any transport and executes it
+fn handle_connection<S: Read + Write>(stream: S) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    if let Err(e) = reader.read_line(&mut line) {
+        error!("Unable to read the command: {}", e);
+        return;
+    }
+
+    let line = line.trim();
+    let parts: Vec<&str> = line.splitn(2, ' ').collect();
+
+    match parts.first() {
+        Some(&"reload") => {
+            if let Some(path) = parts.get(1) {
+                info!("Reload command received with path: {}", path);
+
+                if let Err(e) = reload_client_library(path) {
+                    error!("Error during reload: {}", e);
+                }
+            } else {
+                error!("Reload command received without path!");
+            }
+        }
+        Some(&"dump") => {
+            info!("Dump command received");
+
+            let response = match dump_client_mapping() {
+                Ok(dump) => dump,
+                Err(e) => {
+                    error!("Error during dump: {}", e);
+                    format!("Error during dump: {}\n", e)
+                }
+            };
+            if let Err(e) = reader.get_mut().write_all(response.as_bytes()) {
+                error!("Unable to send the dump: {}", e);
+            }
+        }
+        Some(&"metrics") => {
+            info!("Metrics command received");
+
+            let enable = match parts.get(1).map(|arg| arg.trim()) {
+                None => None,
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(arg) => {
+                    error!("Invalid metrics argument: {}", arg);
+                    let response = format!("Invalid metrics argument {}, use on or off\n", arg);
+                    if let Err(e) = reader.get_mut().write_all(response.as_bytes()) {
+                        error!("Unable to send the metrics: {}", e);
+                    }
+                    return;
+                }
+            };
+
+            let response = match client_metrics(enable) {
+                Ok(report) => report,
+                Err(e) => {
+                    error!("Error while reading metrics: {}", e);
+                    format!("Error while reading metrics: {}\n", e)
+                }
+            };
+            if let Err(e) = reader.get_mut().write_all(response.as_bytes()) {
+                error!("Unable to send the metrics: {}", e);
+            }
+        }
+        Some(&"info") | Some(&"version") => {
+            info!("Info command received");
+
+            let response = match client_info() {
+                Ok(info) => info,
+                Err(e) => {
+                    error!("Error while reading the client info: {}", e);
+                    format!("Error while reading the client info: {}\n", e)
+                }
+            };
+            if let Err(e) = reader.get_mut().write_all(response.as_bytes()) {
+                error!("Unable to send the client info: {}", e);
+            }
+        }
+        Some(&"toggle") => {
+            let response = match parts.get(1).map(|name| name.trim()) {
+                Some(name) if !name.is_empty() => {
+                    info!("Toggle command received for {}", name);
+                    match toggle_client_module(name) {
+                        Ok(reply) => reply,
+                        Err(e) => {
+                            error!("Error during toggle: {}", e);
+                            format!("Error during toggle: {}\n", e)
+                        }
+                    }
+                }
+                _ => {
+                    error!("Toggle command received without module name!");
+                    "Error: missing module name, use toggle <module>\n".to_string()
+                }
+            };
+            if let Err(e) = reader.get_mut().write_all(response.as_bytes()) {
+                error!("Unable to send the toggle reply: {}", e);
+            }
+        }
+        Some(other) => {
+            error!("Unknown command: {}", other);
+        }
+        None => {
+            error!("Empty command received");
+        }
+    }
+}
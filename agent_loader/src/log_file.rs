@@ -0,0 +1,60 @@
+// Log file of the agent, rolled over to `<name>.1` once it reaches its size cap, replacing
+// the previous `.1`, so a session keeps at most twice the cap on disk. It rolls over
+// between lines only. Same format as the client's log, see DARKCLIENT_LOG_MAX_MB
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    // No cap if 0
+    max_size: u64,
+    at_line_start: bool,
+}
+
+// Same code as the client's logging.rs, keep the two copies identical
+impl RotatingFile {
+    // Creates the file, truncating it, capped at `max_size` bytes
+    pub fn create(path: impl Into<PathBuf>, max_size: u64) -> io::Result<Self> {
+        let path = path.into();
+        Ok(Self {
+            file: File::create(&path)?,
+            path,
+            written: 0,
+            max_size,
+            at_line_start: true,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        std::fs::rename(&self.path, rotated)?;
+
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.written >= self.max_size && self.at_line_start {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,114 @@
+// Named pipe transport for the command server, for machines where loopback TCP is blocked
+use crate::{handle_connection, RUNNING};
+use log::{error, info};
+use std::fs::File;
+use std::io;
+use std::os::windows::io::FromRawHandle;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use windows_sys::Win32::Foundation::{
+    CloseHandle, ERROR_NO_DATA, ERROR_PIPE_CONNECTED, ERROR_PIPE_LISTENING, HANDLE,
+    INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{FlushFileBuffers, PIPE_ACCESS_DUPLEX};
+use windows_sys::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, SetNamedPipeHandleState, PIPE_NOWAIT,
+    PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT,
+};
+
+pub const PIPE_NAME: &str = r"\\.\pipe\darkclient";
+const PIPE_BUFFER_SIZE: u32 = 4096;
+
+// Start a named pipe server to listen for commands
+pub fn start_pipe_server() {
+    thread::spawn(move || {
+        info!("Listening on {}", PIPE_NAME);
+
+        while RUNNING.load(Ordering::SeqCst) {
+            // Every connection gets its own pipe instance, closed once the command is handled
+            let pipe = match create_pipe() {
+                Ok(pipe) => pipe,
+                Err(e) => {
+                    error!("Unable to create pipe {}: {}", PIPE_NAME, e);
+                    thread::sleep(Duration::from_millis(1000));
+                    continue;
+                }
+            };
+
+            match wait_for_client(pipe) {
+                Ok(true) => {
+                    let file = unsafe { File::from_raw_handle(pipe as _) };
+                    handle_connection(&file);
+                    unsafe {
+                        FlushFileBuffers(pipe);
+                        DisconnectNamedPipe(pipe);
+                    }
+                    // Dropping the file closes the handle
+                }
+                Ok(false) => unsafe {
+                    CloseHandle(pipe);
+                },
+                Err(e) => {
+                    error!("Error while waiting for pipe client: {}", e);
+                    unsafe { CloseHandle(pipe) };
+                    thread::sleep(Duration::from_millis(1000));
+                }
+            }
+        }
+    });
+}
+
+fn create_pipe() -> io::Result<HANDLE> {
+    let name: Vec<u16> = PIPE_NAME.encode_utf16().chain(Some(0)).collect();
+
+    // Non-blocking so the server can notice shutdown while no client is connected
+    let pipe = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            PIPE_ACCESS_DUPLEX,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_NOWAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            1,
+            PIPE_BUFFER_SIZE,
+            PIPE_BUFFER_SIZE,
+            0,
+            std::ptr::null(),
+        )
+    };
+
+    if pipe == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(pipe)
+}
+
+// Polls the pipe until a client connects (true) or the agent stops (false)
+fn wait_for_client(pipe: HANDLE) -> io::Result<bool> {
+    while RUNNING.load(Ordering::SeqCst) {
+        if unsafe { ConnectNamedPipe(pipe, std::ptr::null_mut()) } == 0 {
+            let error = io::Error::last_os_error();
+            match error.raw_os_error().map(|code| code as u32) {
+                // A client is connected, it may have already written its command and left
+                Some(ERROR_PIPE_CONNECTED) | Some(ERROR_NO_DATA) => {}
+                // No client yet, wait a bit
+                Some(ERROR_PIPE_LISTENING) => {
+                    thread::sleep(Duration::from_millis(100));
+                    continue;
+                }
+                _ => return Err(error),
+            }
+        }
+
+        // Blocking reads from now on, like an accepted TCP stream
+        let mode = PIPE_READMODE_BYTE | PIPE_WAIT;
+        if unsafe { SetNamedPipeHandleState(pipe, &mode, std::ptr::null(), std::ptr::null()) } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        return Ok(true);
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,3 @@
+// Environment variable set by the agent with the timestamp of a reload command, read by the
+// client to match its saved state with this reload. The client compiles this file too
+pub const RELOAD_TIMESTAMP_VAR: &str = "DARKCLIENT_RELOAD_TIMESTAMP";
@@ -0,0 +1,138 @@
+// Registry of the temporary copies of the client library made when reloading
+use log::{error, info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// Followed by the pid of the game that made the copy, so other instances' copies are left alone
+const TEMP_PREFIX: &str = "darkclient_";
+// A copy can stay locked for a moment after being unloaded (e.g. antivirus scans on Windows)
+const DELETE_ATTEMPTS: u32 = 3;
+const DELETE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+// Copies not deleted yet, including the one currently loaded
+static TEMP_LIBRARIES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// Path of the temporary copy of `filename` for the reload at `timestamp`
+pub fn temp_library_path(timestamp: u64, filename: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "{}{}_{}_{}",
+        TEMP_PREFIX,
+        std::process::id(),
+        timestamp,
+        filename
+    ))
+}
+
+// Tracks a copy so it's deleted once it's no longer loaded
+pub fn register(path: PathBuf) {
+    TEMP_LIBRARIES.lock().unwrap().push(path);
+}
+
+// Deletes every tracked copy except `keep`, the ones that can't be deleted are retried next time
+pub fn cleanup(keep: Option<&Path>) {
+    let mut libraries = TEMP_LIBRARIES.lock().unwrap();
+    libraries.retain(|path| {
+        if Some(path.as_path()) == keep {
+            return true;
+        }
+        !remove_with_retry(path)
+    });
+}
+
+// Deletes copies left behind by previous sessions that couldn't clean up (e.g. a crash).
+// Copies of running processes are kept, a loaded library can be unlinked on Linux and
+// another game instance may still be using or loading its copy
+pub fn remove_orphans() {
+    let entries = match std::fs::read_dir(std::env::temp_dir()) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Unable to list the temporary directory: {}", e);
+            return;
+        }
+    };
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let owner = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(temp_library_owner);
+
+        let orphaned = owner.is_some_and(|pid| !is_process_alive(pid));
+        if orphaned && std::fs::remove_file(&path).is_ok() {
+            removed += 1;
+        }
+    }
+
+    if removed > 0 {
+        info!("Removed {} orphaned temporary libraries", removed);
+    }
+}
+
+// Pid of the process that made the copy, for the names created by temp_library_path:
+// darkclient_<pid>_<timestamp>_<library file>
+fn temp_library_owner(name: &str) -> Option<u32> {
+    let (pid, rest) = name.strip_prefix(TEMP_PREFIX)?.split_once('_')?;
+    let (timestamp, filename) = rest.split_once('_')?;
+
+    let is_number = |value: &str| !value.is_empty() && value.chars().all(|c| c.is_ascii_digit());
+    let is_library = Path::new(filename)
+        .extension()
+        .is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION);
+    if !is_number(timestamp) || !is_library {
+        return None;
+    }
+    pid.parse().ok()
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    // Signal 0 only checks the process, EPERM means it exists but belongs to another user
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn is_process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, ERROR_ACCESS_DENIED, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    if pid == std::process::id() {
+        return true;
+    }
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        // Can't be opened: gone, or running with rights we don't have
+        if process.is_null() {
+            return std::io::Error::last_os_error().raw_os_error()
+                == Some(ERROR_ACCESS_DENIED as i32);
+        }
+        let mut exit_code = 0;
+        let alive =
+            GetExitCodeProcess(process, &mut exit_code) != 0 && exit_code == STILL_ACTIVE as u32;
+        CloseHandle(process);
+        alive
+    }
+}
+
+fn remove_with_retry(path: &Path) -> bool {
+    for attempt in 1..=DELETE_ATTEMPTS {
+        match std::fs::remove_file(path) {
+            Ok(_) => return true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return true,
+            Err(e) if attempt == DELETE_ATTEMPTS => {
+                warn!("Unable to delete temporary file {:?}: {}", path, e);
+            }
+            Err(_) => thread::sleep(DELETE_RETRY_DELAY),
+        }
+    }
+    false
+}
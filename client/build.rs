@@ -1,6 +1,8 @@
 // build.rs
-// This build script is only relevant on Windows with MSVC toolchain.
-// It finds the `jvm.lib` import library that is required to link JNI functions.
+// Embeds the build version, the crate version followed by the git hash when built from a
+// git checkout, as DARKCLIENT_BUILD_VERSION.
+// On Windows with MSVC toolchain it also finds the `jvm.lib` import library that is
+// required to link JNI functions.
 // On Linux, this is unnecessary because the linker can directly use libjvm.so.
 
 #[cfg(windows)]
@@ -8,6 +10,8 @@ fn main() {
     use std::path::PathBuf;
     use std::{env, fs};
 
+    emit_build_version();
+
     println!("cargo:rerun-if-env-changed=JAVA_HOME");
     println!("cargo:rerun-if-env-changed=JVM_LIB_DIR");
 
@@ -70,7 +74,37 @@ fn main() {
 
 #[cfg(not(windows))]
 fn main() {
-    // On non-Windows systems this build script does nothing.
+    // On non-Windows systems only the build version is needed
+    emit_build_version();
+}
+
+// Sets DARKCLIENT_BUILD_VERSION, e.g. `0.1.0 (1a2b3c4)`, or only the crate version when
+// git or the repository isn't available
+fn emit_build_version() {
+    let version = std::env::var("CARGO_PKG_VERSION").unwrap_or_default();
+    let build_version = match git(&["rev-parse", "--short", "HEAD"]) {
+        Some(hash) => format!("{} ({})", version, hash),
+        None => version,
+    };
+    println!("cargo:rustc-env=DARKCLIENT_BUILD_VERSION={}", build_version);
+
+    // Rebuild on commits and checkouts, the hash would be stale otherwise
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/refs/heads", git_dir);
+        println!("cargo:rerun-if-changed={}/packed-refs", git_dir);
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+// Output of a git command, None if it can't be run or fails
+fn git(args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_string()).filter(|output| !output.is_empty())
 }
 
 #[cfg(windows)]
@@ -1,26 +1,95 @@
-use crate::module::{Module, ModuleType};
+use crate::config::ClientConfig;
+use crate::gui::Theme;
+use crate::logging::log_limited;
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::client::minecraft::Minecraft;
+use crate::mapping::entity;
+use crate::mapping::entity::player::PlayerState;
+use crate::mapping::Mapping;
+use crate::metrics;
+use crate::module::{set_enabled_by_user, Module, ModuleCategory, ModuleType};
+use crate::notification;
+use crate::packet::{self, ChatListener};
+use crate::render::{self, RenderContext, RenderSettings};
+use indexmap::IndexMap;
+use jni::objects::GlobalRef;
 use jni::sys::{jsize, JNI_GetCreatedJavaVMs, JNI_OK};
-use jni::{JNIEnv, JavaVM};
-use log::error;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use jni::{AttachGuard, JNIEnv, JavaVM};
+use log::{debug, error, info, warn, Level};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+// Capacity of the local reference frame pushed around each module callback
+const LOCAL_FRAME_CAPACITY: i32 = 64;
+// How often cleanup checks whether the module callbacks in progress have returned
+const MODULE_CALLS_POLL: Duration = Duration::from_millis(10);
+// SoundEvents field played when a module is toggled with its keybind
+const TOGGLE_SOUND: &str = "UI_BUTTON_CLICK";
+/// Shown instead of enabling modules when the mapping lacks core classes
+pub const UNSUPPORTED_VERSION: &str = "Unsupported version — modules disabled";
+// Why a module acting on the player can't be enabled at the title screen or in menus
+const NO_PLAYER: &str = "Join a world first";
 
 #[derive(Debug)]
 pub struct DarkClient {
     pub(crate) jvm: Arc<JavaVM>,
-    pub(crate) modules: Arc<RwLock<HashMap<String, Arc<Mutex<ModuleType>>>>>,
+    // Registered modules by id, in registration order
+    pub(crate) modules: Arc<RwLock<IndexMap<String, Arc<Mutex<ModuleType>>>>>,
+    // Registered modules in the order they tick and render, see `ModuleData::priority`
+    tick_order: RwLock<Vec<Arc<Mutex<ModuleType>>>>,
+    // Whether a click is played when a module is toggled with its keybind
+    toggle_sound: AtomicBool,
+    theme: RwLock<Theme>,
+    // Categories moved by the user, shown first in this order, see `sort_by_category`
+    category_order: RwLock<Vec<ModuleCategory>>,
+    render_settings: RwLock<RenderSettings>,
+    // Player state of the current tick, None if it couldn't be read
+    player_state: RwLock<Option<PlayerState>>,
+    // Player object the state was last read from, to notice when the game replaces it
+    current_player: Mutex<Option<GlobalRef>>,
+    // Threads currently calling into modules, cleanup waits for them before unhooking
+    module_calls: AtomicUsize,
+    // Set when the mapping lacks core classes, no module can be enabled then
+    unsupported_version: AtomicBool,
+    // Ids of the modules whose classes the mapping lacks, with the reason shown to the user
+    unavailable_modules: RwLock<HashMap<&'static str, String>>,
+    // Ids of the modules that need a player, see `Module::requires_player`
+    player_modules: RwLock<HashSet<&'static str>>,
+}
+
+/// Held by a thread while it calls into modules, see `DarkClient::begin_module_calls`
+#[must_use]
+pub struct ModuleCallGuard<'a> {
+    module_calls: &'a AtomicUsize,
+}
+
+impl Drop for ModuleCallGuard<'_> {
+    fn drop(&mut self) {
+        self.module_calls.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl DarkClient {
-    pub fn instance() -> &'static DarkClient {
-        static INSTANCE: OnceLock<Arc<DarkClient>> = OnceLock::new();
-
-        INSTANCE.get_or_init(|| unsafe {
-            Arc::new(DarkClient::new().unwrap_or_else(|e| {
-                error!("Failed to create DarkClient: {}", e);
-                panic!("Failed to create DarkClient: {}", e);
-            }))
-        })
+    /// The client, created on first use. Fails without a JVM, the failure isn't cached
+    /// and the next call tries again
+    pub fn instance() -> anyhow::Result<&'static DarkClient> {
+        static INSTANCE: OnceLock<DarkClient> = OnceLock::new();
+        static INIT_LOCK: Mutex<()> = Mutex::new(());
+
+        if let Some(client) = INSTANCE.get() {
+            return Ok(client);
+        }
+
+        let _init = INIT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have finished while this one waited for the lock
+        if let Some(client) = INSTANCE.get() {
+            return Ok(client);
+        }
+
+        let client = unsafe { DarkClient::new()? };
+        Ok(INSTANCE.get_or_init(|| client))
     }
 
     pub unsafe fn new() -> anyhow::Result<Self> {
@@ -38,80 +107,555 @@ impl DarkClient {
 
         Ok(DarkClient {
             jvm: java_vm,
-            modules: Arc::new(RwLock::new(HashMap::new())),
+            modules: Arc::new(RwLock::new(IndexMap::new())),
+            tick_order: RwLock::new(Vec::new()),
+            toggle_sound: AtomicBool::new(true),
+            theme: RwLock::new(Theme::default()),
+            category_order: RwLock::new(Vec::new()),
+            unsupported_version: AtomicBool::new(false),
+            unavailable_modules: RwLock::new(HashMap::new()),
+            player_modules: RwLock::new(HashSet::new()),
+            render_settings: RwLock::new(RenderSettings::default()),
+            player_state: RwLock::new(None),
+            current_player: Mutex::new(None),
+            module_calls: AtomicUsize::new(0),
         })
     }
 
+    /// JNI environment of the current thread, attaching it as a daemon thread if needed.
+    /// The JVM doesn't wait for daemon threads when exiting, so the tick, keyboard and GUI
+    /// threads never keep the game from closing, and the attach is permanent so the
+    /// threads don't pay for it on every call. Use this unless `get_env_non_daemon` is needed
     pub fn get_env(&'_ self) -> jni::errors::Result<JNIEnv<'_>> {
-        //self.jvm.attach_current_thread()
         self.jvm.attach_current_thread_as_daemon()
     }
 
+    /// JNI environment of the current thread attached as a regular thread until the guard
+    /// is dropped. The JVM waits for regular threads before exiting, so work done with it,
+    /// e.g. saving through Java, isn't cut short by a shutdown. Keep it brief, the game
+    /// can't exit meanwhile. A thread already attached, as a daemon or not, keeps its mode
+    pub fn get_env_non_daemon(&self) -> jni::errors::Result<AttachGuard<'_>> {
+        self.jvm.attach_current_thread()
+    }
+
+    /// Runs `f` inside a new JNI local frame, so every local reference it creates
+    /// is freed on return instead of accumulating on a thread that never leaves native code
+    pub fn with_local_frame<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce() -> anyhow::Result<T>,
+    {
+        self.get_env()?
+            .with_local_frame(LOCAL_FRAME_CAPACITY, |_| f())
+    }
+
+    /// Runs `f` on the game's render thread, see `render::run_on_render_thread`
+    pub fn run_on_render_thread<F>(&self, f: F) -> anyhow::Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        render::run_on_render_thread(f)
+    }
+
+    /// Calls `listener` with every chat message received, see `packet::register_chat_listener`
+    pub fn register_chat_listener(&self, listener: ChatListener) {
+        packet::register_chat_listener(listener);
+    }
+
+    /// Exports the state of every module as a shareable string
+    pub fn export_config(&self) -> anyhow::Result<String> {
+        ClientConfig::capture(self).encode()
+    }
+
+    /// Imports a string created by `export_config`, nothing is applied if it's invalid
+    pub fn import_config(&self, value: &str) -> anyhow::Result<()> {
+        // Applying starts and stops modules, not while the client is cleaning up
+        let Some(_calls) = self.begin_module_calls() else {
+            return Err(anyhow::anyhow!("Client is shutting down"));
+        };
+        let config = ClientConfig::decode(value)?;
+        config.validate(self)?;
+        config.apply(self);
+        Ok(())
+    }
+
+    pub fn is_toggle_sound_enabled(&self) -> bool {
+        self.toggle_sound.load(Ordering::Relaxed)
+    }
+
+    pub fn set_toggle_sound_enabled(&self, enabled: bool) {
+        self.toggle_sound.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Look of the client GUI
+    pub fn theme(&self) -> Theme {
+        *self.theme.read().unwrap()
+    }
+
+    pub fn set_theme(&self, theme: Theme) {
+        *self.theme.write().unwrap() = theme;
+    }
+
+    /// Order of the category tabs and of the in-game menu set by the user
+    pub fn category_order(&self) -> Vec<ModuleCategory> {
+        self.category_order.read().unwrap().clone()
+    }
+
+    pub fn set_category_order(&self, order: Vec<ModuleCategory>) {
+        *self.category_order.write().unwrap() = order;
+    }
+
+    /// Visual settings shared by the render modules
+    pub fn render_settings(&self) -> RenderSettings {
+        self.render_settings.read().unwrap().clone()
+    }
+
+    pub fn set_render_settings(&self, settings: RenderSettings) {
+        *self.render_settings.write().unwrap() = settings;
+    }
+
+    /// Plays the feedback click for a module toggled with its keybind, if enabled.
+    /// Disabling uses a lower pitch so the two can be told apart without looking
+    pub fn play_toggle_sound(&self, enabled: bool) {
+        if !self.is_toggle_sound_enabled() {
+            return;
+        }
+
+        let pitch = if enabled { 1.0 } else { 0.7 };
+        let result = self.run_on_render_thread(move || {
+            let result = Minecraft::instance()
+                .and_then(|minecraft| minecraft.get_sound_manager())
+                .and_then(|sound_manager| sound_manager.play_ui_sound(TOGGLE_SOUND, pitch));
+            if let Err(e) = result {
+                error!("Failed to play toggle sound: {}", e);
+            }
+        });
+        if let Err(e) = result {
+            error!("Failed to schedule toggle sound: {}", e);
+        }
+    }
+
+    /// Marks the start of calls into modules from the current thread, until the guard is
+    /// dropped. None once cleanup began, so no module is started or ticked after it
+    pub fn begin_module_calls(&self) -> Option<ModuleCallGuard<'_>> {
+        // Counted before checking the flag, so cleanup either sees the call or the
+        // call sees that cleanup began
+        self.module_calls.fetch_add(1, Ordering::SeqCst);
+        let guard = ModuleCallGuard {
+            module_calls: &self.module_calls,
+        };
+        crate::RUNNING.load(Ordering::SeqCst).then_some(guard)
+    }
+
+    /// Waits for the module calls that began before cleanup to return, false on timeout
+    pub fn wait_for_module_calls(&self, timeout: Duration) -> bool {
+        let started = Instant::now();
+        while self.module_calls.load(Ordering::SeqCst) > 0 {
+            if started.elapsed() >= timeout {
+                return false;
+            }
+            std::thread::sleep(MODULE_CALLS_POLL);
+        }
+        true
+    }
+
+    /// Checks once the game is joined that the mapping has the classes every module
+    /// needs. If not, the version is unsupported and no module can be enabled, which
+    /// is reported once instead of every module failing on its own
+    pub fn check_mapping_health(&self, mapping: &Mapping) {
+        match mapping.validate(&MinecraftClassType::CORE) {
+            Ok(missing) if missing.is_empty() => {}
+            Ok(missing) => {
+                error!(
+                    "{}, missing classes: {}",
+                    UNSUPPORTED_VERSION,
+                    class_names(&missing)
+                );
+                self.unsupported_version.store(true, Ordering::SeqCst);
+                notification::notify_error(UNSUPPORTED_VERSION);
+            }
+            Err(e) => error!("Failed to check the mapping: {}", e),
+        }
+    }
+
+    /// Whether the mapping lacks core classes, see `check_mapping_health`
+    pub fn is_version_unsupported(&self) -> bool {
+        self.unsupported_version.load(Ordering::SeqCst)
+    }
+
+    /// Why the module with the id `module_id` can't be enabled, None if it can
+    pub fn activation_blocked(&self, module_id: &str) -> Option<String> {
+        if self.is_version_unsupported() {
+            return Some(UNSUPPORTED_VERSION.to_string());
+        }
+        if let Some(reason) = self.unavailable_modules.read().unwrap().get(module_id) {
+            return Some(reason.clone());
+        }
+        if self.player_modules.read().unwrap().contains(module_id) && !self.has_player() {
+            return Some(NO_PLAYER.to_string());
+        }
+        None
+    }
+
+    // Whether the last tick found a player, i.e. the game is in a world
+    fn has_player(&self) -> bool {
+        self.player_state.read().unwrap().is_some()
+    }
+
+    // Keeps a module disabled if the mapping lacks the classes it uses
+    fn check_module_classes(&self, module: &mut ModuleType) {
+        let required = module.required_classes();
+        if required.is_empty() {
+            return;
+        }
+
+        let missing = match Minecraft::instance()
+            .and_then(|minecraft| minecraft.get_mapping().validate(required))
+        {
+            Ok(missing) if missing.is_empty() => return,
+            Ok(missing) => missing,
+            Err(e) => {
+                error!(
+                    "Failed to check the classes of module {}: {}",
+                    module.get_module_data().name,
+                    e
+                );
+                return;
+            }
+        };
+
+        let reason = format!("Missing classes: {}", class_names(&missing));
+        warn!(
+            "Module {} unavailable with this mapping. {}",
+            module.get_module_data().name,
+            reason
+        );
+        self.unavailable_modules
+            .write()
+            .unwrap()
+            .insert(module.get_module_data().id, reason);
+        module.get_module_data_mut().set_enabled(false);
+    }
+
+    /// Registers a module, starting it right away if it's registered enabled so it
+    /// behaves like a module enabled later from the GUI or a keybind
     pub fn register_module(&self, module: Arc<Mutex<dyn Module + Send + Sync>>) {
-        let module_name = module.lock().unwrap().get_module_data().name.clone();
-        self.modules.write().unwrap().insert(module_name, module);
+        {
+            let mut module = module.lock().unwrap();
+            self.check_module_classes(&mut *module);
+            if module.requires_player() {
+                self.player_modules
+                    .write()
+                    .unwrap()
+                    .insert(module.get_module_data().id);
+            }
+            if self.is_version_unsupported() {
+                module.get_module_data_mut().set_enabled(false);
+            }
+            Self::start_registered(&mut *module);
+        }
+
+        let module_id = module.lock().unwrap().get_module_data().id;
+        let mut modules = self.modules.write().unwrap();
+        modules.insert(module_id.to_string(), module);
+
+        let mut order: Vec<_> = modules
+            .values()
+            .map(|module| {
+                let priority = module.lock().unwrap().get_module_data().priority;
+                (priority, module.clone())
+            })
+            .collect();
+        sort_by_priority(&mut order);
+        *self.tick_order.write().unwrap() = order.into_iter().map(|(_, m)| m).collect();
+    }
+
+    // A module failing to start is registered disabled, the tick loop would otherwise
+    // tick a module whose side effects never ran
+    fn start_registered(module: &mut ModuleType) {
+        if !module.get_module_data().enabled {
+            return;
+        }
+
+        match module.on_start() {
+            Ok(_) => info!("{} enabled at registration", module.get_module_data().name),
+            Err(e) => {
+                error!(
+                    "Failed to start module {} enabled at registration, disabling: {}",
+                    module.get_module_data().name,
+                    e
+                );
+                module.get_module_data_mut().set_enabled(false);
+            }
+        }
+    }
+
+    /// State of the player read at the start of the current tick, movement modules should
+    /// use it instead of querying the player again. None if the player couldn't be read
+    pub fn player_state(&self) -> Option<PlayerState> {
+        *self.player_state.read().unwrap()
+    }
+
+    /// Reads the player state of this tick, done by the tick thread and once at startup.
+    /// True if the game replaced the player since the last read
+    pub fn update_player_state(&self) -> bool {
+        let result = self.with_local_frame(|| {
+            let player = match Minecraft::instance().and_then(|minecraft| minecraft.player()) {
+                Ok(player) => player,
+                Err(e) => {
+                    // Left the world, the next player counts as a new one
+                    *self.current_player.lock().unwrap() = None;
+                    return Err(e);
+                }
+            };
+            let changed = self.replace_player(&player.jni_ref)?;
+            Ok((player.get_state()?, changed))
+        });
+
+        let (state, changed) = match result {
+            Ok((state, changed)) => (Some(state), changed),
+            Err(e) => {
+                debug!("Failed to read player state: {}", e);
+                (None, false)
+            }
+        };
+        *self.player_state.write().unwrap() = state;
+        changed
+    }
+
+    // Remembers `player` as the current player, true if it's another object than the last
+    // one. The game creates a new player on every join, respawn and dimension change
+    fn replace_player(&self, player: &GlobalRef) -> anyhow::Result<bool> {
+        let mut current = self.current_player.lock().unwrap();
+        if let Some(current) = current.as_ref() {
+            if self.get_env()?.is_same_object(current, player)? {
+                return Ok(false);
+            }
+        }
+        *current = Some(player.clone());
+        Ok(true)
+    }
+
+    // Chat is read by the packet hook, which has to follow the player to every new connection
+    fn update_chat_hook(&self) {
+        if !packet::has_chat_listeners() || self.player_state.read().unwrap().is_none() {
+            return;
+        }
+        let result =
+            self.with_local_frame(|| packet::ensure_packet_hook(&Minecraft::instance()?.player()?));
+        if let Err(e) = result {
+            debug!("Failed to hook the connection for chat: {}", e);
+        }
     }
 
     pub fn tick(&self) {
-        let modules = self.modules.read().unwrap();
-        for module in modules.values() {
-            let module = module.lock().unwrap();
-            if module.get_module_data().enabled {
-                match module.on_tick() {
+        // Values cached by entities during the previous tick are stale now
+        entity::invalidate_cache();
+        let Some(_calls) = self.begin_module_calls() else {
+            return;
+        };
+        let player_changed = self.update_player_state();
+        self.update_chat_hook();
+
+        let has_player = self.has_player();
+        let modules = self.tick_order.read().unwrap();
+        for module in modules.iter() {
+            let mut module = module.lock().unwrap();
+            // Modules acting on the player stay enabled and resume in the next world
+            if module.get_module_data().enabled && (has_player || !module.requires_player()) {
+                if player_changed {
+                    if let Err(e) = self.with_local_frame(|| module.on_player_changed()) {
+                        log_limited(
+                            Level::Error,
+                            &format!(
+                                "Failed to update module {} for the new player: {}",
+                                module.get_module_data().name,
+                                e
+                            ),
+                        );
+                    }
+                }
+
+                // The clock is only read while metrics are enabled
+                let started = metrics::is_enabled().then(Instant::now);
+                let result = self.with_local_frame(|| module.on_tick());
+                if let Some(started) = started {
+                    metrics::record_tick(&module.get_module_data().name, started.elapsed());
+                }
+
+                match result {
                     Ok(_) => {}
                     Err(e) => {
-                        error!(
-                            "Failed to tick module {}, disabling. {}",
-                            module.get_module_data().name,
-                            e
+                        // A module failing as soon as it's enabled again logs every time
+                        log_limited(
+                            Level::Error,
+                            &format!(
+                                "Failed to tick module {}, disabling. {}",
+                                module.get_module_data().name,
+                                e
+                            ),
                         );
-                        match module.on_stop() {
-                            Ok(_) => {}
-                            Err(_) => {
-                                error!(
-                                    "Failed to stop module {} after an error when ticking",
-                                    module.get_module_data().name
-                                );
-                                panic!(
-                                    "Failed to stop module {} after an error when ticking",
-                                    module.get_module_data().name
-                                );
-                            }
+                        // The module is disabled even if it fails to stop, the game
+                        // keeps running rather than panicking the tick thread
+                        if let Err(e) = module.on_stop() {
+                            log_limited(
+                                Level::Error,
+                                &format!(
+                                    "Failed to stop module {} after an error when ticking: {}",
+                                    module.get_module_data().name,
+                                    e
+                                ),
+                            );
                         }
+                        module.get_module_data_mut().set_enabled(false);
                     }
                 }
             }
         }
     }
+
+    pub fn enabled_module_names(&self) -> Vec<String> {
+        self.modules
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|module| {
+                let module = module.lock().unwrap();
+                let module_data = module.get_module_data();
+                module_data.enabled.then(|| module_data.name.clone())
+            })
+            .collect()
+    }
+
+    /// Enables or disables the module with the id `id`, ignoring case, and returns whether
+    /// it's now enabled. The module keeps its state if starting or stopping it fails
+    pub fn toggle_module(&self, id: &str) -> anyhow::Result<bool> {
+        let Some(_calls) = self.begin_module_calls() else {
+            return Err(anyhow::anyhow!("Client is shutting down"));
+        };
+
+        let module = self
+            .modules
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(module_id, _)| module_id.eq_ignore_ascii_case(id))
+            .map(|(_, module)| module.clone())
+            .ok_or_else(|| anyhow::anyhow!("Unknown module {}", id))?;
+        let mut module = module.lock().unwrap();
+
+        let name = module.get_module_data().name.clone();
+        let enabled = !module.get_module_data().enabled;
+        if enabled {
+            if let Some(reason) = self.activation_blocked(module.get_module_data().id) {
+                return Err(anyhow::anyhow!("{} can't be enabled: {}", name, reason));
+            }
+            module
+                .on_start()
+                .map_err(|e| anyhow::anyhow!("Failed to start module {}: {}", name, e))?;
+        } else {
+            module
+                .on_stop()
+                .map_err(|e| anyhow::anyhow!("Failed to stop module {}: {}", name, e))?;
+        }
+        set_enabled_by_user(&mut *module, enabled);
+        info!("{} {}", name, if enabled { "enabled" } else { "disabled" });
+        self.play_toggle_sound(enabled);
+
+        Ok(enabled)
+    }
+
+    /// Stops every enabled module without disabling it, so nothing a module changed in
+    /// the game outlives the library. They stay enabled in the saved reload state and the
+    /// next library starts them again
+    pub fn stop_enabled_modules(&self) {
+        for module in self.modules.read().unwrap().values() {
+            let module = module.lock().unwrap();
+            if !module.get_module_data().enabled {
+                continue;
+            }
+            if let Err(e) = self.with_local_frame(|| module.on_stop()) {
+                error!(
+                    "Failed to stop module {}: {}",
+                    module.get_module_data().name,
+                    e
+                );
+            }
+        }
+    }
+
+    pub fn render(&self, context: &RenderContext) {
+        let Some(_calls) = self.begin_module_calls() else {
+            return;
+        };
+        let has_player = self.has_player();
+        let modules = self.tick_order.read().unwrap();
+        for module in modules.iter() {
+            let mut module = module.lock().unwrap();
+            if !module.get_module_data().enabled || (!has_player && module.requires_player()) {
+                continue;
+            }
+
+            if let Err(e) = module.on_render(context) {
+                // Rendering happens every frame, disable the module to avoid spamming errors
+                error!(
+                    "Failed to render module {}, disabling. {}",
+                    module.get_module_data().name,
+                    e
+                );
+                if let Err(e) = module.on_stop() {
+                    error!(
+                        "Failed to stop module {} after an error when rendering: {}",
+                        module.get_module_data().name,
+                        e
+                    );
+                }
+                module.get_module_data_mut().set_enabled(false);
+            }
+        }
+    }
+}
+
+fn class_names(class_types: &[MinecraftClassType]) -> String {
+    class_types
+        .iter()
+        .map(|class_type| class_type.get_name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Higher priority first, the sort is stable so modules of equal priority keep their
+// registration order
+fn sort_by_priority<T>(entries: &mut [(i32, T)]) {
+    entries.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
 }
 
 // Module for handling keyboard inputs
 pub mod keyboard {
     use super::*;
-    use crate::mapping::client::minecraft::Minecraft;
-    use jni::objects::JValue;
-    use jni::sys::jlong;
+    use crate::module::KeyboardKey;
+    use crate::overlay;
     use log::info;
     use std::collections::HashSet;
     use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc;
     use std::thread;
     use std::time::Duration;
 
+    // How long a poll waits for the render thread to read the keys, normally one frame
+    const KEY_QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
     static RUNNING: OnceLock<AtomicBool> = OnceLock::new();
+    static KEYBOARD_THREAD: Mutex<Option<thread::JoinHandle<()>>> = Mutex::new(None);
 
     pub fn start_keyboard_handler() {
         if RUNNING.get().is_none() {
             RUNNING.set(AtomicBool::new(true)).unwrap();
         }
-        thread::spawn(|| {
-            let minecraft = Minecraft::instance();
-            let client = DarkClient::instance();
-            let mut env = client.get_env().unwrap();
-
-            let glfw_window = match minecraft.window.get_window() {
-                Ok(window) => window,
+        let handle = thread::spawn(|| {
+            let client = match DarkClient::instance() {
+                Ok(client) => client,
                 Err(e) => {
-                    error!("Failed to get GLFW window: {}", e);
+                    error!("Keyboard handler not started: {}", e);
                     return;
                 }
             };
@@ -124,50 +668,88 @@ pub mod keyboard {
             {
                 thread::sleep(Duration::from_millis(100));
 
-                client.modules.read().unwrap().values().for_each(|module| {
-                    let mut module = module.lock().unwrap();
-                    let module_data = module.get_module_data();
-                    let key = module_data.key_bind as i32;
+                // The poll creates local references every iteration, free them each time
+                let result = client.with_local_frame(|| poll_keys(&mut keys));
+                if let Err(e) = result {
+                    log_limited(Level::Error, &format!("Failed to poll keyboard: {}", e));
+                }
+            }
+        });
+        *KEYBOARD_THREAD.lock().unwrap() = Some(handle);
+    }
 
-                    if is_key_down(&mut env, glfw_window, key) {
-                        if !keys.contains(&key) {
-                            keys.insert(key);
+    fn poll_keys(keys: &mut HashSet<i32>) -> anyhow::Result<()> {
+        let client = DarkClient::instance()?;
+        let Some(_calls) = client.begin_module_calls() else {
+            return Ok(());
+        };
 
-                            let enabled = !module_data.enabled;
-                            info!(
-                                "{} {}",
-                                module_data.name,
-                                if enabled { "enabled" } else { "disabled" }
-                            );
-                            if enabled {
-                                match module.on_start() {
-                                    Ok(_) => {}
-                                    Err(e) => error!(
-                                        "Failed to start module {}: {}",
-                                        module.get_module_data().name,
-                                        e
-                                    ),
-                                }
-                            } else {
-                                match module.on_stop() {
-                                    Ok(_) => {}
-                                    Err(e) => error!(
-                                        "Failed to stop module {}: {}",
-                                        module.get_module_data().name,
-                                        e
-                                    ),
-                                }
-                            }
-                            module.get_module_data_mut().set_enabled(enabled);
-                        }
-                    } else {
-                        keys.remove(&key);
-                    }
-                });
+        let mut key_binds: Vec<i32> = client
+            .modules
+            .read()
+            .unwrap()
+            .values()
+            .map(|module| module.lock().unwrap().get_module_data().key_bind)
+            .filter(|key_bind| *key_bind != KeyboardKey::KeyNone)
+            .map(|key_bind| key_bind as i32)
+            .collect();
+        key_binds.extend(overlay::watched_keys());
+        key_binds.sort_unstable();
+        key_binds.dedup();
+        let pressed = read_pressed_keys(key_binds)?;
+
+        // Toggled once the modules are unlocked, toggle_module locks the module itself
+        let mut toggled = Vec::new();
+        client.modules.read().unwrap().values().for_each(|module| {
+            let module = module.lock().unwrap();
+            let module_data = module.get_module_data();
+            if module_data.key_bind == KeyboardKey::KeyNone {
+                return;
+            }
+            let key = module_data.key_bind as i32;
+
+            if pressed.contains(&key) {
+                if keys.insert(key) {
+                    toggled.push((module_data.id, module_data.key_bind));
+                }
+            } else {
+                keys.remove(&key);
             }
         });
+        for (id, key_bind) in toggled {
+            match client.toggle_module(id) {
+                Ok(_) => info!("Toggled {} with {}", id, key_bind.display_name()),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        overlay::handle_keys(&pressed);
+
+        Ok(())
     }
 
+    // GLFW may only be called from the main thread, which is the game's render thread,
+    // other threads can crash or read garbage with some drivers. The keys are read there
+    // during the next frame and sent back to the keyboard thread
+    fn read_pressed_keys(keys: Vec<i32>) -> anyhow::Result<HashSet<i32>> {
+        let (sender, receiver) = mpsc::channel();
+        DarkClient::instance()?.run_on_render_thread(move || {
+            check_window_handle();
+            let pressed: HashSet<i32> = keys.into_iter().filter(|&key| is_key_down(key)).collect();
+            // The keyboard thread stopped waiting if this fails, nothing to do
+            let _ = sender.send(pressed);
+        })?;
+
+        match receiver.recv_timeout(KEY_QUERY_TIMEOUT) {
+            Ok(pressed) => Ok(pressed),
+            Err(_) => Err(anyhow::anyhow!(
+                "Render thread didn't read the keys within {:?}",
+                KEY_QUERY_TIMEOUT
+            )),
+        }
+    }
+
+    /// Stops the keyboard handler and waits for its thread, so no module is toggled
+    /// by a keybind once this returns
     pub fn stop_keyboard_handler() {
         if RUNNING.get().is_none() {
             return;
@@ -176,19 +758,58 @@ pub mod keyboard {
             .get()
             .unwrap()
             .store(false, std::sync::atomic::Ordering::Relaxed);
+
+        let handle = KEYBOARD_THREAD.lock().unwrap().take();
+        if let Some(handle) = handle {
+            if let Err(e) = handle.join() {
+                error!("Error while waiting for keyboard thread: {:?}", e);
+            }
+        }
     }
 
-    fn is_key_down(env: &mut JNIEnv, glfw_window: jlong, key: i32) -> bool {
-        let glfw = env.find_class("org/lwjgl/glfw/GLFW").unwrap();
-        env.call_static_method(
-            glfw,
-            "glfwGetKey",
-            "(JI)I",
-            &[JValue::Long(glfw_window), JValue::Int(key)],
-        )
-        .unwrap()
-        .i()
-        .unwrap()
-            == 1
+    // Toggling fullscreen can recreate the GLFW window, on versions where the key reads
+    // pass the cached handle it's refreshed first so they follow the new window
+    fn check_window_handle() {
+        let recreated = Minecraft::instance().and_then(|minecraft| {
+            if !minecraft.window.key_reads_use_handle()? {
+                return Ok(false);
+            }
+            minecraft.window.check_handle()
+        });
+        match recreated {
+            Ok(true) => info!("Game window recreated, window handle refreshed"),
+            Ok(false) => {}
+            Err(e) => log_limited(
+                Level::Warn,
+                &format!("Failed to read the window handle: {}", e),
+            ),
+        }
+    }
+
+    // A key that can't be read counts as released instead of killing the keyboard thread
+    // Polled for every keybind 10 times a second, a failure would flood the log
+    fn is_key_down(key: i32) -> bool {
+        Minecraft::instance()
+            .and_then(|minecraft| minecraft.window.is_key_down(key))
+            .unwrap_or_else(|e| {
+                log_limited(Level::Warn, &format!("Failed to read key state: {}", e));
+                false
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_order_by_priority_then_registration() {
+        let mut modules = vec![(0, "Reach"), (0, "Fly"), (10, "Hud"), (-5, "Blink")];
+        sort_by_priority(&mut modules);
+
+        assert_eq!(
+            modules,
+            vec![(10, "Hud"), (0, "Reach"), (0, "Fly"), (-5, "Blink")]
+        );
     }
 }
@@ -0,0 +1,656 @@
+use crate::client::DarkClient;
+use crate::gui::Theme;
+use crate::mapping::client::minecraft::Minecraft;
+use crate::module::{KeyboardKey, ModuleCategory, ModuleSetting, ModuleType, MAX_TEXT_LENGTH};
+use crate::render::RenderSettings;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// The agent's definition of the reload variable, so both sides agree on its name
+#[path = "../../agent_loader/src/reload_env.rs"]
+mod reload_env;
+
+// Folder of the client files, inside the game directory
+const CONFIG_DIRECTORY: &str = "config/darkclient";
+
+/// Folder where the client stores its files, `config/darkclient` in the game directory.
+/// Relative to the working directory if the game directory can't be read
+pub fn config_directory() -> PathBuf {
+    match Minecraft::instance().and_then(|minecraft| minecraft.get_game_directory()) {
+        Ok(game_directory) => game_directory.join(CONFIG_DIRECTORY),
+        Err(e) => {
+            warn!(
+                "Failed to get the game directory, using the working directory: {}",
+                e
+            );
+            PathBuf::from(CONFIG_DIRECTORY)
+        }
+    }
+}
+
+/// Serializable state of a single module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleConfig {
+    pub enabled: bool,
+    pub settings: Vec<ModuleSetting>,
+    #[serde(default)]
+    pub notify_on_toggle: bool,
+    // Missing from configs saved before keybinds could be changed, the module keeps its own
+    #[serde(default)]
+    pub key_bind: Option<KeyboardKey>,
+    // Category the module is shown in, which the user can change
+    #[serde(default)]
+    pub category: Option<ModuleCategory>,
+}
+
+/// Serializable state of every registered module, keyed by module id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientConfig {
+    pub modules: HashMap<String, ModuleConfig>,
+    // Global settings, missing from configs saved before they existed
+    #[serde(default)]
+    pub toggle_sound: Option<bool>,
+    #[serde(default)]
+    pub theme: Option<Theme>,
+    #[serde(default)]
+    pub render_settings: Option<Vec<ModuleSetting>>,
+    #[serde(default)]
+    pub category_order: Option<Vec<ModuleCategory>>,
+}
+
+impl ClientConfig {
+    /// Captures the current state of all the modules registered in the client
+    pub fn capture(client: &DarkClient) -> ClientConfig {
+        let modules = client
+            .modules
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, module)| {
+                let module = module.lock().unwrap();
+                let module_data = module.get_module_data();
+                (
+                    name.clone(),
+                    ModuleConfig {
+                        enabled: module_data.enabled,
+                        settings: module_data.settings.clone(),
+                        notify_on_toggle: module_data.notify_on_toggle,
+                        key_bind: Some(module_data.key_bind),
+                        category: Some(module_data.category.clone()),
+                    },
+                )
+            })
+            .collect();
+
+        ClientConfig {
+            modules,
+            toggle_sound: Some(client.is_toggle_sound_enabled()),
+            theme: Some(client.theme()),
+            render_settings: Some(client.render_settings().settings),
+            category_order: Some(client.category_order()),
+        }
+    }
+
+    /// Encodes the configuration as a base64 string that can be shared
+    pub fn encode(&self) -> anyhow::Result<String> {
+        Ok(BASE64.encode(serde_json::to_string(self)?))
+    }
+
+    /// Decodes a configuration encoded with `encode`
+    pub fn decode(value: &str) -> anyhow::Result<ClientConfig> {
+        let json = BASE64
+            .decode(value.trim())
+            .map_err(|e| anyhow::anyhow!("Config is not valid base64: {}", e))?;
+        serde_json::from_slice(&json).map_err(|e| anyhow::anyhow!("Config is not valid: {}", e))
+    }
+
+    /// Checks that the configuration can be applied to the registered modules
+    pub fn validate(&self, client: &DarkClient) -> anyhow::Result<()> {
+        let modules = client.modules.read().unwrap();
+        if !self.modules.keys().any(|name| modules.contains_key(name)) {
+            return Err(anyhow::anyhow!("Config doesn't contain any known module"));
+        }
+
+        for (name, module_config) in &self.modules {
+            let Some(module) = modules.get(name) else {
+                continue;
+            };
+            let module = module.lock().unwrap();
+            let module_data = module.get_module_data();
+
+            for setting in &module_config.settings {
+                let valid = match (module_data.get_setting(setting.name()), setting) {
+                    (
+                        Some(ModuleSetting::Choice { options, .. }),
+                        ModuleSetting::Choice { value, .. },
+                    ) => *value < options.len(),
+                    (
+                        Some(ModuleSetting::IntSlider { min, max, .. }),
+                        ModuleSetting::IntSlider { value, .. },
+                    ) => (*min..=*max).contains(value),
+                    (_, ModuleSetting::Slider { value, .. }) => value.is_finite(),
+                    (_, ModuleSetting::Color { value, .. }) => value.iter().all(|c| c.is_finite()),
+                    (_, ModuleSetting::Text { value, .. }) => {
+                        *value == ModuleSetting::fit_text(value)
+                    }
+                    _ => true,
+                };
+                if !valid {
+                    return Err(anyhow::anyhow!(
+                        "Invalid value for setting {} of module {}",
+                        setting.name(),
+                        name
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies this configuration to the registered modules, starting or stopping them as needed.
+    /// Settings are fitted to the current module definitions, out of range values are
+    /// corrected and logged
+    pub fn apply(&self, client: &DarkClient) {
+        if let Some(toggle_sound) = self.toggle_sound {
+            client.set_toggle_sound_enabled(toggle_sound);
+        }
+        if let Some(theme) = self.theme {
+            client.set_theme(theme);
+        }
+        if let Some(saved) = &self.render_settings {
+            let mut render_settings = client.render_settings();
+            restore_render_settings(&mut render_settings, saved);
+            client.set_render_settings(render_settings);
+        }
+        if let Some(category_order) = &self.category_order {
+            client.set_category_order(category_order.clone());
+        }
+
+        let modules = client.modules.read().unwrap();
+        for (name, module_config) in &self.modules {
+            let Some(module) = modules.get(name) else {
+                warn!("Module {} in config is not registered, skipping", name);
+                continue;
+            };
+
+            let mut module = module.lock().unwrap();
+            module.get_module_data_mut().notify_on_toggle = module_config.notify_on_toggle;
+            if let Some(key_bind) = module_config.key_bind {
+                module.get_module_data_mut().key_bind = key_bind;
+            }
+            if let Some(category) = &module_config.category {
+                module.get_module_data_mut().category = category.clone();
+            }
+            Self::apply_enabled(client, &mut *module, module_config.enabled);
+            Self::apply_settings(&mut *module, &module_config.settings);
+        }
+    }
+
+    fn apply_settings(module: &mut ModuleType, settings: &[ModuleSetting]) {
+        for setting in settings {
+            let module_data = module.get_module_data_mut();
+            match module_data.get_setting_mut(setting.name()) {
+                Some(current)
+                    if std::mem::discriminant(current) == std::mem::discriminant(setting) =>
+                {
+                    let (restored, correction) = restore_setting(current, setting);
+                    *current = restored;
+                    if let Some(correction) = correction {
+                        warn!(
+                            "Corrected setting {} of module {} from config: {}",
+                            setting.name(),
+                            module_data.name,
+                            correction
+                        );
+                    }
+                    if let Err(e) = module.on_setting_changed(setting.name()) {
+                        error!(
+                            "Failed to apply setting {} of module {} from config: {}",
+                            setting.name(),
+                            module.get_module_data().name,
+                            e
+                        );
+                    }
+                }
+                Some(_) => warn!(
+                    "Setting {} of module {} changed type, skipping",
+                    setting.name(),
+                    module_data.name
+                ),
+                None => warn!(
+                    "Setting {} of module {} no longer exists, skipping",
+                    setting.name(),
+                    module_data.name
+                ),
+            }
+        }
+    }
+
+    fn apply_enabled(client: &DarkClient, module: &mut ModuleType, enabled: bool) {
+        if module.get_module_data().enabled == enabled {
+            return;
+        }
+        if enabled {
+            if let Some(reason) = client.activation_blocked(module.get_module_data().id) {
+                warn!(
+                    "Module {} in config can't be enabled: {}",
+                    module.get_module_data().name,
+                    reason
+                );
+                return;
+            }
+        }
+
+        let result = if enabled {
+            module.on_start()
+        } else {
+            module.on_stop()
+        };
+
+        match result {
+            Ok(_) => {
+                module.get_module_data_mut().set_enabled(enabled);
+                info!(
+                    "{} {} from config",
+                    module.get_module_data().name,
+                    if enabled { "enabled" } else { "disabled" }
+                );
+            }
+            Err(e) => error!(
+                "Failed to {} module {} from config: {}",
+                if enabled { "start" } else { "stop" },
+                module.get_module_data().name,
+                e
+            ),
+        }
+    }
+}
+
+// Fits the saved render settings to the current ones like module settings, unknown
+// settings and settings that changed type are skipped
+fn restore_render_settings(render_settings: &mut RenderSettings, saved: &[ModuleSetting]) {
+    for setting in saved {
+        let current = render_settings
+            .settings
+            .iter_mut()
+            .find(|current| current.name() == setting.name());
+        match current {
+            Some(current) if std::mem::discriminant(current) == std::mem::discriminant(setting) => {
+                let (restored, correction) = restore_setting(current, setting);
+                *current = restored;
+                if let Some(correction) = correction {
+                    warn!(
+                        "Corrected render setting {} from config: {}",
+                        setting.name(),
+                        correction
+                    );
+                }
+            }
+            _ => warn!(
+                "Render setting {} in config is unknown, skipping",
+                setting.name()
+            ),
+        }
+    }
+}
+
+/// Fits a saved setting to the current definition of the same setting: bounds, step and
+/// options come from the module, only the value is taken from the saved one. Out of range
+/// values are clamped, invalid choices reset to the first option and non-finite numbers
+/// keep the current value; the returned message describes the correction, if any
+fn restore_setting(
+    current: &ModuleSetting,
+    saved: &ModuleSetting,
+) -> (ModuleSetting, Option<String>) {
+    let mut restored = current.clone();
+
+    let correction = match (&mut restored, saved) {
+        (
+            ModuleSetting::Slider {
+                value, min, max, ..
+            },
+            ModuleSetting::Slider { value: saved, .. },
+        ) => {
+            if !saved.is_finite() {
+                Some(format!("{} is not a number, keeping {}", saved, value))
+            } else {
+                *value = saved.clamp(*min, *max);
+                (*value != *saved).then(|| format!("{} clamped to {}", saved, value))
+            }
+        }
+        (
+            ModuleSetting::IntSlider {
+                value, min, max, ..
+            },
+            ModuleSetting::IntSlider { value: saved, .. },
+        ) => {
+            *value = (*saved).clamp(*min, *max);
+            (*value != *saved).then(|| format!("{} clamped to {}", saved, value))
+        }
+        (
+            ModuleSetting::Choice { value, options, .. },
+            ModuleSetting::Choice { value: saved, .. },
+        ) => {
+            if *saved < options.len() {
+                *value = *saved;
+                None
+            } else if let Some(first) = options.first() {
+                *value = 0;
+                Some(format!("choice {} out of range, reset to {}", saved, first))
+            } else {
+                // A choice without options has nothing to restore
+                None
+            }
+        }
+        (ModuleSetting::Color { value, .. }, ModuleSetting::Color { value: saved, .. }) => {
+            if saved.iter().all(|channel| channel.is_finite()) {
+                *value = saved.map(|channel| channel.clamp(0.0, 1.0));
+                (*value != *saved).then(|| format!("{:?} clamped to {:?}", saved, value))
+            } else {
+                Some(format!("{:?} is not a color, keeping {:?}", saved, value))
+            }
+        }
+        (ModuleSetting::Text { value, .. }, ModuleSetting::Text { value: saved, .. }) => {
+            *value = ModuleSetting::fit_text(saved);
+            (*value != *saved).then(|| {
+                format!(
+                    "text of {} characters fitted to {}",
+                    saved.chars().count(),
+                    MAX_TEXT_LENGTH
+                )
+            })
+        }
+        (restored, saved) => {
+            *restored = saved.clone();
+            None
+        }
+    };
+
+    (restored, correction)
+}
+
+// Module for preserving the module state across a hot reload of the library
+pub mod reload {
+    use super::reload_env::RELOAD_TIMESTAMP_VAR;
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const STATE_FILE_NAME: &str = "dark_client_reload_state.json";
+    // Maximum age in seconds of a saved state to be considered part of the current reload
+    const MAX_STATE_AGE: u64 = 30;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ReloadState {
+        saved_at: u64,
+        config: ClientConfig,
+    }
+
+    fn state_path() -> PathBuf {
+        std::env::temp_dir().join(STATE_FILE_NAME)
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// Saves the current module state so that the next loaded library can restore it
+    pub fn save_state(client: &DarkClient) {
+        let state = ReloadState {
+            saved_at: now(),
+            config: ClientConfig::capture(client),
+        };
+
+        let result = serde_json::to_string(&state)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(state_path(), json)?));
+
+        match result {
+            Ok(_) => info!("Reload state saved to {:?}", state_path()),
+            Err(e) => error!("Failed to save reload state: {}", e),
+        }
+    }
+
+    /// Restores the module state saved by the previous library, if it belongs to the current reload
+    pub fn restore_state(client: &DarkClient) {
+        let path = state_path();
+        if !path.exists() {
+            return;
+        }
+
+        match read_state(&path) {
+            Ok(Some(state)) => {
+                info!("Restoring module state from reload");
+                state.config.apply(client);
+            }
+            Ok(None) => info!("Ignoring stale reload state"),
+            Err(e) => error!("Failed to read reload state: {}", e),
+        }
+
+        // The state is consumed either way, so it can't be applied twice
+        if let Err(e) = std::fs::remove_file(&path) {
+            error!("Unable to delete reload state file: {:?}", e);
+        }
+    }
+
+    fn read_state(path: &PathBuf) -> anyhow::Result<Option<ReloadState>> {
+        let reload_timestamp = match std::env::var(RELOAD_TIMESTAMP_VAR) {
+            Ok(value) => value.parse::<u64>()?,
+            // Not loaded through a reload command
+            Err(_) => return Ok(None),
+        };
+
+        let state: ReloadState = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        // The state must have been saved while handling this reload command, and recently
+        let is_current = state.saved_at >= reload_timestamp
+            && state.saved_at - reload_timestamp <= MAX_STATE_AGE
+            && now().saturating_sub(state.saved_at) <= MAX_STATE_AGE;
+
+        Ok(is_current.then_some(state))
+    }
+}
+
+// Module for remembering where the client GUI window was left, saved next to the other
+// client files in the config directory
+pub mod window {
+    use super::*;
+
+    const WINDOW_FILE_NAME: &str = "window.json";
+
+    /// Position and size of the GUI window, in logical points
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub struct WindowGeometry {
+        // Outer position of the window, None if the platform doesn't report it
+        pub position: Option<[f32; 2]>,
+        pub size: [f32; 2],
+    }
+
+    fn window_path() -> PathBuf {
+        config_directory().join(WINDOW_FILE_NAME)
+    }
+
+    /// Geometry saved when the GUI was last closed, None if there is none
+    pub fn load_geometry() -> Option<WindowGeometry> {
+        let path = window_path();
+        if !path.exists() {
+            return None;
+        }
+
+        let geometry = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(serde_json::from_str::<WindowGeometry>(&json)?));
+        match geometry {
+            Ok(geometry) => Some(geometry),
+            Err(e) => {
+                warn!("Ignoring invalid window geometry {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save_geometry(geometry: &WindowGeometry) {
+        let path = window_path();
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(serde_json::to_string(geometry)?))
+            .and_then(|json| Ok(std::fs::write(&path, json)?));
+
+        match result {
+            Ok(_) => info!("Window geometry saved to {:?}", path),
+            Err(e) => error!("Failed to save window geometry: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slider(value: f32) -> ModuleSetting {
+        ModuleSetting::Slider {
+            name: "Speed".to_string(),
+            value,
+            min: 1.0,
+            max: 3.0,
+            step: Some(0.5),
+        }
+    }
+
+    fn choice(value: usize) -> ModuleSetting {
+        ModuleSetting::Choice {
+            name: "Mode".to_string(),
+            value,
+            options: vec!["Vanilla".to_string(), "Packet".to_string()],
+        }
+    }
+
+    #[test]
+    fn restore_setting_keeps_valid_values() {
+        let (restored, correction) = restore_setting(&slider(1.0), &slider(2.5));
+        assert_eq!(restored.get_slider_value(), Some(2.5));
+        assert!(correction.is_none());
+
+        let (restored, correction) = restore_setting(&choice(0), &choice(1));
+        assert!(matches!(restored, ModuleSetting::Choice { value: 1, .. }));
+        assert!(correction.is_none());
+    }
+
+    #[test]
+    fn restore_setting_corrects_invalid_values() {
+        let (restored, correction) = restore_setting(&slider(2.0), &slider(10.0));
+        assert_eq!(restored.get_slider_value(), Some(3.0));
+        assert!(correction.is_some());
+
+        let (restored, correction) = restore_setting(&slider(2.0), &slider(f32::NAN));
+        assert_eq!(restored.get_slider_value(), Some(2.0));
+        assert!(correction.is_some());
+
+        let (restored, correction) = restore_setting(&choice(1), &choice(5));
+        assert!(matches!(restored, ModuleSetting::Choice { value: 0, .. }));
+        assert!(correction.is_some());
+
+        let empty = ModuleSetting::Choice {
+            name: "Mode".to_string(),
+            value: 0,
+            options: Vec::new(),
+        };
+        let (restored, correction) = restore_setting(&empty, &choice(1));
+        assert!(matches!(restored, ModuleSetting::Choice { value: 0, .. }));
+        assert!(correction.is_none());
+    }
+
+    #[test]
+    fn restore_setting_fits_text() {
+        let text = |value: &str| ModuleSetting::Text {
+            name: "Brand".to_string(),
+            value: value.to_string(),
+        };
+
+        let (restored, correction) = restore_setting(&text("vanilla"), &text("fabric"));
+        assert_eq!(restored.get_text_value(), Some("fabric"));
+        assert!(correction.is_none());
+
+        let (restored, correction) = restore_setting(&text("vanilla"), &text("two\nlines"));
+        assert_eq!(restored.get_text_value(), Some("two lines"));
+        assert!(correction.is_some());
+
+        let long = "a".repeat(MAX_TEXT_LENGTH + 10);
+        let (restored, _) = restore_setting(&text("vanilla"), &text(&long));
+        assert_eq!(restored.get_text_value().unwrap().len(), MAX_TEXT_LENGTH);
+
+        let json = serde_json::to_string(&text("vanilla")).unwrap();
+        let parsed: ModuleSetting = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.get_text_value(), Some("vanilla"));
+    }
+
+    #[test]
+    fn restore_render_settings_fits_saved_values() {
+        let mut render_settings = RenderSettings::default();
+        let saved = vec![
+            ModuleSetting::Color {
+                name: "Box color".to_string(),
+                value: [0.0, 1.0, 0.0, 0.5],
+            },
+            ModuleSetting::Slider {
+                name: "Line width".to_string(),
+                value: 20.0,
+                min: 0.0,
+                max: 50.0,
+                step: None,
+            },
+            ModuleSetting::Toggle {
+                name: "Tracers".to_string(),
+                value: true,
+            },
+            ModuleSetting::Toggle {
+                name: "Removed".to_string(),
+                value: true,
+            },
+        ];
+
+        restore_render_settings(&mut render_settings, &saved);
+        assert_eq!(render_settings.box_color(), [0.0, 1.0, 0.0, 0.5]);
+        assert_eq!(render_settings.line_width(), 5.0);
+        assert!(render_settings.tracers());
+        assert_eq!(render_settings.settings.len(), 3);
+    }
+
+    #[test]
+    fn restore_setting_keeps_the_module_definition() {
+        let saved = ModuleSetting::Slider {
+            name: "Speed".to_string(),
+            value: 2.0,
+            min: 0.0,
+            max: 100.0,
+            step: None,
+        };
+
+        let (restored, _) = restore_setting(&slider(1.0), &saved);
+        assert!(matches!(
+            restored,
+            ModuleSetting::Slider { value, min, max, step: Some(_), .. }
+                if value == 2.0 && min == 1.0 && max == 3.0
+        ));
+    }
+
+    #[test]
+    fn module_config_saved_by_older_versions_loads() {
+        let config: ClientConfig =
+            serde_json::from_str(r#"{"modules": {"Fly": {"enabled": true, "settings": []}}}"#)
+                .unwrap();
+        assert!(!config.modules["Fly"].notify_on_toggle);
+        assert_eq!(config.modules["Fly"].key_bind, None);
+        assert_eq!(config.modules["Fly"].category, None);
+        assert_eq!(config.category_order, None);
+    }
+}
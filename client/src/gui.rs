@@ -1,14 +1,27 @@
-use crate::client::DarkClient;
-use crate::module::{ModuleCategory, ModuleSetting};
-use crate::{cleanup_client, RUNNING};
+use crate::client::{DarkClient, UNSUPPORTED_VERSION};
+use crate::config::window::{self, WindowGeometry};
+use crate::module::{
+    keybind_conflicts, sort_by_category, KeyboardKey, ModuleCategory, ModuleSetting,
+    MAX_TEXT_LENGTH,
+};
+use crate::notification::{self, NotificationLevel};
+use crate::overlay::OVERLAY_KEY;
+use crate::{cleanup_client, BUILD_VERSION, RUNNING};
 use eframe::Frame;
 use egui::{Context, ScrollArea, Ui};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Mutex;
+#[cfg(windows)]
+use winit::platform::windows::EventLoopBuilderExtWindows;
 #[cfg(target_os = "linux")]
 use winit::platform::x11::EventLoopBuilderExtX11;
 
-pub fn call_panic() {
-    let client = DarkClient::instance();
+// Set to `off` to run without the GUI window, keybinds and agent commands still work
+const GUI_VAR: &str = "DARKCLIENT_GUI";
+
+pub fn call_panic(client: &DarkClient) {
     client.modules.read().unwrap().values().for_each(|module| {
         let mut module = module.lock().unwrap();
         if module.get_module_data().enabled {
@@ -28,11 +41,37 @@ pub fn call_panic() {
     cleanup_client();
 }
 
+/// Why the GUI window can't run on a client thread, None if it can. The game owns the
+/// main thread, so the window is only shown where winit runs off the main thread
+pub fn gui_unavailable_reason() -> Option<String> {
+    if std::env::var(GUI_VAR).is_ok_and(|value| value.eq_ignore_ascii_case("off")) {
+        return Some(format!("disabled by {}", GUI_VAR));
+    }
+    if cfg!(target_os = "macos") {
+        return Some("macOS only allows windows on the game's main thread".to_string());
+    }
+    // The window is forced on X11, which needs a display even under Wayland
+    if cfg!(target_os = "linux") && std::env::var_os("DISPLAY").is_none() {
+        return Some("no X11 display, DISPLAY is not set".to_string());
+    }
+    None
+}
+
 pub fn start_gui() -> anyhow::Result<()> {
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(DEFAULT_WINDOW_SIZE)
+        .with_min_inner_size([700.0, 500.0]);
+    // Reopen the window where it was left
+    if let Some(geometry) = window::load_geometry() {
+        viewport = viewport.with_inner_size(geometry.size);
+        if let Some(position) = geometry.position {
+            viewport = viewport.with_position(position);
+        }
+    }
+
+    #[cfg_attr(not(any(windows, target_os = "linux")), allow(unused_mut))]
     let mut native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([800.0, 600.0])
-            .with_min_inner_size([700.0, 500.0]),
+        viewport,
         run_and_return: true,
         ..Default::default()
     };
@@ -44,24 +83,92 @@ pub fn start_gui() -> anyhow::Result<()> {
         }));
     }
 
-    match eframe::run_native(
+    // winit refuses event loops off the main thread on Windows unless allowed
+    #[cfg(windows)]
+    {
+        native_options.event_loop_builder = Some(Box::new(|builder| {
+            builder.with_any_thread(true);
+        }));
+    }
+
+    let client = DarkClient::instance()?;
+    let result = eframe::run_native(
         "DarkClient Injector",
         native_options,
-        Box::new(|_| Ok(Box::new(GUI::default()))),
-    ) {
+        Box::new(move |_| Ok(Box::new(GUI::new(client)))),
+    );
+
+    if let Some(geometry) = *WINDOW_GEOMETRY.lock().unwrap() {
+        window::save_geometry(&geometry);
+    }
+
+    match result {
         Ok(_) => Ok(()),
         Err(e) => Err(anyhow::anyhow!("Failed to run the GUI, {}", e)),
     }
 }
 
+const DEFAULT_WINDOW_SIZE: [f32; 2] = [800.0, 600.0];
+
+// Last geometry of the window while it was shown, saved once the GUI closes
+static WINDOW_GEOMETRY: Mutex<Option<WindowGeometry>> = Mutex::new(None);
+
+/// Look of the GUI, saved with the config
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    pub dark: bool,
+    // Color of selections and highlights, RGB
+    pub accent: [u8; 3],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            dark: true,
+            accent: [0, 92, 128],
+        }
+    }
+}
+
+impl Theme {
+    fn visuals(&self) -> egui::Visuals {
+        let mut visuals = if self.dark {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+
+        let [r, g, b] = self.accent;
+        let accent = egui::Color32::from_rgb(r, g, b);
+        visuals.selection.bg_fill = accent;
+        visuals.hyperlink_color = accent;
+        visuals
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct GUI {
+    client: &'static DarkClient,
     selected_category: ModuleCategory,
+    // The keybinds of every module are shown instead of a category
+    show_keybinds: bool,
+    // Category names being typed, by module id
+    category_inputs: HashMap<&'static str, String>,
+    // Theme currently set on the context, applied again only when it changes
+    applied_theme: Option<Theme>,
+    // Config string pasted by the user for importing
+    config_input: String,
 }
 
-impl Default for GUI {
-    fn default() -> Self {
+impl GUI {
+    pub fn new(client: &'static DarkClient) -> Self {
         Self {
+            client,
             selected_category: ModuleCategory::COMBAT,
+            show_keybinds: false,
+            category_inputs: HashMap::new(),
+            applied_theme: None,
+            config_input: String::new(),
         }
     }
 }
@@ -74,8 +181,23 @@ impl eframe::App for GUI {
             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
         }
 
+        track_window_geometry(ctx);
+
+        let client = self.client;
+        let theme = client.theme();
+        if self.applied_theme != Some(theme) {
+            ctx.set_visuals(theme.visuals());
+            self.applied_theme = Some(theme);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("DarkClient");
+            ui.horizontal(|ui| {
+                ui.heading("DarkClient");
+                ui.weak(BUILD_VERSION);
+            });
+            if client.is_version_unsupported() {
+                ui.colored_label(egui::Color32::RED, UNSUPPORTED_VERSION);
+            }
             ui.separator();
 
             ui.horizontal(|ui| {
@@ -84,57 +206,121 @@ impl eframe::App for GUI {
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if ui.button("Panic").clicked() {
-                        std::thread::spawn(|| call_panic());
+                        std::thread::spawn(move || call_panic(client));
                     }
+
+                    let mut toggle_sound = client.is_toggle_sound_enabled();
+                    if ui.checkbox(&mut toggle_sound, "🔊 Toggle sound").changed() {
+                        client.set_toggle_sound_enabled(toggle_sound);
+                    }
+
+                    ui.menu_button("⚙", |ui| {
+                        let mut theme = client.theme();
+                        ui.checkbox(&mut theme.dark, "Dark mode");
+                        ui.horizontal(|ui| {
+                            ui.label("Accent");
+                            ui.color_edit_button_srgb(&mut theme.accent);
+                        });
+                        if theme != client.theme() {
+                            client.set_theme(theme);
+                        }
+
+                        ui.separator();
+                        ui.label("Render");
+                        let mut render_settings = client.render_settings();
+                        let mut changed = false;
+                        for setting in &mut render_settings.settings {
+                            changed |= render_setting(ui, setting);
+                        }
+                        if changed {
+                            client.set_render_settings(render_settings);
+                        }
+                    });
                 });
             });
 
-            ui.add_space(10.0);
-
-            // Category selection
             ui.horizontal(|ui| {
-                ui.label("Category:");
-                if ui
-                    .selectable_label(self.selected_category == ModuleCategory::COMBAT, "⚔ Combat")
-                    .clicked()
-                {
-                    self.selected_category = ModuleCategory::COMBAT;
+                ui.label("Config:");
+                if ui.button("📋 Export").clicked() {
+                    match client.export_config() {
+                        Ok(config) => {
+                            ctx.copy_text(config);
+                            notification::notify_info("Config copied to the clipboard");
+                        }
+                        Err(e) => {
+                            notification::notify_error(format!("Failed to export config: {}", e))
+                        }
+                    }
                 }
-                if ui
-                    .selectable_label(
-                        self.selected_category == ModuleCategory::MOVEMENT,
-                        "🏃 Movement",
-                    )
-                    .clicked()
-                {
-                    self.selected_category = ModuleCategory::MOVEMENT;
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.config_input)
+                        .hint_text("Paste a config here"),
+                );
+                if ui.button("📥 Import").clicked() {
+                    match client.import_config(&self.config_input) {
+                        Ok(_) => {
+                            self.config_input.clear();
+                            notification::notify_info("Config imported");
+                        }
+                        Err(e) => {
+                            notification::notify_error(format!("Failed to import config: {}", e))
+                        }
+                    }
                 }
-                if ui
-                    .selectable_label(self.selected_category == ModuleCategory::RENDER, "👁 Render")
-                    .clicked()
-                {
-                    self.selected_category = ModuleCategory::RENDER;
+            });
+
+            ui.add_space(10.0);
+
+            // Category selection, with the number of enabled modules of each category
+            let enabled_counts = enabled_module_counts(client);
+            let mut tabs = category_tabs(client);
+            // The selected category is gone once its last module moved to another one
+            if !tabs.contains(&self.selected_category) {
+                if let Some(first) = tabs.first() {
+                    self.selected_category = first.clone();
                 }
-                if ui
-                    .selectable_label(
-                        self.selected_category == ModuleCategory::PLAYER,
-                        "🧍 Player",
-                    )
-                    .clicked()
-                {
-                    self.selected_category = ModuleCategory::PLAYER;
+            }
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Category:");
+                for category in &tabs {
+                    let selected = !self.show_keybinds && self.selected_category == *category;
+                    if ui
+                        .selectable_label(selected, category_label(category))
+                        .clicked()
+                    {
+                        self.selected_category = category.clone();
+                        self.show_keybinds = false;
+                    }
+
+                    let enabled = enabled_counts.get(category).copied().unwrap_or(0);
+                    if enabled > 0 {
+                        ui.colored_label(egui::Color32::GREEN, format!("● {}", enabled))
+                            .on_hover_text(format!("{} enabled", enabled));
+                    }
                 }
+
+                ui.separator();
                 if ui
-                    .selectable_label(self.selected_category == ModuleCategory::WORLD, "🌍 World")
+                    .selectable_label(self.show_keybinds, "⌨ Keybinds")
                     .clicked()
                 {
-                    self.selected_category = ModuleCategory::WORLD;
+                    self.show_keybinds = true;
                 }
-                if ui
-                    .selectable_label(self.selected_category == ModuleCategory::MISC, "🔧 Misc")
-                    .clicked()
-                {
-                    self.selected_category = ModuleCategory::MISC;
+
+                // Moves the selected tab, the new order is saved with the config
+                let selected = tabs.iter().position(|tab| *tab == self.selected_category);
+                if let (Some(index), false) = (selected, self.show_keybinds) {
+                    ui.separator();
+                    let left = ui.add_enabled(index > 0, egui::Button::new("⏴"));
+                    if left.on_hover_text("Move the tab left").clicked() {
+                        tabs.swap(index, index - 1);
+                        client.set_category_order(tabs.clone());
+                    }
+                    let right = ui.add_enabled(index + 1 < tabs.len(), egui::Button::new("⏵"));
+                    if right.on_hover_text("Move the tab right").clicked() {
+                        tabs.swap(index, index + 1);
+                        client.set_category_order(tabs.clone());
+                    }
                 }
             });
 
@@ -142,73 +328,124 @@ impl eframe::App for GUI {
 
             // Modules list
             ScrollArea::vertical().show(ui, |ui| {
-                self.render_modules(ui);
+                if self.show_keybinds {
+                    render_keybinds(client, ui);
+                } else {
+                    self.render_modules(ui);
+                }
             });
         });
+
+        self.render_notifications(ctx);
     }
 }
 
 impl GUI {
+    fn render_notifications(&self, ctx: &Context) {
+        let notifications = notification::active_notifications();
+        if notifications.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("notifications"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .show(ctx, |ui| {
+                for notification in notifications {
+                    let color = match notification.level {
+                        NotificationLevel::Info => egui::Color32::LIGHT_GREEN,
+                        NotificationLevel::Error => egui::Color32::LIGHT_RED,
+                    };
+                    // Fade out during the last second
+                    let alpha = (notification.remaining() * 4.0).clamp(0.0, 1.0);
+
+                    egui::Frame::popup(ui.style())
+                        .multiply_with_opacity(alpha)
+                        .show(ui, |ui| {
+                            ui.colored_label(color.gamma_multiply(alpha), &notification.message);
+                        });
+                }
+            });
+    }
+
     fn render_modules(&mut self, ui: &mut Ui) {
-        let client = DarkClient::instance();
+        let client = self.client;
+        // Modules can't be toggled or edited once cleanup began
+        let Some(_calls) = client.begin_module_calls() else {
+            return;
+        };
         let modules = client.modules.read().unwrap();
 
-        let mut modules_in_category: Vec<_> = modules
+        // Shown in registration order
+        let modules_in_category: Vec<_> = modules
             .iter()
             .filter(|(_, module)| {
                 module.lock().unwrap().get_module_data().category == self.selected_category
             })
             .collect();
 
-        modules_in_category.sort_by(|a, b| {
-            a.1.lock()
-                .unwrap()
-                .get_module_data()
-                .name
-                .cmp(&b.1.lock().unwrap().get_module_data().name)
-        });
-
         if modules_in_category.is_empty() {
             ui.label("No modules in this category");
             return;
         }
 
+        // Toggled once the modules are unlocked, toggle_module locks the module itself
+        let mut toggled = None;
         for (_, module) in modules_in_category {
             let mut module = module.lock().unwrap();
 
             ui.group(|ui| {
+                let blocked = client.activation_blocked(module.get_module_data().id);
                 ui.horizontal(|ui| {
                     let mut enabled = module.get_module_data().enabled;
-                    if ui.checkbox(&mut enabled, "").changed() {
-                        if enabled {
-                            match module.on_start() {
-                                Ok(_) => {
-                                    module.get_module_data_mut().set_enabled(true);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to start module: {}", e);
-                                }
-                            }
-                        } else {
-                            match module.on_stop() {
-                                Ok(_) => {
-                                    module.get_module_data_mut().set_enabled(false);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to stop module: {}", e);
-                                }
-                            }
-                        }
+                    // A blocked module can still be turned off
+                    let can_toggle = blocked.is_none() || enabled;
+                    let checkbox = egui::Checkbox::new(&mut enabled, "");
+                    if ui.add_enabled(can_toggle, checkbox).changed() {
+                        toggled = Some(module.get_module_data().id);
                     }
 
                     let module_data = module.get_module_data();
                     ui.vertical(|ui| {
                         ui.strong(&module_data.name);
                         ui.label(&module_data.description);
-                        ui.label(format!("Keybind: {:?}", module_data.key_bind));
+                        ui.label(format!("Keybind: {}", module_data.key_bind.display_name()));
+                        if let Some(status) = module.get_status() {
+                            ui.weak(status);
+                        }
+                        if let Some(reason) = &blocked {
+                            ui.colored_label(egui::Color32::LIGHT_RED, reason);
+                        }
                     });
                 });
 
+                // Typing the name of a category that doesn't exist creates it
+                let id = module.get_module_data().id;
+                let current = module.get_module_data().category.display_name().to_string();
+                ui.horizontal(|ui| {
+                    ui.label("📁 Category:");
+                    let input = self.category_inputs.entry(id).or_insert(current.clone());
+                    let response = ui.add(
+                        egui::TextEdit::singleline(input)
+                            .desired_width(120.0)
+                            .hint_text("Misc"),
+                    );
+                    if response.lost_focus() {
+                        module.get_module_data_mut().category = ModuleCategory::from_name(input);
+                        self.category_inputs.remove(id);
+                    } else if !response.has_focus() {
+                        // Follows changes made elsewhere, e.g. by importing a config
+                        *input = current;
+                    }
+                });
+
+                let mut notify_on_toggle = module.get_module_data().notify_on_toggle;
+                if ui
+                    .checkbox(&mut notify_on_toggle, "🔔 Notify on toggle")
+                    .changed()
+                {
+                    module.get_module_data_mut().notify_on_toggle = notify_on_toggle;
+                }
+
                 let module_data = module.get_module_data();
                 // Render module settings
                 if module_data.enabled {
@@ -219,6 +456,13 @@ impl GUI {
 
             ui.add_space(5.0);
         }
+        drop(modules);
+
+        if let Some(id) = toggled {
+            if let Err(e) = client.toggle_module(id) {
+                log::error!("{}", e);
+            }
+        }
     }
 
     fn render_module_settings(&mut self, ui: &mut Ui, module: &mut dyn crate::module::Module) {
@@ -228,77 +472,270 @@ impl GUI {
             return;
         }
 
+        // The module is notified once the settings are no longer borrowed
+        let mut changed_setting = None;
+
         ui.label("⚙ Settings:");
         ui.indent("settings", |ui| {
             let settings_len = module_data.settings.len();
             for i in 0..settings_len {
                 let setting = &mut module_data.settings[i];
+                if render_setting(ui, setting) {
+                    changed_setting = Some(setting.name().to_string());
+                }
+            }
+        });
 
-                match setting {
-                    ModuleSetting::Slider {
-                        name,
-                        value,
-                        min,
-                        max,
-                    } => {
-                        ui.horizontal(|ui| {
-                            ui.label(name.as_str());
-                            let mut temp_value = *value;
-                            if ui
-                                .add(
-                                    egui::Slider::new(&mut temp_value, *min..=*max)
-                                        .fixed_decimals(1),
-                                )
-                                .changed()
-                            {
-                                *value = temp_value;
-                            }
-                        });
-                    }
-                    ModuleSetting::Toggle { name, value } => {
-                        ui.horizontal(|ui| {
-                            let mut temp_value = *value;
-                            if ui.checkbox(&mut temp_value, name.as_str()).changed() {
-                                *value = temp_value;
-                            }
-                        });
-                    }
-                    ModuleSetting::Choice {
-                        name,
-                        value,
-                        options,
-                    } => {
-                        ui.horizontal(|ui| {
-                            ui.label(name.as_str());
-                            egui::ComboBox::from_id_salt(format!("choice_{}", name))
-                                .selected_text(&options[*value])
-                                .show_ui(ui, |ui| {
-                                    for (idx, option) in options.iter().enumerate() {
-                                        ui.selectable_value(value, idx, option);
-                                    }
-                                });
-                        });
-                    }
-                    ModuleSetting::Color { name, value } => {
-                        ui.horizontal(|ui| {
-                            ui.label(name.as_str());
-                            let mut color = egui::Color32::from_rgba_unmultiplied(
-                                (value[0] * 255.0) as u8,
-                                (value[1] * 255.0) as u8,
-                                (value[2] * 255.0) as u8,
-                                (value[3] * 255.0) as u8,
-                            );
-                            if ui.color_edit_button_srgba(&mut color).changed() {
-                                let rgba = color.to_srgba_unmultiplied();
-                                value[0] = rgba[0] as f32 / 255.0;
-                                value[1] = rgba[1] as f32 / 255.0;
-                                value[2] = rgba[2] as f32 / 255.0;
-                                value[3] = rgba[3] as f32 / 255.0;
+        if let Some(setting_name) = changed_setting {
+            if let Err(e) = module.on_setting_changed(&setting_name) {
+                log::error!(
+                    "Failed to apply setting {} of module {}: {}",
+                    setting_name,
+                    module.get_module_data().name,
+                    e
+                );
+            }
+        }
+    }
+}
+
+// Draws the widget of a setting, true if the user changed its value
+fn render_setting(ui: &mut Ui, setting: &mut ModuleSetting) -> bool {
+    let mut changed = false;
+    match setting {
+        ModuleSetting::Slider {
+            name,
+            value,
+            min,
+            max,
+            step,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label(name.as_str());
+                let mut temp_value = *value;
+                let mut slider = egui::Slider::new(&mut temp_value, *min..=*max);
+                slider = match step {
+                    Some(step) => slider
+                        .step_by(*step as f64)
+                        .fixed_decimals(step_decimals(*step)),
+                    None => slider.fixed_decimals(1),
+                };
+                if ui.add(slider).changed() {
+                    *value = temp_value;
+                    changed = true;
+                }
+            });
+        }
+        ModuleSetting::IntSlider {
+            name,
+            value,
+            min,
+            max,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label(name.as_str());
+                let mut temp_value = *value;
+                if ui
+                    .add(egui::Slider::new(&mut temp_value, *min..=*max))
+                    .changed()
+                {
+                    *value = temp_value;
+                    changed = true;
+                }
+            });
+        }
+        ModuleSetting::Toggle { name, value } => {
+            ui.horizontal(|ui| {
+                let mut temp_value = *value;
+                if ui.checkbox(&mut temp_value, name.as_str()).changed() {
+                    *value = temp_value;
+                    changed = true;
+                }
+            });
+        }
+        ModuleSetting::Choice {
+            name,
+            value,
+            options,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label(name.as_str());
+                egui::ComboBox::from_id_salt(format!("choice_{}", name))
+                    .selected_text(&options[*value])
+                    .show_ui(ui, |ui| {
+                        for (idx, option) in options.iter().enumerate() {
+                            if ui.selectable_value(value, idx, option).changed() {
+                                changed = true;
                             }
-                        });
-                    }
+                        }
+                    });
+            });
+        }
+        ModuleSetting::Color { name, value } => {
+            ui.horizontal(|ui| {
+                ui.label(name.as_str());
+                let mut color = egui::Color32::from_rgba_unmultiplied(
+                    (value[0] * 255.0) as u8,
+                    (value[1] * 255.0) as u8,
+                    (value[2] * 255.0) as u8,
+                    (value[3] * 255.0) as u8,
+                );
+                if ui.color_edit_button_srgba(&mut color).changed() {
+                    let rgba = color.to_srgba_unmultiplied();
+                    value[0] = rgba[0] as f32 / 255.0;
+                    value[1] = rgba[1] as f32 / 255.0;
+                    value[2] = rgba[2] as f32 / 255.0;
+                    value[3] = rgba[3] as f32 / 255.0;
+                    changed = true;
+                }
+            });
+        }
+        ModuleSetting::Text { name, value } => {
+            ui.horizontal(|ui| {
+                ui.label(name.as_str());
+                let text_edit = egui::TextEdit::singleline(value).char_limit(MAX_TEXT_LENGTH);
+                if ui.add(text_edit).changed() {
+                    changed = true;
                 }
+            });
+        }
+    }
+
+    changed
+}
+
+// Remembers the window geometry, unless minimized or maximized so the window reopens
+// with its normal size
+fn track_window_geometry(ctx: &Context) {
+    let geometry = ctx.input(|input| {
+        let viewport = input.viewport();
+        if viewport.minimized == Some(true) || viewport.maximized == Some(true) {
+            return None;
+        }
+
+        let size = viewport.inner_rect?.size();
+        Some(WindowGeometry {
+            position: viewport.outer_rect.map(|rect| [rect.min.x, rect.min.y]),
+            size: [size.x, size.y],
+        })
+    });
+
+    if geometry.is_some() {
+        *WINDOW_GEOMETRY.lock().unwrap() = geometry;
+    }
+}
+
+// Every module with its keybind, which can be changed from a list of keys, and a
+// warning when a key toggles several modules or opens the in-game menu
+fn render_keybinds(client: &DarkClient, ui: &mut Ui) {
+    let Some(_calls) = client.begin_module_calls() else {
+        return;
+    };
+    let modules = client.modules.read().unwrap();
+
+    let key_binds: Vec<(String, KeyboardKey)> = modules
+        .values()
+        .map(|module| {
+            let module = module.lock().unwrap();
+            let module_data = module.get_module_data();
+            (module_data.name.clone(), module_data.key_bind)
+        })
+        .collect();
+    let named_binds: Vec<(&str, KeyboardKey)> = key_binds
+        .iter()
+        .map(|(name, key_bind)| (name.as_str(), *key_bind))
+        .collect();
+    let conflicts = keybind_conflicts(&named_binds);
+
+    egui::Grid::new("keybinds")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            for module in modules.values() {
+                let mut module = module.lock().unwrap();
+                let module_data = module.get_module_data_mut();
+                let name = module_data.name.clone();
+
+                ui.strong(&name);
+                let mut key_bind = module_data.key_bind;
+                egui::ComboBox::from_id_salt(("keybind", module_data.id))
+                    .selected_text(key_bind.display_name())
+                    .height(300.0)
+                    .show_ui(ui, |ui| {
+                        for key in KeyboardKey::ALL {
+                            ui.selectable_value(&mut key_bind, key, key.display_name());
+                        }
+                    });
+                if key_bind != module_data.key_bind {
+                    log::info!("{} bound to {}", name, key_bind.display_name());
+                    module_data.key_bind = key_bind;
+                }
+
+                if let Some(others) = conflicts.get(name.as_str()) {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!("⚠ Also toggles {}", others.join(", ")),
+                    );
+                } else if key_bind == OVERLAY_KEY {
+                    ui.colored_label(egui::Color32::YELLOW, "⚠ Also opens the module menu");
+                } else {
+                    ui.label("");
+                }
+                ui.end_row();
             }
         });
+}
+
+// Category tabs in display order, one for each category with a registered module
+fn category_tabs(client: &DarkClient) -> Vec<ModuleCategory> {
+    let mut tabs = Vec::new();
+    for module in client.modules.read().unwrap().values() {
+        let category = module.lock().unwrap().get_module_data().category.clone();
+        if !tabs.contains(&category) {
+            tabs.push(category);
+        }
+    }
+
+    sort_by_category(&mut tabs, &client.category_order(), |category| category);
+    tabs
+}
+
+fn category_icon(category: &ModuleCategory) -> &'static str {
+    match category {
+        ModuleCategory::COMBAT => "⚔",
+        ModuleCategory::MOVEMENT => "🏃",
+        ModuleCategory::RENDER => "👁",
+        ModuleCategory::PLAYER => "🧍",
+        ModuleCategory::WORLD => "🌍",
+        ModuleCategory::MISC => "🔧",
+        ModuleCategory::Custom(_) => "📁",
+    }
+}
+
+fn category_label(category: &ModuleCategory) -> String {
+    format!("{} {}", category_icon(category), category.display_name())
+}
+
+// Number of enabled modules in each category
+fn enabled_module_counts(client: &DarkClient) -> HashMap<ModuleCategory, usize> {
+    let mut counts = HashMap::new();
+    for module in client.modules.read().unwrap().values() {
+        let module = module.lock().unwrap();
+        let module_data = module.get_module_data();
+        if module_data.enabled {
+            *counts.entry(module_data.category.clone()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// Decimals needed to display multiples of the step, e.g. 1 for 0.5 and 0 for 2
+fn step_decimals(step: f32) -> usize {
+    let mut step = step.abs();
+    let mut decimals = 0;
+    while decimals < 4 && (step - step.round()).abs() > 1e-4 {
+        step *= 10.0;
+        decimals += 1;
     }
+    decimals
 }
@@ -2,21 +2,34 @@
 
 extern crate jni;
 mod client;
+mod config;
 mod gui;
+mod logging;
 mod mapping;
+mod metrics;
 mod module;
+mod notification;
+mod overlay;
+mod packet;
+mod random;
+mod render;
+mod time;
 
 use crate::client::keyboard::{start_keyboard_handler, stop_keyboard_handler};
 use crate::client::DarkClient;
-use crate::gui::start_gui;
+use crate::config::reload;
+use crate::gui::{gui_unavailable_reason, start_gui};
+use crate::logging::{log_limited, max_log_size, RotatingFile};
 use crate::mapping::client::minecraft::Minecraft;
-use crate::module::fly::FlyModule;
-use crate::module::ModuleType;
-use log::{error, info, LevelFilter};
+use crate::module::built_in_modules;
+use crate::render::{install_render_hook, uninstall_render_hook};
+use log::{error, info, warn, Level, LevelFilter};
 use simplelog::{Config, WriteLogger};
-use std::fs::File;
+use std::any::Any;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::AssertUnwindSafe;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::{Mutex, OnceLock};
 use std::thread;
 use std::time::Duration;
 
@@ -26,6 +39,15 @@ static GUI_THREAD: OnceLock<Mutex<Option<thread::JoinHandle<()>>>> = OnceLock::n
 // Flag to control if the client is running
 static RUNNING: AtomicBool = AtomicBool::new(false);
 
+// How long cleanup waits for module calls in progress before removing the hooks
+const MODULE_CALLS_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Set to start with every module disabled, to tell module crashes apart from core ones
+const SAFE_MODE_VAR: &str = "DARKCLIENT_SAFE_MODE";
+
+// How long startup waits before trying to initialize the game again
+const INIT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
 fn tick_thread() -> &'static Mutex<Option<thread::JoinHandle<()>>> {
     TICK_THREAD.get_or_init(|| Mutex::new(None))
 }
@@ -35,72 +57,278 @@ fn gui_thread() -> &'static Mutex<Option<thread::JoinHandle<()>>> {
 }
 
 #[no_mangle]
-pub extern "C" fn initialize_client() {
+pub extern "C-unwind" fn initialize_client() {
+    ffi_guard("initialize_client", (), initialize);
+}
+
+fn initialize() {
     // Make sure we can't initialize more than once
     if RUNNING.swap(true, Ordering::SeqCst) {
         info!("Client already initialized");
         return;
     }
 
-    // Initialize the logger
-    match WriteLogger::init(
-        LevelFilter::Debug,
-        Config::default(),
-        File::create("dark_client.log").unwrap(),
-    ) {
-        Ok(_) => info!("Logger initialized"),
-        Err(e) => eprintln!("Error during logger initialization: {:?}", e),
+    // Initialize the logger, the client still runs without it
+    let (max_log_size, log_size_warning) = max_log_size();
+    match RotatingFile::create("dark_client.log", max_log_size) {
+        Ok(log_file) => match WriteLogger::init(LevelFilter::Debug, Config::default(), log_file) {
+            Ok(_) => {
+                info!("Logger initialized");
+                if let Some(warning) = log_size_warning {
+                    warn!("{}", warning);
+                }
+            }
+            Err(e) => eprintln!("Error during logger initialization: {:?}", e),
+        },
+        Err(e) => eprintln!("Unable to create the log file: {:?}", e),
     }
 
     thread::spawn(|| {
         info!("Starting DarkClient...");
-        let minecraft = Minecraft::instance();
+        let minecraft = match wait_for_minecraft() {
+            Some(minecraft) => minecraft,
+            None => {
+                info!("Client stopped before the game was ready");
+                return;
+            }
+        };
+
+        let client = match DarkClient::instance() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to start DarkClient: {}", e);
+                return;
+            }
+        };
+
+        let safe_mode = std::env::var_os(SAFE_MODE_VAR).is_some();
+        if safe_mode {
+            info!("Safe mode active, every module starts disabled");
+        }
+
+        client.check_mapping_health(minecraft.get_mapping());
+        // Modules acting on the player can only be enabled once it's known to exist
+        client.update_player_state();
+        register_modules(client, minecraft, safe_mode);
 
-        register_modules(minecraft);
+        // Restore the module state if we are being hot reloaded, safe mode keeps modules off
+        if !safe_mode {
+            reload::restore_state(client);
+        }
 
-        start_keyboard_handler();
+        // Keys are read on the render thread through the hook, without it every poll
+        // would fail, so keybinds are turned off once with a visible error instead
+        match install_render_hook() {
+            Ok(()) => start_keyboard_handler(),
+            Err(e) => {
+                error!("Failed to install render hook: {}", e);
+                notification::notify_error(
+                    "Render hook unavailable — keybinds and the module menu are disabled",
+                );
+            }
+        }
 
         // Tick thread
         let thread_handle = thread::spawn(move || {
-            let client = DarkClient::instance();
             while RUNNING.load(Ordering::SeqCst) {
                 // Wait for Minecraft tick
-                thread::sleep(Duration::from_millis(50)); // 20 ticks per second
+                thread::sleep(time::TICK_INTERVAL);
                 client.tick();
             }
             info!("Tick thread terminated");
         });
 
-        let gui_handle = thread::spawn(move || match start_gui() {
-            Ok(_) => info!("GUI thread started"),
-            Err(e) => error!("Error while starting GUI thread: {}", e),
-        });
+        let gui_handle = match gui_unavailable_reason() {
+            Some(reason) => {
+                warn!(
+                    "GUI not started, {}. Modules can still be toggled with their keybinds \
+                     or the agent toggle command",
+                    reason
+                );
+                None
+            }
+            None => Some(thread::spawn(move || match start_gui() {
+                Ok(_) => info!("GUI thread started"),
+                Err(e) => error!("Error while starting GUI thread: {}", e),
+            })),
+        };
 
         // Memorize the thread handle in a thread-safe way
         let mut tick_lock = tick_thread().lock().unwrap();
         *tick_lock = Some(thread_handle);
 
         let mut gui_lock = gui_thread().lock().unwrap();
-        *gui_lock = Some(gui_handle);
+        *gui_lock = gui_handle;
+    });
+}
 
-        info!(
-            "Player position: {:?}",
-            minecraft.player.entity.get_position()
-        );
+// Retries the game initialization until it succeeds, the game may still be loading when
+// injected early. None if the client is stopped meanwhile
+fn wait_for_minecraft() -> Option<&'static Minecraft> {
+    while RUNNING.load(Ordering::SeqCst) {
+        match Minecraft::try_instance() {
+            Ok(minecraft) => return Some(minecraft),
+            Err(e) => {
+                log_limited(Level::Warn, &format!("Game not ready, retrying: {:?}", e));
+                thread::sleep(INIT_RETRY_INTERVAL);
+            }
+        }
+    }
+
+    None
+}
+
+// Mapping dump for the agent_loader `dump` command, free the result with free_client_string
+#[no_mangle]
+pub extern "C" fn dump_mapping() -> *mut c_char {
+    ffi_guard("dump_mapping", std::ptr::null_mut(), || {
+        if !RUNNING.load(Ordering::SeqCst) {
+            return std::ptr::null_mut();
+        }
+
+        let dump = match Minecraft::try_instance() {
+            Ok(minecraft) => minecraft.get_mapping().dump_resolved(),
+            Err(e) => format!("Game not initialized yet: {}", e),
+        };
+
+        match CString::new(dump) {
+            Ok(dump) => dump.into_raw(),
+            Err(e) => {
+                error!("Invalid mapping dump: {}", e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Version of this build, the crate version followed by the git hash when available
+pub const BUILD_VERSION: &str = env!("DARKCLIENT_BUILD_VERSION");
+// BUILD_VERSION returned to the agent_loader `info` command, nul-terminated for C
+const CLIENT_VERSION: &str = concat!(env!("DARKCLIENT_BUILD_VERSION"), "\0");
+
+/// Version of this client build, a static string that must not be freed
+#[no_mangle]
+pub extern "C" fn client_version() -> *const c_char {
+    CLIENT_VERSION.as_ptr().cast()
+}
+
+/// Turns the recording of module tick times on or off
+#[no_mangle]
+pub extern "C" fn set_metrics_enabled(enabled: bool) {
+    ffi_guard("set_metrics_enabled", (), || {
+        metrics::set_enabled(enabled);
+        info!("Metrics {}", if enabled { "enabled" } else { "disabled" });
     });
 }
 
+/// Returns the module tick times report, to be freed with `free_client_string`
+#[no_mangle]
+pub extern "C" fn dump_metrics() -> *mut c_char {
+    ffi_guard("dump_metrics", std::ptr::null_mut(), || {
+        let report = CString::new(metrics::report());
+        match report {
+            Ok(report) => report.into_raw(),
+            Err(e) => {
+                error!("Invalid metrics report: {}", e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Toggles the module with the id `id` for the agent_loader `toggle` command and returns
+/// the reply, the new state or the error, to be freed with `free_client_string`
+///
+/// # Safety
+///
+/// `id` must be a valid nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn toggle_module(id: *const c_char) -> *mut c_char {
+    ffi_guard("toggle_module", std::ptr::null_mut(), || {
+        if !RUNNING.load(Ordering::SeqCst) || id.is_null() {
+            return std::ptr::null_mut();
+        }
+
+        let id = CStr::from_ptr(id).to_string_lossy();
+        let id = id.trim();
+        let reply = match DarkClient::instance().and_then(|client| client.toggle_module(id)) {
+            Ok(true) => format!("{} enabled\n", id),
+            Ok(false) => format!("{} disabled\n", id),
+            Err(e) => {
+                warn!("Toggle command failed: {}", e);
+                format!("Error: {}\n", e)
+            }
+        };
+
+        match CString::new(reply) {
+            Ok(reply) => reply.into_raw(),
+            Err(e) => {
+                error!("Invalid toggle reply: {}", e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Frees a string returned to the agent_loader
+///
+/// # Safety
+///
+/// `value` must be null or a pointer returned by `dump_mapping`, `dump_metrics` or
+/// `toggle_module`, freed only once
+#[no_mangle]
+pub unsafe extern "C" fn free_client_string(value: *mut c_char) {
+    if !value.is_null() {
+        ffi_guard("free_client_string", (), || drop(CString::from_raw(value)));
+    }
+}
+
 // Cleanup function for agent_loader
 #[no_mangle]
-pub extern "C" fn cleanup_client() {
+pub extern "C-unwind" fn cleanup_client() {
+    ffi_guard("cleanup_client", (), cleanup);
+}
+
+fn cleanup() {
     info!("Client cleanup in progress...");
 
-    // Set the execution flag to false
+    // Set the execution flag to false, no module call begins after this
     RUNNING.store(false, Ordering::SeqCst);
 
+    // Without a client nothing was hooked, the threads stop on their own
+    let client = match DarkClient::instance() {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to clean up: {}", e);
+            return;
+        }
+    };
+
     // Stop the keyboard handler
     stop_keyboard_handler();
 
+    // Module calls already in progress on the tick, render or GUI threads may still use
+    // the hooks, let them finish before removing anything
+    if !client.wait_for_module_calls(MODULE_CALLS_TIMEOUT) {
+        error!(
+            "Module calls still running after {:?}, cleaning up anyway",
+            MODULE_CALLS_TIMEOUT
+        );
+    }
+
+    // Undo what the enabled modules changed in the game, e.g. Reach's extended range,
+    // before the next library reads it as the original
+    client.stop_enabled_modules();
+
+    // Save the module state so it survives a hot reload, nothing toggles modules anymore
+    reload::save_state(client);
+
+    // Stop rendering before the library is unloaded
+    uninstall_render_hook();
+
+    // Same for the packet hook, held packets are sent first
+    packet::uninstall_packet_hook();
+
     // Wait for the tick thread to terminate
     let thread_handle = {
         let mut tick_lock = tick_thread().lock().unwrap();
@@ -130,14 +358,37 @@ pub extern "C" fn cleanup_client() {
     info!("Client cleanup completed");
 }
 
-fn register_modules(minecraft: &'static Minecraft) {
-    let client = DarkClient::instance();
+// Runs the body of a function called by the agent loader. A panic unwinding out of an
+// extern "C" function is undefined behavior and can take the JVM down, it's logged instead
+fn ffi_guard<T>(name: &str, fallback: T, f: impl FnOnce() -> T) -> T {
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => value,
+        Err(panic) => {
+            error!("Panic in {}: {}", name, panic_message(panic.as_ref()));
+            fallback
+        }
+    }
+}
 
-    let fly_module = Arc::new(Mutex::new(FlyModule::new(minecraft.player.clone())));
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
 
-    let register_module = |module: Arc<Mutex<ModuleType>>| {
+fn register_modules(client: &DarkClient, minecraft: &'static Minecraft, safe_mode: bool) {
+    for module in built_in_modules(minecraft.get_mapping()) {
+        if safe_mode {
+            module
+                .lock()
+                .unwrap()
+                .get_module_data_mut()
+                .set_enabled(false);
+        }
         client.register_module(module);
-    };
-
-    register_module(fly_module);
+    }
 }
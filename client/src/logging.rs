@@ -0,0 +1,291 @@
+use log::{error, log, warn, Level};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Size cap of the log file in megabytes, 0 for no cap
+const LOG_MAX_MB_VAR: &str = "DARKCLIENT_LOG_MAX_MB";
+const DEFAULT_LOG_MAX_MB: u64 = 10;
+
+// Identical messages logged again within this window are only counted
+const REPEAT_WINDOW: Duration = Duration::from_secs(10);
+// Above this many tracked messages, the ones past their window are forgotten
+const MAX_TRACKED_MESSAGES: usize = 64;
+
+static REPEATS: Mutex<Option<RepeatFilter>> = Mutex::new(None);
+
+/// Unwrapping that logs the failure first, a panic in the injected library can take the
+/// whole game down with it, so prefer `log_warn_or_default` outside of critical paths
+pub trait LogExpect<T> {
+    /// Logs the error and panics, only for failures the client can't run without
+    fn log_expect(self, message: &str) -> T;
+
+    /// Logs the error as a warning and returns the default value instead of panicking
+    fn log_warn_or_default(self, message: &str) -> T
+    where
+        T: Default;
+
+    /// Logs the error and returns None instead of panicking, to propagate with `?`
+    fn log_err(self, message: &str) -> Option<T>;
+}
+
+impl<T, E: Display> LogExpect<T> for Result<T, E> {
+    fn log_expect(self, message: &str) -> T {
+        self.unwrap_or_else(|e| {
+            error!("{}: {}", message, e);
+            panic!("{}: {}", message, e);
+        })
+    }
+
+    fn log_warn_or_default(self, message: &str) -> T
+    where
+        T: Default,
+    {
+        self.unwrap_or_else(|e| {
+            warn!("{}: {}", message, e);
+            T::default()
+        })
+    }
+
+    fn log_err(self, message: &str) -> Option<T> {
+        self.map_err(|e| error!("{}: {}", message, e)).ok()
+    }
+}
+
+impl<T> LogExpect<T> for Option<T> {
+    fn log_expect(self, message: &str) -> T {
+        self.unwrap_or_else(|| {
+            error!("{}", message);
+            panic!("{}", message);
+        })
+    }
+
+    fn log_warn_or_default(self, message: &str) -> T
+    where
+        T: Default,
+    {
+        self.unwrap_or_else(|| {
+            warn!("{}", message);
+            T::default()
+        })
+    }
+
+    fn log_err(self, message: &str) -> Option<T> {
+        if self.is_none() {
+            error!("{}", message);
+        }
+        self
+    }
+}
+
+/// Logs `message` unless the same message was logged in the last 10 seconds, for errors
+/// that can happen every tick. Repeats are counted and reported with the first one logged
+/// after the window, as "<message> (repeated N times)"
+pub fn log_limited(level: Level, message: &str) {
+    let message = REPEATS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(RepeatFilter::default)
+        .filter(message, Instant::now());
+    if let Some(message) = message {
+        log!(level, "{}", message);
+    }
+}
+
+/// Log file rolled over to `<name>.1` once it reaches its size cap, replacing the previous
+/// `.1`, so a session keeps at most twice the cap on disk. Rolls over between lines only
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    // No cap if 0
+    max_size: u64,
+    at_line_start: bool,
+}
+
+// Same code as the agent's log_file.rs, keep the two copies identical
+impl RotatingFile {
+    // Creates the file, truncating it, capped at `max_size` bytes
+    pub fn create(path: impl Into<PathBuf>, max_size: u64) -> io::Result<Self> {
+        let path = path.into();
+        Ok(Self {
+            file: File::create(&path)?,
+            path,
+            written: 0,
+            max_size,
+            at_line_start: true,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        std::fs::rename(&self.path, rotated)?;
+
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.written >= self.max_size && self.at_line_start {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Size cap in bytes of the log file from DARKCLIENT_LOG_MAX_MB, 0 for no cap. It's read
+/// before the logger exists, so an invalid value comes back as a warning to log once
+/// the logger is initialized, and the default is used
+pub fn max_log_size() -> (u64, Option<String>) {
+    let (megabytes, warning) = match std::env::var(LOG_MAX_MB_VAR) {
+        Ok(value) => match value.trim().parse() {
+            Ok(megabytes) => (megabytes, None),
+            Err(_) => (
+                DEFAULT_LOG_MAX_MB,
+                Some(format!(
+                    "Invalid value {:?} for {}, using {}",
+                    value, LOG_MAX_MB_VAR, DEFAULT_LOG_MAX_MB
+                )),
+            ),
+        },
+        Err(_) => (DEFAULT_LOG_MAX_MB, None),
+    };
+    (megabytes.saturating_mul(1024 * 1024), warning)
+}
+
+#[derive(Debug, Default)]
+struct RepeatFilter {
+    messages: HashMap<String, Repeat>,
+}
+
+#[derive(Debug)]
+struct Repeat {
+    // When the message was last written
+    logged_at: Instant,
+    suppressed: u32,
+}
+
+impl RepeatFilter {
+    // Text to log for `message`, None while it repeats within its window
+    fn filter(&mut self, message: &str, now: Instant) -> Option<String> {
+        if let Some(repeat) = self.messages.get_mut(message) {
+            if now.duration_since(repeat.logged_at) < REPEAT_WINDOW {
+                repeat.suppressed += 1;
+                return None;
+            }
+
+            let suppressed = std::mem::take(&mut repeat.suppressed);
+            repeat.logged_at = now;
+            return Some(match suppressed {
+                0 => message.to_string(),
+                n => format!("{} (repeated {} times)", message, n),
+            });
+        }
+
+        if self.messages.len() >= MAX_TRACKED_MESSAGES {
+            self.messages
+                .retain(|_, repeat| now.duration_since(repeat.logged_at) < REPEAT_WINDOW);
+        }
+        self.messages.insert(
+            message.to_string(),
+            Repeat {
+                logged_at: now,
+                suppressed: 0,
+            },
+        );
+        Some(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_warn_or_default() {
+        let failed: Result<i32, String> = Err(String::from("Failed"));
+        assert_eq!(failed.log_warn_or_default("Reading value"), 0);
+        assert_eq!(Ok::<_, String>(5).log_warn_or_default("Reading value"), 5);
+        assert!(!None::<bool>.log_warn_or_default("Reading value"));
+    }
+
+    #[test]
+    fn test_log_err() {
+        let failed: Result<i32, String> = Err(String::from("Failed"));
+        assert_eq!(failed.log_err("Reading value"), None);
+        assert_eq!(Some(5).log_err("Reading value"), Some(5));
+    }
+
+    #[test]
+    fn test_rotating_file_rolls_over_between_lines() {
+        let directory = std::env::temp_dir().join(format!("darkclient_log_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("test.log");
+
+        let mut file = RotatingFile::create(&path, 4).unwrap();
+        file.write_all(b"first ").unwrap();
+        // Over the cap but mid line, nothing rolls over until the line ends
+        file.write_all(b"line\n").unwrap();
+        file.write_all(b"second ").unwrap();
+        file.write_all(b"line\n").unwrap();
+        file.flush().unwrap();
+
+        let rotated = std::fs::read_to_string(directory.join("test.log.1")).unwrap();
+        assert_eq!(rotated, "first line\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second line\n");
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_repeat_filter_collapses_repeats() {
+        let mut filter = RepeatFilter::default();
+        let start = Instant::now();
+
+        assert_eq!(filter.filter("Failed", start).as_deref(), Some("Failed"));
+        assert_eq!(
+            filter.filter("Failed", start + Duration::from_secs(1)),
+            None
+        );
+        assert_eq!(
+            filter.filter("Failed", start + Duration::from_secs(2)),
+            None
+        );
+        assert_eq!(
+            filter
+                .filter("Other", start + Duration::from_secs(2))
+                .as_deref(),
+            Some("Other")
+        );
+
+        let later = start + REPEAT_WINDOW + Duration::from_secs(1);
+        assert_eq!(
+            filter.filter("Failed", later).as_deref(),
+            Some("Failed (repeated 2 times)")
+        );
+        assert_eq!(
+            filter.filter("Failed", later + REPEAT_WINDOW).as_deref(),
+            Some("Failed")
+        );
+    }
+}
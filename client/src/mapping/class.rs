@@ -3,8 +3,9 @@ use anyhow::anyhow;
 use jni::objects::{JClass, JObject, JString, JValue, JValueOwned};
 use jni::JNIEnv;
 use serde::de::{MapAccess, Visitor};
-use serde::{Deserialize, Deserializer};
-use std::collections::HashMap;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 
 /// Custom deserializer that handles both single Method and Vec<Method> formats
@@ -46,6 +47,38 @@ where
     deserializer.deserialize_map(MethodsVisitor)
 }
 
+/// Custom serializer writing the format read by `deserialize_methods`, a single Method
+/// for a name without overloads and an array of methods otherwise, sorted by name
+fn serialize_methods<S>(
+    methods: &HashMap<String, Vec<Method>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let sorted: BTreeMap<_, _> = methods.iter().collect();
+    let mut map = serializer.serialize_map(Some(sorted.len()))?;
+    for (name, overloads) in sorted {
+        match overloads.as_slice() {
+            [method] => map.serialize_entry(name, method)?,
+            _ => map.serialize_entry(name, overloads)?,
+        }
+    }
+    map.end()
+}
+
+/// Serializes a map sorted by key, so a generated mapping file diffs cleanly
+pub(crate) fn serialize_sorted<S, V>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    V: Serialize,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
 /// Helper enum for deserializing either a single Method or Vec<Method>
 #[derive(Deserialize)]
 #[serde(untagged)]
@@ -55,44 +88,119 @@ enum MethodOrVec {
 }
 
 /// Represents a mapped Minecraft class with its methods and fields
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MinecraftClass {
     pub name: String,
-    #[serde(deserialize_with = "deserialize_methods", default)]
+    #[serde(
+        deserialize_with = "deserialize_methods",
+        serialize_with = "serialize_methods",
+        default
+    )]
     methods: HashMap<String, Vec<Method>>,
+    #[serde(serialize_with = "serialize_sorted")]
     fields: HashMap<String, Field>,
 }
 
 /// Represents a method with its obfuscated name and JNI signature
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Method {
     pub name: String,
     pub signature: String,
 }
 
 /// Represents a field with its obfuscated name
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Field {
     pub name: String,
 }
 
 /// Signature matching result for method resolution
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum SignatureMatch {
     Exact,
-    Compatible,
+    /// Needs conversions, costing more the further they are from the argument type
+    Compatible(u32),
     Incompatible,
 }
 
+// Costs of the conversions of object arguments. An exact class is an `Exact` match
+const SUBTYPE_COST: u32 = 1;
+const NULL_COST: u32 = 1;
+const OBJECT_COST: u32 = 2;
+
+impl SignatureMatch {
+    // Total cost of the conversions, None if incompatible
+    fn cost(self) -> Option<u32> {
+        match self {
+            SignatureMatch::Exact => Some(0),
+            SignatureMatch::Compatible(cost) => Some(cost),
+            SignatureMatch::Incompatible => None,
+        }
+    }
+}
+
+// Position of a primitive in the widening order, a widening costs the difference
+fn widening_rank(value: &JValue) -> u32 {
+    match value {
+        JValue::Byte(_) => 0,
+        JValue::Short(_) | JValue::Char(_) => 1,
+        JValue::Int(_) => 2,
+        JValue::Long(_) => 3,
+        JValue::Float(_) => 4,
+        _ => 5,
+    }
+}
+
+fn widening(to_rank: u32, value: &JValue) -> SignatureMatch {
+    SignatureMatch::Compatible(to_rank - widening_rank(value))
+}
+
 #[allow(dead_code)]
 impl MinecraftClass {
+    /// Creates a class without methods and fields, `name` is its obfuscated internal name
+    pub fn new(name: &str) -> MinecraftClass {
+        MinecraftClass {
+            name: name.to_string(),
+            methods: HashMap::new(),
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Adds a method, calling it again with the same `name` adds an overload
+    pub fn method(mut self, name: &str, obfuscated_name: &str, signature: &str) -> MinecraftClass {
+        self.methods
+            .entry(name.to_string())
+            .or_default()
+            .push(Method {
+                name: obfuscated_name.to_string(),
+                signature: signature.to_string(),
+            });
+        self
+    }
+
+    /// Adds a field, replacing any field with the same `name`
+    pub fn field(mut self, name: &str, obfuscated_name: &str) -> MinecraftClass {
+        self.fields.insert(
+            name.to_string(),
+            Field {
+                name: obfuscated_name.to_string(),
+            },
+        );
+        self
+    }
+
     pub fn get_method(&self, name: &str) -> anyhow::Result<&Method> {
-        match self.methods.get(name).unwrap().first() {
+        match self.methods.get(name).and_then(|methods| methods.first()) {
             Some(method) => Ok(method),
             None => Err(anyhow!("{} method not found", name)),
         }
     }
 
+    /// All the mapped methods, keyed by their deobfuscated name
+    pub fn get_all_methods(&self) -> &HashMap<String, Vec<Method>> {
+        &self.methods
+    }
+
     pub fn get_methods(&self, name: &str) -> anyhow::Result<&Vec<Method>> {
         match self.methods.get(name) {
             Some(methods) => Ok(methods),
@@ -120,25 +228,21 @@ impl MinecraftClass {
             return Ok(&methods[0]);
         }
 
-        // Find the best matching method based on argument compatibility
-        let mut best_method = None;
-        let mut best_match_quality = SignatureMatch::Incompatible;
-
-        for method in methods {
-            let match_quality = self.evaluate_signature_compatibility(&method.signature, args);
-
-            if match_quality == SignatureMatch::Exact {
-                // Exact match found, return immediately
-                return Ok(method);
-            }
-
-            if match_quality == SignatureMatch::Compatible
-                && best_match_quality != SignatureMatch::Exact
-            {
-                best_method = Some(method);
-                best_match_quality = match_quality;
-            }
-        }
+        // Pick the overload needing the cheapest conversions, ties are broken by signature
+        // so the choice doesn't depend on the order of the overloads
+        let best_method = methods
+            .iter()
+            .filter_map(|method| {
+                let cost = self
+                    .evaluate_signature_compatibility(&method.signature, args)
+                    .cost()?;
+                Some((cost, method))
+            })
+            .min_by(|(cost, method), (other_cost, other_method)| {
+                cost.cmp(other_cost)
+                    .then_with(|| method.signature.cmp(&other_method.signature))
+            })
+            .map(|(_, method)| method);
 
         match best_method {
             Some(method) => {
@@ -175,24 +279,19 @@ impl MinecraftClass {
             return SignatureMatch::Incompatible;
         }
 
-        let mut exact_matches = 0;
-        let mut compatible_matches = 0;
-
-        // Check each parameter for compatibility
+        // Sum the cost of each parameter conversion
+        let mut cost = 0;
         for (param_type, arg) in param_types.iter().zip(args.iter()) {
-            match self.check_type_compatibility(param_type, arg) {
-                SignatureMatch::Exact => exact_matches += 1,
-                SignatureMatch::Compatible => compatible_matches += 1,
-                SignatureMatch::Incompatible => return SignatureMatch::Incompatible,
+            match self.check_type_compatibility(param_type, arg).cost() {
+                Some(param_cost) => cost += param_cost,
+                None => return SignatureMatch::Incompatible,
             }
         }
 
-        if exact_matches == args.len() {
+        if cost == 0 {
             SignatureMatch::Exact
-        } else if exact_matches + compatible_matches == args.len() {
-            SignatureMatch::Compatible
         } else {
-            SignatureMatch::Incompatible
+            SignatureMatch::Compatible(cost)
         }
     }
 
@@ -229,7 +328,7 @@ impl MinecraftClass {
                 // Object types
                 'L' => {
                     let mut object_type = String::from("L");
-                    while let Some(ch) = chars.next() {
+                    for ch in chars.by_ref() {
                         object_type.push(ch);
                         if ch == ';' {
                             break;
@@ -246,7 +345,7 @@ impl MinecraftClass {
                                 array_type.push(chars.next().unwrap());
                             }
                             'L' => {
-                                while let Some(ch) = chars.next() {
+                                for ch in chars.by_ref() {
                                     array_type.push(ch);
                                     if ch == ';' {
                                         break;
@@ -279,14 +378,12 @@ impl MinecraftClass {
             ("D", JValue::Double(_)) => SignatureMatch::Exact,
 
             // Numeric type promotions (compatible matches)
-            ("I", JValue::Byte(_) | JValue::Short(_) | JValue::Char(_)) => {
-                SignatureMatch::Compatible
-            }
+            ("I", JValue::Byte(_) | JValue::Short(_) | JValue::Char(_)) => widening(2, value),
             ("J", JValue::Byte(_) | JValue::Short(_) | JValue::Char(_) | JValue::Int(_)) => {
-                SignatureMatch::Compatible
+                widening(3, value)
             }
             ("F", JValue::Byte(_) | JValue::Short(_) | JValue::Char(_) | JValue::Int(_)) => {
-                SignatureMatch::Compatible
+                widening(4, value)
             }
             (
                 "D",
@@ -296,7 +393,7 @@ impl MinecraftClass {
                 | JValue::Int(_)
                 | JValue::Long(_)
                 | JValue::Float(_),
-            ) => SignatureMatch::Compatible,
+            ) => widening(5, value),
 
             // Object types - with proper type checking
             (jni_type, JValue::Object(obj))
@@ -313,7 +410,7 @@ impl MinecraftClass {
                 if jni_type.starts_with('L') || jni_type.starts_with('[') =>
             {
                 if obj.is_null() {
-                    SignatureMatch::Compatible
+                    SignatureMatch::Compatible(NULL_COST)
                 } else {
                     SignatureMatch::Incompatible
                 }
@@ -331,7 +428,7 @@ impl MinecraftClass {
     ) -> SignatureMatch {
         // Handle null objects - they're compatible with any object type
         if obj.is_null() {
-            return SignatureMatch::Compatible;
+            return SignatureMatch::Compatible(NULL_COST);
         }
 
         // Get the actual class name from the JNI type signature
@@ -340,11 +437,11 @@ impl MinecraftClass {
 
         // Special case for java.lang.Object - everything is compatible
         if expected_class_name == "java/lang/Object" {
-            return SignatureMatch::Compatible;
+            return SignatureMatch::Compatible(OBJECT_COST);
         }
 
         // Get JNI environment to check actual object type
-        if let Ok(mut env) = DarkClient::instance().get_env() {
+        if let Ok(mut env) = DarkClient::instance().and_then(|client| Ok(client.get_env()?)) {
             // Get the actual class of the object
             if let Ok(obj_class) = env.get_object_class(obj) {
                 // Check for exact class match first
@@ -358,15 +455,18 @@ impl MinecraftClass {
                     // Check if the object is an instance of the expected type (inheritance/interface)
                     if let Ok(is_instance) = env.is_instance_of(obj, &expected_class) {
                         if is_instance {
-                            return SignatureMatch::Compatible;
+                            return SignatureMatch::Compatible(SUBTYPE_COST);
                         }
                     }
+                } else {
+                    // Clear the NoClassDefFoundError so the call that follows isn't aborted
+                    let _ = env.exception_clear();
                 }
 
                 // Additional check for common Java types that might have special handling
                 if let Ok(class_name) = self.get_class_name_from_object(&mut env, &obj_class) {
                     if self.are_compatible_types(&class_name, expected_class_name) {
-                        return SignatureMatch::Compatible;
+                        return SignatureMatch::Compatible(SUBTYPE_COST);
                     }
                 }
             }
@@ -383,10 +483,10 @@ impl MinecraftClass {
     ) -> SignatureMatch {
         // Handle null arrays
         if obj.is_null() {
-            return SignatureMatch::Compatible;
+            return SignatureMatch::Compatible(NULL_COST);
         }
 
-        if let Ok(mut env) = DarkClient::instance().get_env() {
+        if let Ok(mut env) = DarkClient::instance().and_then(|client| Ok(client.get_env()?)) {
             // Check if the object is actually an array
             if let Ok(obj_class) = env.get_object_class(obj) {
                 if let Ok(class_name) = self.get_class_name_from_object(&mut env, &obj_class) {
@@ -400,7 +500,7 @@ impl MinecraftClass {
                         // For compatible match, check if array types are compatible
                         // This is a simplified check - could be enhanced for inheritance
                         if self.are_compatible_array_types(&class_name, expected_type) {
-                            return SignatureMatch::Compatible;
+                            return SignatureMatch::Compatible(SUBTYPE_COST);
                         }
                     }
                 }
@@ -540,6 +640,75 @@ mod tests {
         assert_eq!(class.extract_parameter_types("([I)V").unwrap(), vec!["[I"]);
     }
 
+    #[test]
+    fn test_methods_serialize_single_or_array() {
+        let class = MinecraftClass::new("abc")
+            .method("tick", "a", "()V")
+            .method("move", "b", "(D)V")
+            .method("move", "b", "(DD)V")
+            .field("level", "c");
+
+        let json = serde_json::to_value(&class).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "abc",
+                "methods": {
+                    "move": [
+                        {"name": "b", "signature": "(D)V"},
+                        {"name": "b", "signature": "(DD)V"}
+                    ],
+                    "tick": {"name": "a", "signature": "()V"}
+                },
+                "fields": {"level": {"name": "c"}}
+            })
+        );
+
+        let parsed: MinecraftClass = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.get_methods("move").unwrap().len(), 2);
+        assert_eq!(parsed.get_method("tick").unwrap().name, "a");
+        assert_eq!(parsed.get_field("level").unwrap().name, "c");
+    }
+
+    #[test]
+    fn test_method_by_args_prefers_fewest_widenings() {
+        let class = MinecraftClass::new("abc")
+            .method("move", "a", "(D)V")
+            .method("move", "a", "(J)V")
+            .method("move", "a", "(F)V");
+
+        // int widens to long in one step, to float in two and to double in three
+        let method = class.get_method_by_args("move", &[JValue::Int(1)]).unwrap();
+        assert_eq!(method.signature, "(J)V");
+
+        let class = MinecraftClass::new("abc")
+            .method("move", "a", "(D)V")
+            .method("move", "a", "(F)V");
+        let method = class.get_method_by_args("move", &[JValue::Int(1)]).unwrap();
+        assert_eq!(method.signature, "(F)V");
+    }
+
+    #[test]
+    fn test_method_by_args_ignores_overload_order() {
+        let args = [JValue::Short(1), JValue::Short(2)];
+        let forward = MinecraftClass::new("abc")
+            .method("set", "a", "(IJ)V")
+            .method("set", "a", "(JI)V");
+        let backward = MinecraftClass::new("abc")
+            .method("set", "a", "(JI)V")
+            .method("set", "a", "(IJ)V");
+
+        // Both cost the same, the tie is broken by signature
+        assert_eq!(
+            forward.get_method_by_args("set", &args).unwrap().signature,
+            "(IJ)V"
+        );
+        assert_eq!(
+            backward.get_method_by_args("set", &args).unwrap().signature,
+            "(IJ)V"
+        );
+    }
+
     #[test]
     fn test_type_compatibility() {
         let class = MinecraftClass {
@@ -557,7 +726,7 @@ mod tests {
         // Test compatible matches (promotion)
         assert_eq!(
             class.check_type_compatibility("I", &JValue::Byte(42)),
-            SignatureMatch::Compatible
+            SignatureMatch::Compatible(2)
         );
 
         // Test incompatible matches
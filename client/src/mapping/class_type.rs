@@ -4,26 +4,223 @@ use std::fmt;
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MinecraftClassType {
     Minecraft,
+    BlockableEventLoop,
     LocalPlayer,
     Level,
     Player,
     Abilities,
     Entity,
+    LivingEntity,
+    Attributes,
+    AttributeInstance,
+    Holder,
     Vec3,
+    Vec2,
+    BlockGetter,
+    ClipContext,
+    ClipContextBlock,
+    ClipContextFluid,
+    HitResult,
+    HitResultType,
+    BlockHitResult,
+    EntityHitResult,
+    Vec3i,
+    MultiPlayerGameMode,
+    Options,
+    KeyMapping,
     Window,
+    InputConstants,
+    ClientInput,
+    PlayerInput,
+    Toast,
+    ToastVisibility,
+    ToastManager,
+    GuiGraphics,
+    Font,
+    Screen,
+    DisconnectedScreen,
+    ConnectScreen,
+    ServerAddress,
+    ServerData,
+    ClientPacketListener,
+    ClientCommonPacketListenerImpl,
+    PlayerInfo,
+    Connection,
+    Packet,
+    ServerboundMovePlayerPacket,
+    ServerboundCustomPayloadPacket,
+    BrandPayload,
+    ClientboundSystemChatPacket,
+    ClientboundPlayerChatPacket,
+    SignedMessageBodyPacked,
+    Component,
+    HolderReference,
+    SoundManager,
+    SoundEvents,
+    SimpleSoundInstance,
 }
 
 impl MinecraftClassType {
+    /// Every class type, in declaration order
+    pub const ALL: [MinecraftClassType; 55] = [
+        MinecraftClassType::Minecraft,
+        MinecraftClassType::BlockableEventLoop,
+        MinecraftClassType::LocalPlayer,
+        MinecraftClassType::Level,
+        MinecraftClassType::Player,
+        MinecraftClassType::Abilities,
+        MinecraftClassType::Entity,
+        MinecraftClassType::LivingEntity,
+        MinecraftClassType::Attributes,
+        MinecraftClassType::AttributeInstance,
+        MinecraftClassType::Holder,
+        MinecraftClassType::Vec3,
+        MinecraftClassType::Vec2,
+        MinecraftClassType::BlockGetter,
+        MinecraftClassType::ClipContext,
+        MinecraftClassType::ClipContextBlock,
+        MinecraftClassType::ClipContextFluid,
+        MinecraftClassType::HitResult,
+        MinecraftClassType::HitResultType,
+        MinecraftClassType::BlockHitResult,
+        MinecraftClassType::EntityHitResult,
+        MinecraftClassType::Vec3i,
+        MinecraftClassType::MultiPlayerGameMode,
+        MinecraftClassType::Options,
+        MinecraftClassType::KeyMapping,
+        MinecraftClassType::Window,
+        MinecraftClassType::InputConstants,
+        MinecraftClassType::ClientInput,
+        MinecraftClassType::PlayerInput,
+        MinecraftClassType::Toast,
+        MinecraftClassType::ToastVisibility,
+        MinecraftClassType::ToastManager,
+        MinecraftClassType::GuiGraphics,
+        MinecraftClassType::Font,
+        MinecraftClassType::Screen,
+        MinecraftClassType::DisconnectedScreen,
+        MinecraftClassType::ConnectScreen,
+        MinecraftClassType::ServerAddress,
+        MinecraftClassType::ServerData,
+        MinecraftClassType::ClientPacketListener,
+        MinecraftClassType::ClientCommonPacketListenerImpl,
+        MinecraftClassType::PlayerInfo,
+        MinecraftClassType::Connection,
+        MinecraftClassType::Packet,
+        MinecraftClassType::ServerboundMovePlayerPacket,
+        MinecraftClassType::ServerboundCustomPayloadPacket,
+        MinecraftClassType::BrandPayload,
+        MinecraftClassType::ClientboundSystemChatPacket,
+        MinecraftClassType::ClientboundPlayerChatPacket,
+        MinecraftClassType::SignedMessageBodyPacked,
+        MinecraftClassType::Component,
+        MinecraftClassType::HolderReference,
+        MinecraftClassType::SoundManager,
+        MinecraftClassType::SoundEvents,
+        MinecraftClassType::SimpleSoundInstance,
+    ];
+
+    /// Classes the client itself needs, without them no module can work
+    pub const CORE: [MinecraftClassType; 8] = [
+        MinecraftClassType::Minecraft,
+        MinecraftClassType::BlockableEventLoop,
+        MinecraftClassType::LocalPlayer,
+        MinecraftClassType::Player,
+        MinecraftClassType::Entity,
+        MinecraftClassType::Vec3,
+        MinecraftClassType::Window,
+        MinecraftClassType::InputConstants,
+    ];
+
     pub fn get_name(&self) -> &str {
         match self {
             MinecraftClassType::Minecraft => "net/minecraft/client/Minecraft",
+            MinecraftClassType::BlockableEventLoop => {
+                "net/minecraft/util/thread/BlockableEventLoop"
+            }
             MinecraftClassType::LocalPlayer => "net/minecraft/client/player/LocalPlayer",
             MinecraftClassType::Level => "net/minecraft/client/multiplayer/ClientLevel",
             MinecraftClassType::Player => "net/minecraft/world/entity/player/Player",
             MinecraftClassType::Abilities => "net/minecraft/world/entity/player/Abilities",
             MinecraftClassType::Entity => "net/minecraft/world/entity/Entity",
+            MinecraftClassType::LivingEntity => "net/minecraft/world/entity/LivingEntity",
+            MinecraftClassType::Attributes => "net/minecraft/world/entity/ai/attributes/Attributes",
+            MinecraftClassType::AttributeInstance => {
+                "net/minecraft/world/entity/ai/attributes/AttributeInstance"
+            }
+            MinecraftClassType::Holder => "net/minecraft/core/Holder",
             MinecraftClassType::Vec3 => "net/minecraft/world/phys/Vec3",
+            MinecraftClassType::Vec2 => "net/minecraft/world/phys/Vec2",
+            MinecraftClassType::BlockGetter => "net/minecraft/world/level/BlockGetter",
+            MinecraftClassType::ClipContext => "net/minecraft/world/level/ClipContext",
+            MinecraftClassType::ClipContextBlock => "net/minecraft/world/level/ClipContext$Block",
+            MinecraftClassType::ClipContextFluid => "net/minecraft/world/level/ClipContext$Fluid",
+            MinecraftClassType::HitResult => "net/minecraft/world/phys/HitResult",
+            MinecraftClassType::HitResultType => "net/minecraft/world/phys/HitResult$Type",
+            MinecraftClassType::BlockHitResult => "net/minecraft/world/phys/BlockHitResult",
+            MinecraftClassType::EntityHitResult => "net/minecraft/world/phys/EntityHitResult",
+            MinecraftClassType::Vec3i => "net/minecraft/core/Vec3i",
+            MinecraftClassType::MultiPlayerGameMode => {
+                "net/minecraft/client/multiplayer/MultiPlayerGameMode"
+            }
+            MinecraftClassType::Options => "net/minecraft/client/Options",
+            MinecraftClassType::KeyMapping => "net/minecraft/client/KeyMapping",
             MinecraftClassType::Window => "com/mojang/blaze3d/platform/Window",
+            MinecraftClassType::InputConstants => "com/mojang/blaze3d/platform/InputConstants",
+            MinecraftClassType::ClientInput => "net/minecraft/client/player/ClientInput",
+            MinecraftClassType::PlayerInput => "net/minecraft/world/entity/player/Input",
+            MinecraftClassType::Toast => "net/minecraft/client/gui/components/toasts/Toast",
+            MinecraftClassType::ToastVisibility => {
+                "net/minecraft/client/gui/components/toasts/Toast$Visibility"
+            }
+            MinecraftClassType::ToastManager => {
+                "net/minecraft/client/gui/components/toasts/ToastManager"
+            }
+            MinecraftClassType::GuiGraphics => "net/minecraft/client/gui/GuiGraphics",
+            MinecraftClassType::Font => "net/minecraft/client/gui/Font",
+            MinecraftClassType::Screen => "net/minecraft/client/gui/screens/Screen",
+            MinecraftClassType::DisconnectedScreen => {
+                "net/minecraft/client/gui/screens/DisconnectedScreen"
+            }
+            MinecraftClassType::ConnectScreen => "net/minecraft/client/gui/screens/ConnectScreen",
+            MinecraftClassType::ServerAddress => {
+                "net/minecraft/client/multiplayer/resolver/ServerAddress"
+            }
+            MinecraftClassType::ServerData => "net/minecraft/client/multiplayer/ServerData",
+            MinecraftClassType::ClientPacketListener => {
+                "net/minecraft/client/multiplayer/ClientPacketListener"
+            }
+            MinecraftClassType::ClientCommonPacketListenerImpl => {
+                "net/minecraft/client/multiplayer/ClientCommonPacketListenerImpl"
+            }
+            MinecraftClassType::PlayerInfo => "net/minecraft/client/multiplayer/PlayerInfo",
+            MinecraftClassType::Connection => "net/minecraft/network/Connection",
+            MinecraftClassType::Packet => "net/minecraft/network/protocol/Packet",
+            MinecraftClassType::ServerboundMovePlayerPacket => {
+                "net/minecraft/network/protocol/game/ServerboundMovePlayerPacket"
+            }
+            MinecraftClassType::ServerboundCustomPayloadPacket => {
+                "net/minecraft/network/protocol/common/ServerboundCustomPayloadPacket"
+            }
+            MinecraftClassType::BrandPayload => {
+                "net/minecraft/network/protocol/common/custom/BrandPayload"
+            }
+            MinecraftClassType::ClientboundSystemChatPacket => {
+                "net/minecraft/network/protocol/game/ClientboundSystemChatPacket"
+            }
+            MinecraftClassType::ClientboundPlayerChatPacket => {
+                "net/minecraft/network/protocol/game/ClientboundPlayerChatPacket"
+            }
+            MinecraftClassType::SignedMessageBodyPacked => {
+                "net/minecraft/network/chat/SignedMessageBody$Packed"
+            }
+            MinecraftClassType::Component => "net/minecraft/network/chat/Component",
+            MinecraftClassType::HolderReference => "net/minecraft/core/Holder$Reference",
+            MinecraftClassType::SoundManager => "net/minecraft/client/sounds/SoundManager",
+            MinecraftClassType::SoundEvents => "net/minecraft/sounds/SoundEvents",
+            MinecraftClassType::SimpleSoundInstance => {
+                "net/minecraft/client/resources/sounds/SimpleSoundInstance"
+            }
         }
     }
 }
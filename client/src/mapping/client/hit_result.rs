@@ -0,0 +1,156 @@
+use crate::mapping::entity::Entity;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::mapping::{FieldType, MinecraftClassType};
+use jni::objects::{GlobalRef, JObject};
+
+/// What the crosshair points at, read from `Minecraft.hitResult`
+#[derive(Debug, Clone)]
+pub enum HitResult {
+    /// A block, `location` is the exact point hit on its face
+    Block {
+        jni_ref: GlobalRef,
+        location: (f64, f64, f64),
+        block_pos: (i32, i32, i32),
+    },
+    /// An entity in range, `location` is the point hit on its bounding box
+    Entity {
+        jni_ref: GlobalRef,
+        location: (f64, f64, f64),
+        entity: Entity,
+    },
+}
+
+#[allow(dead_code)]
+impl HitResult {
+    /// Reads the hit result of `minecraft`, None when it's null or a miss, i.e. when
+    /// looking at nothing in range
+    pub fn read<J: JvmAccess + ?Sized>(
+        jvm: &J,
+        minecraft: &JObject,
+    ) -> anyhow::Result<Option<HitResult>> {
+        let hit = jvm.get_field_optional(
+            MinecraftClassType::Minecraft,
+            minecraft,
+            "hitResult",
+            FieldType::Object(MinecraftClassType::HitResult, jvm.mapping()),
+        )?;
+        let Some(hit) = hit else {
+            return Ok(None);
+        };
+
+        let hit_type = jvm
+            .call_method(MinecraftClassType::HitResult, hit.as_obj(), "getType", &[])?
+            .l()?;
+        let is_type = |name: &str| -> anyhow::Result<bool> {
+            let expected = jvm
+                .get_static_field(
+                    MinecraftClassType::HitResultType,
+                    name,
+                    FieldType::Object(MinecraftClassType::HitResultType, jvm.mapping()),
+                )?
+                .l()?;
+            jvm.is_same_object(&hit_type, &expected)
+        };
+
+        if is_type("BLOCK")? {
+            let block_pos = jvm
+                .call_method(
+                    MinecraftClassType::BlockHitResult,
+                    hit.as_obj(),
+                    "getBlockPos",
+                    &[],
+                )?
+                .l()?;
+            let block_pos = read_vec3i(jvm, &block_pos)?;
+            let location = read_location(jvm, hit.as_obj())?;
+
+            Ok(Some(HitResult::Block {
+                jni_ref: hit,
+                location,
+                block_pos,
+            }))
+        } else if is_type("ENTITY")? {
+            let entity = jvm
+                .call_method(
+                    MinecraftClassType::EntityHitResult,
+                    hit.as_obj(),
+                    "getEntity",
+                    &[],
+                )?
+                .l()?;
+            let entity = Entity::new(jvm.new_global_ref(&entity)?);
+            let location = read_location(jvm, hit.as_obj())?;
+
+            Ok(Some(HitResult::Entity {
+                jni_ref: hit,
+                location,
+                entity,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_block(&self) -> bool {
+        matches!(self, HitResult::Block { .. })
+    }
+
+    pub fn is_entity(&self) -> bool {
+        matches!(self, HitResult::Entity { .. })
+    }
+
+    /// The exact point hit
+    pub fn location(&self) -> (f64, f64, f64) {
+        match self {
+            HitResult::Block { location, .. } | HitResult::Entity { location, .. } => *location,
+        }
+    }
+
+    /// Position of the block hit, None for an entity
+    pub fn block_pos(&self) -> Option<(i32, i32, i32)> {
+        match self {
+            HitResult::Block { block_pos, .. } => Some(*block_pos),
+            HitResult::Entity { .. } => None,
+        }
+    }
+
+    /// The entity hit, None for a block
+    pub fn entity(&self) -> Option<&Entity> {
+        match self {
+            HitResult::Entity { entity, .. } => Some(entity),
+            HitResult::Block { .. } => None,
+        }
+    }
+
+    /// The `BlockHitResult` or `EntityHitResult` of the game
+    pub fn jni_ref(&self) -> &GlobalRef {
+        match self {
+            HitResult::Block { jni_ref, .. } | HitResult::Entity { jni_ref, .. } => jni_ref,
+        }
+    }
+}
+
+fn read_location<J: JvmAccess + ?Sized>(jvm: &J, hit: &JObject) -> anyhow::Result<(f64, f64, f64)> {
+    let vec3 = jvm
+        .call_method(MinecraftClassType::HitResult, hit, "getLocation", &[])?
+        .l()?;
+
+    let mut coordinates = [0.0; 3];
+    for (coordinate, name) in coordinates.iter_mut().zip(["x", "y", "z"]) {
+        *coordinate = jvm
+            .get_field(MinecraftClassType::Vec3, &vec3, name, FieldType::Double)?
+            .d()?;
+    }
+    Ok((coordinates[0], coordinates[1], coordinates[2]))
+}
+
+// Coordinates of a BlockPos, through its Vec3i getters
+fn read_vec3i<J: JvmAccess + ?Sized>(jvm: &J, vec3i: &JObject) -> anyhow::Result<(i32, i32, i32)> {
+    let mut coordinates = [0; 3];
+    for (coordinate, getter) in coordinates.iter_mut().zip(["getX", "getY", "getZ"]) {
+        *coordinate = jvm
+            .call_method(MinecraftClassType::Vec3i, vec3i, getter, &[])?
+            .i()?;
+    }
+    Ok((coordinates[0], coordinates[1], coordinates[2]))
+}
@@ -1,55 +1,73 @@
+use crate::client::DarkClient;
+use crate::mapping::client::hit_result::HitResult;
+use crate::mapping::client::sound::SoundManager;
 use crate::mapping::client::window::Window;
 use crate::mapping::client::world::World;
 use crate::mapping::entity::player::LocalPlayer;
-use crate::mapping::{Mapping, MinecraftClassType};
-use jni::objects::GlobalRef;
-use log::error;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::mapping::{FieldType, Mapping, MinecraftClassType};
+use jni::objects::{GlobalRef, JObject, JValue};
 use std::ops::Deref;
-use std::sync::{Arc, OnceLock};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, PoisonError};
 
 #[derive(Debug)]
 pub struct Minecraft {
     pub jni_ref: GlobalRef,
     mapping: Mapping,
-    pub player: LocalPlayer,
-    #[allow(dead_code)]
-    pub world: World,
     pub window: Window,
 }
 
+static INSTANCE: OnceLock<Minecraft> = OnceLock::new();
+
+// Held while initializing, so only one thread at a time reads the game
+static INIT_LOCK: Mutex<()> = Mutex::new(());
+
 impl Minecraft {
-    pub fn instance() -> &'static Minecraft {
-        static INSTANCE: OnceLock<Arc<Minecraft>> = OnceLock::new();
-
-        INSTANCE.get_or_init(|| unsafe {
-            Arc::new(Minecraft::new().unwrap_or_else(|e| {
-                error!("Failed to initialize Minecraft: {:?}", e);
-                panic!("Failed to initialize Minecraft");
-            }))
-        })
+    /// The initialized game, fails until startup got `try_instance`
+    pub fn instance() -> anyhow::Result<&'static Minecraft> {
+        INSTANCE
+            .get()
+            .ok_or_else(|| anyhow::anyhow!("Minecraft used before being initialized"))
+    }
+
+    /// The game, initializing it if needed. A failure, e.g. at the title screen without a
+    /// world, isn't cached and the next call tries again
+    pub fn try_instance() -> anyhow::Result<&'static Minecraft> {
+        if let Some(minecraft) = INSTANCE.get() {
+            return Ok(minecraft);
+        }
+
+        let _init = INIT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have finished while this one waited for the lock
+        if let Some(minecraft) = INSTANCE.get() {
+            return Ok(minecraft);
+        }
+
+        let minecraft = unsafe { Minecraft::new()? };
+        Ok(INSTANCE.get_or_init(|| minecraft))
     }
 
     unsafe fn new() -> anyhow::Result<Minecraft> {
-        let mapping = Mapping::new()?;
+        let mapping = Mapping::load()?;
+        // Fail here with one clear error if the game version doesn't match the mapping
+        mapping.check_supported()?;
+
         let minecraft = mapping
             .call_static_method(MinecraftClassType::Minecraft, "getInstance", &[])?
             .l()?;
 
         if minecraft.is_null() {
-            error!("Minecraft is null")
+            return Err(anyhow::anyhow!("Minecraft is null"));
         }
 
         let minecraft = mapping.new_global_ref(minecraft)?;
 
-        let player = LocalPlayer::new(&minecraft, &mapping)?;
-        let world = World::new(&minecraft, &mapping)?;
         let window = Window::new(&minecraft, &mapping)?;
 
         Ok(Minecraft {
             jni_ref: minecraft,
             mapping,
-            player,
-            world,
             window,
         })
     }
@@ -57,6 +75,174 @@ impl Minecraft {
     pub fn get_mapping(&self) -> &Mapping {
         &self.mapping
     }
+
+    /// The local player, read from the game on every call so it's never one from a world
+    /// left since. Fails without a world, e.g. at the title screen
+    pub fn player(&self) -> anyhow::Result<LocalPlayer> {
+        LocalPlayer::new(&self.jni_ref, &self.mapping)
+    }
+
+    /// The current level, fails without a world
+    #[allow(dead_code)]
+    pub fn world(&self) -> anyhow::Result<World> {
+        World::new(&self.jni_ref, &self.mapping)
+    }
+
+    /// What the crosshair points at, None when looking at nothing in range or before
+    /// the first tick of a world
+    pub fn get_hit_result(&self) -> anyhow::Result<Option<HitResult>> {
+        self.mapping.hit_result(self.jni_ref.as_obj())
+    }
+
+    /// The `MultiPlayerGameMode` handling block breaking and item use, also in
+    /// singleplayer. None without a world
+    pub fn get_game_mode(&self) -> anyhow::Result<Option<GlobalRef>> {
+        self.mapping.game_mode(self.jni_ref.as_obj())
+    }
+
+    /// Whether the key or mouse button bound to `key_mapping`, a `KeyMapping` field of
+    /// `Options` such as "keyAttack", is held. Follows the bindings of the player
+    pub fn is_key_mapping_down(&self, key_mapping: &str) -> anyhow::Result<bool> {
+        self.mapping
+            .is_key_mapping_down(self.jni_ref.as_obj(), key_mapping)
+    }
+
+    /// Screen currently open, None while playing without any menu
+    pub fn get_screen(&self) -> anyhow::Result<Option<GlobalRef>> {
+        self.mapping.screen(self.jni_ref.as_obj())
+    }
+
+    /// Connection still logging in to a server, None once joined or when not connecting
+    pub fn get_pending_connection(&self) -> anyhow::Result<Option<GlobalRef>> {
+        self.mapping.pending_connection(self.jni_ref.as_obj())
+    }
+
+    /// Data of the multiplayer server the client is connected to, None in singleplayer and menus
+    pub fn get_current_server(&self) -> anyhow::Result<Option<GlobalRef>> {
+        self.mapping.current_server(self.jni_ref.as_obj())
+    }
+
+    /// Starts connecting to the server described by `server_data`, showing the connect screen.
+    /// Must be called on the render thread
+    pub fn connect_to_server(
+        &self,
+        parent: &JObject,
+        server_data: &GlobalRef,
+    ) -> anyhow::Result<()> {
+        let mapping = &self.mapping;
+
+        let ip = mapping
+            .get_field(
+                MinecraftClassType::ServerData,
+                server_data.as_obj(),
+                "ip",
+                FieldType::String,
+            )?
+            .l()?;
+        let address = mapping
+            .call_static_method(
+                MinecraftClassType::ServerAddress,
+                "parseString",
+                &[JValue::Object(&ip)],
+            )?
+            .l()?;
+
+        let no_transfer = JObject::null();
+        mapping.call_static_method(
+            MinecraftClassType::ConnectScreen,
+            "startConnecting",
+            &[
+                JValue::Object(parent),
+                JValue::Object(self.jni_ref.as_obj()),
+                JValue::Object(&address),
+                JValue::Object(server_data.as_obj()),
+                JValue::Bool(0),
+                JValue::Object(&no_transfer),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_sound_manager(&self) -> anyhow::Result<SoundManager> {
+        SoundManager::new(&self.jni_ref, &self.mapping)
+    }
+
+    /// Number of client ticks since the game started, it keeps counting in menus
+    pub fn get_client_tick_count(&self) -> anyhow::Result<i32> {
+        Ok(self
+            .mapping
+            .get_field(
+                MinecraftClassType::Minecraft,
+                self.jni_ref.as_obj(),
+                "clientTickCount",
+                FieldType::Int,
+            )?
+            .i()?)
+    }
+
+    /// Latency of the local player in milliseconds, as shown in the player list. It's
+    /// read from the player's entry in the online players of the connection, so it fails
+    /// when not connected
+    pub fn get_player_ping(&self) -> anyhow::Result<i32> {
+        let player = self.player()?;
+        let connection = player.get_connection()?;
+        let uuid = self
+            .mapping
+            .call_method(
+                MinecraftClassType::Entity,
+                player.jni_ref.as_obj(),
+                "getUUID",
+                &[],
+            )?
+            .l()?;
+
+        let player_info = self
+            .mapping
+            .call_method(
+                MinecraftClassType::ClientPacketListener,
+                connection.as_obj(),
+                "getPlayerInfo",
+                &[JValue::Object(&uuid)],
+            )?
+            .l()?;
+        if player_info.is_null() {
+            return Err(anyhow::anyhow!("Player is not in the online players"));
+        }
+
+        Ok(self
+            .mapping
+            .call_method(
+                MinecraftClassType::PlayerInfo,
+                &player_info,
+                "getLatency",
+                &[],
+            )?
+            .i()?)
+    }
+
+    /// Absolute path of the game directory, usually `.minecraft` or the launcher's
+    /// instance folder
+    pub fn get_game_directory(&self) -> anyhow::Result<PathBuf> {
+        let mut env = DarkClient::instance()?.get_env()?;
+
+        // gameDirectory is a java.io.File, which isn't in the mapping
+        let field = self
+            .mapping
+            .get_class(MinecraftClassType::Minecraft.get_name())?
+            .get_field("gameDirectory")?;
+        let directory = env
+            .get_field(self.jni_ref.as_obj(), &field.name, "Ljava/io/File;")?
+            .l()?;
+        if directory.is_null() {
+            return Err(anyhow::anyhow!("Game directory is not set"));
+        }
+
+        let path = env
+            .call_method(&directory, "getAbsolutePath", "()Ljava/lang/String;", &[])?
+            .l()?;
+        Ok(PathBuf::from(self.mapping.get_string(path)?))
+    }
 }
 
 impl Deref for Minecraft {
@@ -1,3 +1,5 @@
+pub mod hit_result;
 pub mod minecraft;
+pub mod sound;
 pub mod window;
 pub mod world;
@@ -0,0 +1,73 @@
+use crate::mapping::{FieldType, GameContext, Mapping, MinecraftClassType};
+use jni::objects::{GlobalRef, JValue};
+use std::ops::Deref;
+
+/// The game's sound manager, its methods must be called on the render thread
+#[derive(Debug)]
+pub struct SoundManager {
+    pub jni_ref: GlobalRef,
+}
+
+impl GameContext for SoundManager {}
+
+impl SoundManager {
+    pub fn new(minecraft: &GlobalRef, mapping: &Mapping) -> anyhow::Result<SoundManager> {
+        let sound_manager = mapping
+            .call_method(
+                MinecraftClassType::Minecraft,
+                minecraft.as_obj(),
+                "getSoundManager",
+                &[],
+            )?
+            .l()?;
+
+        Ok(SoundManager {
+            jni_ref: mapping.new_global_ref(sound_manager)?,
+        })
+    }
+
+    /// Plays a UI sound (not positioned in the world), `sound` is the name of a
+    /// `SoundEvents` field such as `UI_BUTTON_CLICK`
+    pub fn play_ui_sound(&self, sound: &str, pitch: f32) -> anyhow::Result<()> {
+        let mapping = self.mapping()?;
+
+        let holder = mapping
+            .get_static_field(
+                MinecraftClassType::SoundEvents,
+                sound,
+                FieldType::Object(MinecraftClassType::HolderReference, mapping),
+            )?
+            .l()?;
+        let sound_event = mapping
+            .call_method(MinecraftClassType::Holder, &holder, "value", &[])?
+            .l()?;
+
+        // forUI(SoundEvent, pitch, volume), the two arguments overloads are ambiguous
+        let instance = mapping
+            .call_static_method(
+                MinecraftClassType::SimpleSoundInstance,
+                "forUI",
+                &[
+                    JValue::Object(&sound_event),
+                    JValue::Float(pitch),
+                    JValue::Float(1.0),
+                ],
+            )?
+            .l()?;
+
+        mapping.call_void_method(
+            MinecraftClassType::SoundManager,
+            self.jni_ref.as_obj(),
+            "play",
+            &[JValue::Object(&instance)],
+        )
+    }
+}
+
+impl Deref for SoundManager {
+    type Target = GlobalRef;
+
+    fn deref(&self) -> &Self::Target {
+        &self.jni_ref
+    }
+}
@@ -1,12 +1,35 @@
+use crate::client::DarkClient;
 use crate::mapping::method::MethodName;
+use crate::mapping::minecraft_version::MinecraftVersion;
 use crate::mapping::{GameContext, Mapping, MinecraftClassType};
-use jni::objects::GlobalRef;
+use jni::objects::{GlobalRef, JClass, JObject, JValue};
 use jni::sys::jlong;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::OnceLock;
+
+/// Native GLFW window pointer, as returned by `Window.handle` (`getWindow` before 1.21.9)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowHandle(jlong);
+
+impl WindowHandle {
+    pub fn raw(self) -> jlong {
+        self.0
+    }
+
+    /// The handle as an argument of a Java method taking a window pointer
+    pub fn as_jvalue(self) -> JValue<'static, 'static> {
+        JValue::Long(self.0)
+    }
+}
 
 #[derive(Debug)]
 pub struct Window {
     pub jni_ref: GlobalRef,
+    // InputConstants class, resolved once instead of at every key query
+    input_constants: OnceLock<GlobalRef>,
+    // Last handle read, 0 before the first read as GLFW never returns a null window
+    handle: AtomicI64,
 }
 
 impl GameContext for Window {}
@@ -24,20 +47,88 @@ impl Window {
 
         Ok(Window {
             jni_ref: mapping.new_global_ref(window_obj)?,
+            input_constants: OnceLock::new(),
+            handle: AtomicI64::new(0),
         })
     }
 
-    pub fn get_window(&self) -> anyhow::Result<jlong> {
-        let mapping = self.mapping();
+    /// Native handle of the window, read once and cached. Use `refresh_handle` when the
+    /// GLFW window may have been recreated
+    pub fn handle(&self) -> anyhow::Result<WindowHandle> {
+        match self.handle.load(Ordering::SeqCst) {
+            0 => self.refresh_handle(),
+            handle => Ok(WindowHandle(handle)),
+        }
+    }
+
+    /// Compares the current native handle with the cached one and refreshes the cache,
+    /// true if the GLFW window was recreated since the last read
+    pub fn check_handle(&self) -> anyhow::Result<bool> {
+        let previous = self.handle.load(Ordering::SeqCst);
+        let current = self.refresh_handle()?;
+        Ok(previous != 0 && previous != current.raw())
+    }
+
+    /// Reads the native handle from the game again and caches it
+    pub fn refresh_handle(&self) -> anyhow::Result<WindowHandle> {
+        let mapping = self.mapping()?;
 
-        Ok(mapping
+        let handle = mapping
             .call_method(
                 MinecraftClassType::Window,
                 self.jni_ref.as_obj(),
                 MethodName::WindowGetWindow.get_name(mapping.get_version()),
                 &[],
             )?
-            .j()?)
+            .j()?;
+        if handle == 0 {
+            return Err(anyhow::anyhow!("The game has no window"));
+        }
+
+        self.handle.store(handle, Ordering::SeqCst);
+        Ok(WindowHandle(handle))
+    }
+
+    /// Whether key reads pass the native handle, `InputConstants.isKeyDown` takes it
+    /// before 1.21.9 and the `Window` itself since
+    pub fn key_reads_use_handle(&self) -> anyhow::Result<bool> {
+        Ok(self.mapping()?.get_version() < MinecraftVersion::new(1, 21, 9))
+    }
+
+    /// Whether `key` is held down, read like the game does with `InputConstants.isKeyDown`.
+    /// It calls GLFW, which may only be used from the render thread
+    pub fn is_key_down(&self, key: i32) -> anyhow::Result<bool> {
+        let mapping = self.mapping()?;
+        let mut env = DarkClient::instance()?.get_env()?;
+
+        let class = match self.input_constants.get() {
+            Some(class) => class,
+            None => {
+                let class = mapping.find_class(&mut env, MinecraftClassType::InputConstants)?;
+                let class = mapping.new_global_ref(JObject::from(class))?;
+                self.input_constants.get_or_init(|| class)
+            }
+        };
+
+        let window = if self.key_reads_use_handle()? {
+            self.handle()?.as_jvalue()
+        } else {
+            JValue::Object(self.jni_ref.as_obj())
+        };
+        let args = [window, JValue::Int(key)];
+        let method = mapping
+            .get_class(MinecraftClassType::InputConstants.get_name())?
+            .get_method_by_args("isKeyDown", &args)?;
+        let down = env
+            .call_static_method(
+                <&JClass>::from(class.as_obj()),
+                &method.name,
+                &method.signature,
+                &args,
+            )?
+            .z()?;
+
+        Ok(down)
     }
 }
 
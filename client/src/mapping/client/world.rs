@@ -1,5 +1,7 @@
+use crate::client::DarkClient;
+use crate::mapping::entity::Entity;
 use crate::mapping::{FieldType, GameContext, Mapping, MinecraftClassType};
-use jni::objects::GlobalRef;
+use jni::objects::{GlobalRef, JValue};
 use std::ops::Deref;
 
 #[derive(Debug)]
@@ -11,17 +13,103 @@ impl GameContext for World {}
 
 impl World {
     pub fn new(minecraft: &GlobalRef, mapping: &Mapping) -> anyhow::Result<World> {
-        let world_obj = mapping
-            .get_field(
+        let world_ref = mapping
+            .get_field_optional(
                 MinecraftClassType::Minecraft,
                 minecraft.as_obj(),
                 "level",
                 FieldType::Object(MinecraftClassType::Level, mapping),
             )?
-            .l()?;
+            .ok_or_else(|| anyhow::anyhow!("No level, not in a world yet"))?;
 
-        Ok(World {
-            jni_ref: mapping.new_global_ref(world_obj)?,
+        Ok(World { jni_ref: world_ref })
+    }
+
+    /// Whether `to` is in the line of sight of `from`, tracing a ray between their eyes
+    /// that is stopped by block collision shapes like the vanilla check.
+    /// False when not in a world
+    pub fn can_see(&self, from: &Entity, to: &Entity) -> anyhow::Result<bool> {
+        let mapping = self.mapping()?;
+
+        // The level read at startup is replaced on every dimension change, use the current one
+        let level = mapping.get_field_optional(
+            MinecraftClassType::Minecraft,
+            self.minecraft()?.jni_ref.as_obj(),
+            "level",
+            FieldType::Object(MinecraftClassType::Level, mapping),
+        )?;
+        let Some(level) = level else {
+            return Ok(false);
+        };
+
+        let client = DarkClient::instance()?;
+        client.with_local_frame(|| {
+            let mut env = client.get_env()?;
+
+            let from_eyes = mapping
+                .call_method(
+                    MinecraftClassType::Entity,
+                    from.jni_ref.as_obj(),
+                    "getEyePosition",
+                    &[],
+                )?
+                .l()?;
+            let to_eyes = mapping
+                .call_method(
+                    MinecraftClassType::Entity,
+                    to.jni_ref.as_obj(),
+                    "getEyePosition",
+                    &[],
+                )?
+                .l()?;
+
+            let block = mapping
+                .get_static_field(
+                    MinecraftClassType::ClipContextBlock,
+                    "COLLIDER",
+                    FieldType::Object(MinecraftClassType::ClipContextBlock, mapping),
+                )?
+                .l()?;
+            let fluid = mapping
+                .get_static_field(
+                    MinecraftClassType::ClipContextFluid,
+                    "NONE",
+                    FieldType::Object(MinecraftClassType::ClipContextFluid, mapping),
+                )?
+                .l()?;
+            let context = mapping.new_object(
+                &mut env,
+                MinecraftClassType::ClipContext,
+                &[
+                    JValue::Object(&from_eyes),
+                    JValue::Object(&to_eyes),
+                    JValue::Object(&block),
+                    JValue::Object(&fluid),
+                    JValue::Object(from.jni_ref.as_obj()),
+                ],
+            )?;
+
+            let hit = mapping
+                .call_method(
+                    MinecraftClassType::BlockGetter,
+                    level.as_obj(),
+                    "clip",
+                    &[JValue::Object(&context)],
+                )?
+                .l()?;
+            let hit_type = mapping
+                .call_method(MinecraftClassType::HitResult, &hit, "getType", &[])?
+                .l()?;
+            let miss = mapping
+                .get_static_field(
+                    MinecraftClassType::HitResultType,
+                    "MISS",
+                    FieldType::Object(MinecraftClassType::HitResultType, mapping),
+                )?
+                .l()?;
+
+            // Nothing was hit on the way, the ray reached the target
+            Ok(env.is_same_object(&hit_type, &miss)?)
         })
     }
 }
@@ -0,0 +1,80 @@
+use crate::mapping::jvm_access::JvmAccess;
+use crate::mapping::{FieldType, MinecraftClassType};
+use jni::objects::{JObject, JValue};
+
+/// Attributes of a living entity, named after the static fields of `Attributes`
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Attribute {
+    EntityInteractionRange,
+    BlockInteractionRange,
+    StepHeight,
+    MovementSpeed,
+}
+
+impl Attribute {
+    pub fn get_field_name(&self) -> &str {
+        match self {
+            Attribute::EntityInteractionRange => "ENTITY_INTERACTION_RANGE",
+            Attribute::BlockInteractionRange => "BLOCK_INTERACTION_RANGE",
+            Attribute::StepHeight => "STEP_HEIGHT",
+            Attribute::MovementSpeed => "MOVEMENT_SPEED",
+        }
+    }
+}
+
+/// Value of an attribute on a specific entity, accessed through `JvmAccess`
+#[derive(Debug, Copy, Clone)]
+pub struct AttributeInstance;
+
+impl AttributeInstance {
+    /// Looks up the instance of `attribute` on `entity` through `jvm`, null if the entity
+    /// doesn't have it
+    pub fn find<'a>(
+        jvm: &'a dyn JvmAccess,
+        entity: &JObject,
+        attribute: Attribute,
+    ) -> anyhow::Result<JObject<'a>> {
+        let holder = jvm
+            .get_static_field(
+                MinecraftClassType::Attributes,
+                attribute.get_field_name(),
+                FieldType::Object(MinecraftClassType::Holder, jvm.mapping()),
+            )?
+            .l()?;
+
+        Ok(jvm
+            .call_method(
+                MinecraftClassType::LivingEntity,
+                entity,
+                "getAttribute",
+                &[JValue::Object(&holder)],
+            )?
+            .l()?)
+    }
+
+    /// Base value of an attribute instance, before modifiers
+    pub fn get_base_value(jvm: &dyn JvmAccess, instance: &JObject) -> anyhow::Result<f64> {
+        Ok(jvm
+            .call_method(
+                MinecraftClassType::AttributeInstance,
+                instance,
+                "getBaseValue",
+                &[],
+            )?
+            .d()?)
+    }
+
+    pub fn set_base_value(
+        jvm: &dyn JvmAccess,
+        instance: &JObject,
+        value: f64,
+    ) -> anyhow::Result<()> {
+        jvm.call_void_method(
+            MinecraftClassType::AttributeInstance,
+            instance,
+            "setBaseValue",
+            &[JValue::Double(value)],
+        )
+    }
+}
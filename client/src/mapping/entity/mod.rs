@@ -1,7 +1,11 @@
+use crate::mapping::jvm_access::JvmAccess;
 use crate::mapping::{FieldType, GameContext, MinecraftClassType};
-use jni::objects::{GlobalRef, JValue};
+use jni::objects::{GlobalRef, JObject, JValue};
 use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
+pub mod attribute;
 pub mod player;
 
 #[allow(dead_code)]
@@ -10,9 +14,42 @@ pub struct EntityLivingBase {
     pub jni_ref: GlobalRef,
 }
 
+// Incremented at every tick, values cached during an older tick are stale
+static TICK_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Invalidates the values cached by every entity, called at the start of each tick
+pub fn invalidate_cache() {
+    TICK_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// A value read at most once per tick
+#[derive(Debug, Default)]
+struct TickCache<T: Copy> {
+    // Tick generation the value was read in
+    value: Option<(u64, T)>,
+}
+
+impl<T: Copy> TickCache<T> {
+    fn get_or_try_insert<F>(&mut self, generation: u64, read: F) -> anyhow::Result<T>
+    where
+        F: FnOnce() -> anyhow::Result<T>,
+    {
+        match self.value {
+            Some((cached_generation, value)) if cached_generation == generation => Ok(value),
+            _ => {
+                let value = read()?;
+                self.value = Some((generation, value));
+                Ok(value)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Entity {
     pub jni_ref: GlobalRef,
+    // Shared by the clones of the entity, e.g. the player held by every module
+    position_cache: Arc<Mutex<TickCache<(f64, f64, f64)>>>,
 }
 
 impl GameContext for Entity {}
@@ -20,13 +57,26 @@ impl GameContext for Entity {}
 #[allow(dead_code)]
 impl Entity {
     pub fn new(jni_ref: GlobalRef) -> Entity {
-        Entity { jni_ref }
+        Entity {
+            jni_ref,
+            position_cache: Arc::default(),
+        }
     }
 
+    /// Position of the entity, read once per tick and cached until `invalidate_cache`.
+    /// Use `read_position` right after moving the entity
     pub fn get_position(&self) -> anyhow::Result<(f64, f64, f64)> {
-        let mapping = self.mapping();
+        let generation = TICK_GENERATION.load(Ordering::SeqCst);
+        self.position_cache
+            .lock()
+            .unwrap()
+            .get_or_try_insert(generation, || self.read_position())
+    }
 
-        let vec3 = mapping
+    /// Position of the entity read from the game, bypassing the cache
+    pub fn read_position(&self) -> anyhow::Result<(f64, f64, f64)> {
+        let vec3 = self
+            .mapping()?
             .call_method(
                 MinecraftClassType::Entity,
                 self.jni_ref.as_obj(),
@@ -35,36 +85,116 @@ impl Entity {
             )?
             .l()?;
 
+        self.read_vec3(&vec3)
+    }
+
+    /// Velocity in blocks per tick
+    pub fn get_motion(&self) -> anyhow::Result<(f64, f64, f64)> {
+        let vec3 = self
+            .mapping()?
+            .call_method(
+                MinecraftClassType::Entity,
+                self.jni_ref.as_obj(),
+                "getDeltaMovement",
+                &[],
+            )?
+            .l()?;
+
+        self.read_vec3(&vec3)
+    }
+
+    /// Position of the eyes, where attacks and raycasts start. Uses the overload without a
+    /// partial tick, giving the eyes at the last tick position like `read_position`
+    pub fn get_eye_position(&self) -> anyhow::Result<(f64, f64, f64)> {
+        let vec3 = self
+            .mapping()?
+            .call_method(
+                MinecraftClassType::Entity,
+                self.jni_ref.as_obj(),
+                "getEyePosition",
+                &[],
+            )?
+            .l()?;
+
+        self.read_vec3(&vec3)
+    }
+
+    /// Sets the velocity of `entity` in blocks per tick
+    pub fn set_motion(
+        jvm: &dyn JvmAccess,
+        entity: &JObject,
+        motion: (f64, f64, f64),
+    ) -> anyhow::Result<()> {
+        jvm.call_void_method(
+            MinecraftClassType::Entity,
+            entity,
+            "setDeltaMovement",
+            &[
+                JValue::Double(motion.0),
+                JValue::Double(motion.1),
+                JValue::Double(motion.2),
+            ],
+        )
+    }
+
+    /// Horizontal rotation of `entity` in degrees, 0 facing south (+Z) and 90 facing
+    /// west (-X)
+    pub fn get_y_rot(jvm: &dyn JvmAccess, entity: &JObject) -> anyhow::Result<f32> {
+        Ok(jvm
+            .call_method(MinecraftClassType::Entity, entity, "getYRot", &[])?
+            .f()?)
+    }
+
+    fn read_vec3(&self, vec3: &JObject) -> anyhow::Result<(f64, f64, f64)> {
+        let mapping = self.mapping()?;
+
         let x = mapping
-            .get_field(MinecraftClassType::Vec3, &vec3, "x", FieldType::Double)?
+            .get_field(MinecraftClassType::Vec3, vec3, "x", FieldType::Double)?
             .d()?;
 
         let y = mapping
-            .get_field(MinecraftClassType::Vec3, &vec3, "y", FieldType::Double)?
+            .get_field(MinecraftClassType::Vec3, vec3, "y", FieldType::Double)?
             .d()?;
 
         let z = mapping
-            .get_field(MinecraftClassType::Vec3, &vec3, "z", FieldType::Double)?
+            .get_field(MinecraftClassType::Vec3, vec3, "z", FieldType::Double)?
             .d()?;
 
         Ok((x, y, z))
     }
 
+    pub fn is_on_ground(&self) -> anyhow::Result<bool> {
+        self.call_bool_method("onGround")
+    }
+
+    pub fn is_in_water(&self) -> anyhow::Result<bool> {
+        self.call_bool_method("isInWater")
+    }
+
+    pub fn is_sprinting(&self) -> anyhow::Result<bool> {
+        self.call_bool_method("isSprinting")
+    }
+
+    fn call_bool_method(&self, name: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .mapping()?
+            .call_method(MinecraftClassType::Entity, self.jni_ref.as_obj(), name, &[])?
+            .z()?)
+    }
+
     pub fn set_invulnerable(&self, value: bool) -> anyhow::Result<()> {
-        let mapping = self.mapping();
+        let mapping = self.mapping()?;
 
-        mapping.call_method(
+        mapping.call_void_method(
             MinecraftClassType::Entity,
             self.jni_ref.as_obj(),
             "setInvulnerable",
             &[JValue::from(value)],
-        )?;
-
-        Ok(())
+        )
     }
 
     pub fn get_fall_distance(&self) -> anyhow::Result<f64> {
-        let mapping = self.mapping();
+        let mapping = self.mapping()?;
 
         Ok(mapping
             .get_field(
@@ -77,20 +207,18 @@ impl Entity {
     }
 
     pub fn reset_fall_distance(&self) -> anyhow::Result<()> {
-        let mapping = self.mapping();
+        let mapping = self.mapping()?;
 
-        Ok(mapping
-            .call_method(
-                MinecraftClassType::Entity,
-                self.jni_ref.as_obj(),
-                "resetFallDistance",
-                &[],
-            )?
-            .v()?)
+        mapping.call_void_method(
+            MinecraftClassType::Entity,
+            self.jni_ref.as_obj(),
+            "resetFallDistance",
+            &[],
+        )
     }
 
     pub fn get_name(&self) -> anyhow::Result<String> {
-        let mapping = self.mapping();
+        let mapping = self.mapping()?;
 
         mapping.get_string(
             mapping
@@ -105,6 +233,16 @@ impl Entity {
     }
 }
 
+/// Block coordinates containing the given position, flooring like Minecraft's `Mth.floor`
+/// so negative coordinates map to the block below (-0.5 is in block -1, not 0)
+pub fn to_block_pos(position: (f64, f64, f64)) -> (i32, i32, i32) {
+    (
+        position.0.floor() as i32,
+        position.1.floor() as i32,
+        position.2.floor() as i32,
+    )
+}
+
 impl Deref for Entity {
     type Target = GlobalRef;
 
@@ -120,3 +258,47 @@ impl Deref for EntityLivingBase {
         &self.jni_ref
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_pos_positive() {
+        assert_eq!(to_block_pos((0.0, 64.0, 0.0)), (0, 64, 0));
+        assert_eq!(to_block_pos((10.7, 64.999, 3.2)), (10, 64, 3));
+    }
+
+    #[test]
+    fn test_tick_cache_reads_once_per_generation() {
+        let mut cache = TickCache::default();
+        let reads = std::cell::Cell::new(0);
+        let read = |value: i32| {
+            reads.set(reads.get() + 1);
+            Ok(value)
+        };
+
+        assert_eq!(cache.get_or_try_insert(0, || read(1)).unwrap(), 1);
+        assert_eq!(cache.get_or_try_insert(0, || read(2)).unwrap(), 1);
+        assert_eq!(cache.get_or_try_insert(1, || read(3)).unwrap(), 3);
+        assert_eq!(reads.get(), 2);
+    }
+
+    #[test]
+    fn test_tick_cache_does_not_cache_errors() {
+        let mut cache = TickCache::default();
+
+        assert!(cache
+            .get_or_try_insert(0, || Err(anyhow::anyhow!("Failed")))
+            .is_err());
+        assert_eq!(cache.get_or_try_insert(0, || Ok(5)).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_block_pos_negative() {
+        // Truncation would give 0 here, the block is -1
+        assert_eq!(to_block_pos((-0.5, -0.001, -0.999)), (-1, -1, -1));
+        assert_eq!(to_block_pos((-10.3, -64.0, -1.0)), (-11, -64, -1));
+        assert_eq!(to_block_pos((-0.0, 0.0, -2.5)), (0, 0, -3));
+    }
+}
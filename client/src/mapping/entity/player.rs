@@ -1,6 +1,7 @@
-use crate::mapping::entity::Entity;
+use crate::mapping::entity::{to_block_pos, Entity};
+use crate::mapping::jvm_access::JvmAccess;
 use crate::mapping::{FieldType, GameContext, Mapping, MinecraftClassType};
-use jni::objects::{GlobalRef, JValue};
+use jni::objects::{GlobalRef, JObject, JValue};
 use jni::sys::jboolean;
 use std::ops::Deref;
 
@@ -11,6 +12,66 @@ pub struct LocalPlayer {
     pub entity: Entity,
 }
 
+/// Snapshot of the player state taken once per tick, so modules don't each make
+/// the same JNI calls. See `DarkClient::player_state`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PlayerState {
+    pub position: (f64, f64, f64),
+    /// Velocity in blocks per tick
+    pub motion: (f64, f64, f64),
+    pub on_ground: bool,
+    pub in_water: bool,
+    pub sprinting: bool,
+}
+
+/// Movement input of the player, read from the game so it follows the key bindings
+/// instead of polling WASD
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct MoveInput {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub jump: bool,
+    pub shift: bool,
+    pub sprint: bool,
+    /// Forward movement applied by the game, -1 to 1 and slowed down e.g. while sneaking
+    /// or using an item
+    pub forward_impulse: f32,
+    /// Sideways movement applied by the game, positive to the left
+    pub left_impulse: f32,
+}
+
+impl MoveInput {
+    /// 1 going forward, -1 backward, 0 for none or both keys
+    pub fn forward_axis(&self) -> f32 {
+        axis(self.forward, self.backward)
+    }
+
+    /// 1 going left, -1 right, 0 for none or both keys
+    pub fn strafe_axis(&self) -> f32 {
+        axis(self.left, self.right)
+    }
+
+    /// 1 going up with jump, -1 down with shift, 0 for none or both keys
+    pub fn vertical_axis(&self) -> f32 {
+        axis(self.jump, self.shift)
+    }
+
+    /// Whether a horizontal movement key is held
+    pub fn is_moving(&self) -> bool {
+        self.forward_axis() != 0.0 || self.strafe_axis() != 0.0
+    }
+}
+
+fn axis(positive: bool, negative: bool) -> f32 {
+    match (positive, negative) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => 0.0,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Abilities {
     pub jni_ref: GlobalRef,
@@ -21,16 +82,14 @@ impl GameContext for Abilities {}
 
 impl LocalPlayer {
     pub fn new(minecraft: &GlobalRef, mapping: &Mapping) -> anyhow::Result<Self> {
-        let player_obj = mapping
-            .get_field(
+        let player_ref = mapping
+            .get_field_optional(
                 MinecraftClassType::Minecraft,
                 minecraft.as_obj(),
                 "player",
                 FieldType::Object(MinecraftClassType::LocalPlayer, mapping),
             )?
-            .l()?;
-
-        let player_ref = mapping.new_global_ref(player_obj)?;
+            .ok_or_else(|| anyhow::anyhow!("No player, not in a world yet"))?;
         let abilities = Abilities::new(player_ref.clone(), mapping)?;
         let entity = Entity::new(player_ref.clone());
 
@@ -40,6 +99,116 @@ impl LocalPlayer {
             entity,
         })
     }
+
+    /// Reads the current state of the player
+    pub fn get_state(&self) -> anyhow::Result<PlayerState> {
+        Ok(PlayerState {
+            position: self.entity.get_position()?,
+            motion: self.entity.get_motion()?,
+            on_ground: self.entity.is_on_ground()?,
+            in_water: self.entity.is_in_water()?,
+            sprinting: self.entity.is_sprinting()?,
+        })
+    }
+
+    /// Movement keys and impulses of the last tick of `player`
+    pub fn get_input(jvm: &dyn JvmAccess, player: &JObject) -> anyhow::Result<MoveInput> {
+        let mapping = jvm.mapping();
+
+        let input = jvm
+            .get_field(
+                MinecraftClassType::LocalPlayer,
+                player,
+                "input",
+                FieldType::Object(MinecraftClassType::ClientInput, mapping),
+            )?
+            .l()?;
+        let key_presses = jvm
+            .get_field(
+                MinecraftClassType::ClientInput,
+                &input,
+                "keyPresses",
+                FieldType::Object(MinecraftClassType::PlayerInput, mapping),
+            )?
+            .l()?;
+        let key = |name: &str| -> anyhow::Result<bool> {
+            Ok(jvm
+                .get_field(
+                    MinecraftClassType::PlayerInput,
+                    &key_presses,
+                    name,
+                    FieldType::Boolean,
+                )?
+                .z()?)
+        };
+
+        let move_vector = jvm
+            .get_field(
+                MinecraftClassType::ClientInput,
+                &input,
+                "moveVector",
+                FieldType::Object(MinecraftClassType::Vec2, mapping),
+            )?
+            .l()?;
+        let impulse = |name: &str| -> anyhow::Result<f32> {
+            Ok(jvm
+                .get_field(
+                    MinecraftClassType::Vec2,
+                    &move_vector,
+                    name,
+                    FieldType::Float,
+                )?
+                .f()?)
+        };
+
+        Ok(MoveInput {
+            forward: key("forward")?,
+            backward: key("backward")?,
+            left: key("left")?,
+            right: key("right")?,
+            jump: key("jump")?,
+            shift: key("shift")?,
+            sprint: key("sprint")?,
+            // The vector is (left, forward)
+            forward_impulse: impulse("y")?,
+            left_impulse: impulse("x")?,
+        })
+    }
+
+    /// Block coordinates the player is standing in
+    pub fn get_block_pos(&self) -> anyhow::Result<(i32, i32, i32)> {
+        Ok(to_block_pos(self.entity.get_position()?))
+    }
+
+    /// The player's `ClientPacketListener`, used to talk to the server.
+    ///
+    /// On 1.21 it is the public `LocalPlayer.connection` field, older versions without it
+    /// need `Minecraft.getConnection()` instead
+    pub fn get_connection(&self) -> anyhow::Result<GlobalRef> {
+        let mapping = self.mapping()?;
+
+        mapping
+            .get_field_optional(
+                MinecraftClassType::LocalPlayer,
+                self.jni_ref.as_obj(),
+                "connection",
+                FieldType::Object(MinecraftClassType::ClientPacketListener, mapping),
+            )?
+            .ok_or_else(|| anyhow::anyhow!("Player has no connection"))
+    }
+
+    /// Sends a packet to the server through the player's connection
+    pub fn send_packet(&self, packet: &JObject) -> anyhow::Result<()> {
+        let connection = self.get_connection()?;
+
+        // send is declared on the common listener shared with the configuration phase
+        self.mapping()?.call_void_method(
+            MinecraftClassType::ClientCommonPacketListenerImpl,
+            connection.as_obj(),
+            "send",
+            &[JValue::Object(packet)],
+        )
+    }
 }
 
 impl Abilities {
@@ -52,22 +221,21 @@ impl Abilities {
         })
     }
 
-    pub fn fly(&self, value: bool) -> anyhow::Result<()> {
-        let mapping = self.mapping();
-
+    /// Sets both whether the player of `abilities` may fly and whether it's flying
+    pub fn fly(jvm: &dyn JvmAccess, abilities: &JObject, value: bool) -> anyhow::Result<()> {
         let value: jboolean = if value { 1 } else { 0 };
 
-        mapping.set_field(
+        jvm.set_field_if_changed(
             MinecraftClassType::Abilities,
-            self.jni_ref.as_obj(),
+            abilities,
             "flying",
             FieldType::Boolean,
             JValue::Bool(value),
         )?;
 
-        mapping.set_field(
+        jvm.set_field_if_changed(
             MinecraftClassType::Abilities,
-            self.jni_ref.as_obj(),
+            abilities,
             "mayfly",
             FieldType::Boolean,
             JValue::Bool(value),
@@ -76,9 +244,36 @@ impl Abilities {
         Ok(())
     }
 
+    #[allow(dead_code)]
+    pub fn get_flying_speed(&self) -> anyhow::Result<f32> {
+        let mapping = self.mapping()?;
+
+        Ok(mapping
+            .call_method(
+                MinecraftClassType::Abilities,
+                self.jni_ref.as_obj(),
+                "getFlyingSpeed",
+                &[],
+            )?
+            .f()?)
+    }
+
+    pub fn set_flying_speed(
+        jvm: &dyn JvmAccess,
+        abilities: &JObject,
+        value: f32,
+    ) -> anyhow::Result<()> {
+        jvm.call_void_method(
+            MinecraftClassType::Abilities,
+            abilities,
+            "setFlyingSpeed",
+            &[JValue::Float(value)],
+        )
+    }
+
     #[allow(dead_code)]
     pub fn get_may_fly(&self) -> anyhow::Result<bool> {
-        let mapping = self.mapping();
+        let mapping = self.mapping()?;
 
         Ok(mapping
             .get_field(
@@ -106,3 +301,34 @@ impl Deref for Abilities {
         &self.jni_ref
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_input_axes() {
+        let input = MoveInput {
+            forward: true,
+            right: true,
+            jump: true,
+            shift: true,
+            ..MoveInput::default()
+        };
+
+        assert_eq!(input.forward_axis(), 1.0);
+        assert_eq!(input.strafe_axis(), -1.0);
+        // Both vertical keys cancel out
+        assert_eq!(input.vertical_axis(), 0.0);
+        assert!(input.is_moving());
+
+        let input = MoveInput {
+            left: true,
+            right: true,
+            jump: true,
+            ..MoveInput::default()
+        };
+        assert!(!input.is_moving());
+        assert_eq!(input.vertical_axis(), 1.0);
+    }
+}
@@ -1,6 +1,8 @@
-use jni::objects::GlobalRef;
+use jni::objects::{GlobalRef, JValue};
 use std::ops::Deref;
 
+pub mod native_class;
+
 #[allow(dead_code)]
 pub struct JavaList {
     pub jni_ref: GlobalRef,
@@ -11,6 +13,21 @@ pub struct JavaSet {
     pub jni_ref: GlobalRef,
 }
 
+/// A Java array created from Rust, usable as a method argument
+#[allow(dead_code)]
+pub struct JavaArray {
+    pub jni_ref: GlobalRef,
+    pub length: usize,
+}
+
+#[allow(dead_code)]
+impl JavaArray {
+    /// Returns the array as a `JValue` to be passed in a method call arguments slice
+    pub fn as_arg(&self) -> JValue<'_, '_> {
+        JValue::Object(self.jni_ref.as_obj())
+    }
+}
+
 impl Deref for JavaList {
     type Target = GlobalRef;
 
@@ -26,3 +43,11 @@ impl Deref for JavaSet {
         &self.jni_ref
     }
 }
+
+impl Deref for JavaArray {
+    type Target = GlobalRef;
+
+    fn deref(&self) -> &Self::Target {
+        &self.jni_ref
+    }
+}
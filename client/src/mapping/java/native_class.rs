@@ -0,0 +1,251 @@
+use jni::objects::{GlobalRef, JObject};
+use jni::{JNIEnv, NativeMethod};
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+// Class file constants
+const CLASS_FILE_MAGIC: u32 = 0xCAFEBABE;
+// Java 8, so no stack map frames are required
+const CLASS_FILE_MAJOR_VERSION: u16 = 52;
+
+const CONSTANT_UTF8: u8 = 1;
+const CONSTANT_CLASS: u8 = 7;
+const CONSTANT_METHOD_REF: u8 = 10;
+const CONSTANT_NAME_AND_TYPE: u8 = 12;
+
+const ACC_PUBLIC: u16 = 0x0001;
+const ACC_FINAL: u16 = 0x0010;
+const ACC_SUPER: u16 = 0x0020;
+const ACC_NATIVE: u16 = 0x0100;
+
+const OPCODE_ALOAD_0: u8 = 0x2a;
+const OPCODE_INVOKESPECIAL: u8 = 0xb7;
+const OPCODE_RETURN: u8 = 0xb1;
+
+/// A native method of a `NativeClass`, bound to a Rust function
+struct NativeClassMethod {
+    name: String,
+    signature: String,
+    fn_ptr: *mut c_void,
+}
+
+/// Builder for a Java class whose methods are all implemented in Rust.
+///
+/// The generated class has a public no-args constructor and one `native` method
+/// for each registered method, so it can implement game interfaces (e.g. a
+/// callback invoked by Minecraft) without shipping any Java code.
+pub struct NativeClass {
+    name: String,
+    super_class: String,
+    interfaces: Vec<String>,
+    methods: Vec<NativeClassMethod>,
+}
+
+#[allow(dead_code)]
+impl NativeClass {
+    /// Creates a new class with the given internal name (e.g. `darkclient/Hook`)
+    pub fn new(name: &str) -> NativeClass {
+        NativeClass {
+            name: name.to_string(),
+            super_class: "java/lang/Object".to_string(),
+            interfaces: Vec::new(),
+            methods: Vec::new(),
+        }
+    }
+
+    /// Sets the super class, it must have an accessible no-args constructor
+    pub fn super_class(mut self, name: &str) -> NativeClass {
+        self.super_class = name.to_string();
+        self
+    }
+
+    /// Adds an interface implemented by the class
+    pub fn interface(mut self, name: &str) -> NativeClass {
+        self.interfaces.push(name.to_string());
+        self
+    }
+
+    /// Adds a native method, `fn_ptr` must be an `extern "system"` function
+    /// taking the `JNIEnv` and the instance followed by the method arguments
+    pub fn method(mut self, name: &str, signature: &str, fn_ptr: *mut c_void) -> NativeClass {
+        self.methods.push(NativeClassMethod {
+            name: name.to_string(),
+            signature: signature.to_string(),
+            fn_ptr,
+        });
+        self
+    }
+
+    /// Defines the class in the given class loader and registers its native methods
+    pub fn define(&self, env: &mut JNIEnv, loader: &JObject) -> anyhow::Result<GlobalRef> {
+        let class = env
+            .define_class(&self.name, loader, &self.to_bytes())
+            .map_err(|e| anyhow::anyhow!("Failed to define class {}: {}", self.name, e))?;
+
+        let native_methods: Vec<NativeMethod> = self
+            .methods
+            .iter()
+            .map(|method| NativeMethod {
+                name: method.name.as_str().into(),
+                sig: method.signature.as_str().into(),
+                fn_ptr: method.fn_ptr,
+            })
+            .collect();
+        env.register_native_methods(&class, &native_methods)
+            .map_err(|e| anyhow::anyhow!("Failed to register natives of {}: {}", self.name, e))?;
+
+        Ok(env.new_global_ref(class)?)
+    }
+
+    /// Generates the class file bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut pool = ConstantPool::default();
+
+        let this_class = pool.class(&self.name);
+        let super_class = pool.class(&self.super_class);
+        let interfaces: Vec<u16> = self.interfaces.iter().map(|i| pool.class(i)).collect();
+
+        let code_name = pool.utf8("Code");
+        let init_name = pool.utf8("<init>");
+        let init_signature = pool.utf8("()V");
+        let super_init = pool.method_ref(&self.super_class, "<init>", "()V");
+
+        let methods: Vec<(u16, u16)> = self
+            .methods
+            .iter()
+            .map(|m| (pool.utf8(&m.name), pool.utf8(&m.signature)))
+            .collect();
+
+        let mut bytes = Vec::new();
+        write_u32(&mut bytes, CLASS_FILE_MAGIC);
+        write_u16(&mut bytes, 0);
+        write_u16(&mut bytes, CLASS_FILE_MAJOR_VERSION);
+
+        write_u16(&mut bytes, pool.count);
+        bytes.extend_from_slice(&pool.bytes);
+
+        write_u16(&mut bytes, ACC_PUBLIC | ACC_FINAL | ACC_SUPER);
+        write_u16(&mut bytes, this_class);
+        write_u16(&mut bytes, super_class);
+        write_u16(&mut bytes, interfaces.len() as u16);
+        for interface in interfaces {
+            write_u16(&mut bytes, interface);
+        }
+
+        // No fields
+        write_u16(&mut bytes, 0);
+
+        write_u16(&mut bytes, methods.len() as u16 + 1);
+
+        // Constructor calling the super constructor
+        let code = [
+            OPCODE_ALOAD_0,
+            OPCODE_INVOKESPECIAL,
+            (super_init >> 8) as u8,
+            super_init as u8,
+            OPCODE_RETURN,
+        ];
+        write_u16(&mut bytes, ACC_PUBLIC);
+        write_u16(&mut bytes, init_name);
+        write_u16(&mut bytes, init_signature);
+        write_u16(&mut bytes, 1);
+        write_u16(&mut bytes, code_name);
+        write_u32(&mut bytes, 12 + code.len() as u32);
+        write_u16(&mut bytes, 1); // max stack
+        write_u16(&mut bytes, 1); // max locals
+        write_u32(&mut bytes, code.len() as u32);
+        bytes.extend_from_slice(&code);
+        write_u16(&mut bytes, 0); // exception table
+        write_u16(&mut bytes, 0); // attributes
+
+        // Native methods, they have no code attribute
+        for (name, signature) in methods {
+            write_u16(&mut bytes, ACC_PUBLIC | ACC_NATIVE);
+            write_u16(&mut bytes, name);
+            write_u16(&mut bytes, signature);
+            write_u16(&mut bytes, 0);
+        }
+
+        // No class attributes
+        write_u16(&mut bytes, 0);
+
+        bytes
+    }
+}
+
+/// Constant pool of the class file being generated, the index 0 is reserved
+struct ConstantPool {
+    bytes: Vec<u8>,
+    count: u16,
+    utf8_entries: HashMap<String, u16>,
+    class_entries: HashMap<String, u16>,
+}
+
+impl Default for ConstantPool {
+    fn default() -> Self {
+        Self {
+            bytes: Vec::new(),
+            count: 1,
+            utf8_entries: HashMap::new(),
+            class_entries: HashMap::new(),
+        }
+    }
+}
+
+impl ConstantPool {
+    fn push(&mut self, entry: &[u8]) -> u16 {
+        self.bytes.extend_from_slice(entry);
+        self.count += 1;
+        self.count - 1
+    }
+
+    fn utf8(&mut self, value: &str) -> u16 {
+        if let Some(index) = self.utf8_entries.get(value) {
+            return *index;
+        }
+
+        let mut entry = vec![CONSTANT_UTF8];
+        write_u16(&mut entry, value.len() as u16);
+        entry.extend_from_slice(value.as_bytes());
+        let index = self.push(&entry);
+        self.utf8_entries.insert(value.to_string(), index);
+        index
+    }
+
+    fn class(&mut self, name: &str) -> u16 {
+        if let Some(index) = self.class_entries.get(name) {
+            return *index;
+        }
+
+        let name_index = self.utf8(name);
+        let mut entry = vec![CONSTANT_CLASS];
+        write_u16(&mut entry, name_index);
+        let index = self.push(&entry);
+        self.class_entries.insert(name.to_string(), index);
+        index
+    }
+
+    fn method_ref(&mut self, class: &str, name: &str, signature: &str) -> u16 {
+        let class = self.class(class);
+        let name = self.utf8(name);
+        let signature = self.utf8(signature);
+
+        let mut name_and_type = vec![CONSTANT_NAME_AND_TYPE];
+        write_u16(&mut name_and_type, name);
+        write_u16(&mut name_and_type, signature);
+        let name_and_type = self.push(&name_and_type);
+
+        let mut entry = vec![CONSTANT_METHOD_REF];
+        write_u16(&mut entry, class);
+        write_u16(&mut entry, name_and_type);
+        self.push(&entry)
+    }
+}
+
+fn write_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
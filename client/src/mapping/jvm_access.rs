@@ -0,0 +1,556 @@
+use crate::mapping::client::hit_result::HitResult;
+use crate::mapping::{FieldType, Mapping, MinecraftClassType};
+use crate::packet;
+use jni::objects::{GlobalRef, JObject, JValue, JValueOwned};
+use std::fmt::Debug;
+
+/// JNI operations on mapped classes, implemented by `Mapping` for the game and by
+/// `fake::FakeJvm` in tests, so code written against it runs without a JVM
+pub trait JvmAccess: Debug + Send + Sync {
+    /// The mapping names are resolved in, e.g. for `FieldType::Object`
+    fn mapping(&self) -> &Mapping;
+
+    /// The local player, fails without a world, e.g. at the title screen
+    fn player(&self) -> anyhow::Result<JObject<'_>>;
+
+    fn new_global_ref(&self, object: &JObject) -> anyhow::Result<GlobalRef>;
+
+    fn is_same_object(&self, a: &JObject, b: &JObject) -> anyhow::Result<bool>;
+
+    /// Installs the packet hook on `connection` unless it's already there, see
+    /// `packet::ensure_connection_hook`
+    fn ensure_packet_hook(&self, connection: &JObject) -> anyhow::Result<()>;
+
+    fn call_static_method(
+        &self,
+        class_type: MinecraftClassType,
+        method_name: &str,
+        args: &[JValue],
+    ) -> anyhow::Result<JValueOwned<'_>>;
+
+    fn call_method(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        method_name: &str,
+        args: &[JValue],
+    ) -> anyhow::Result<JValueOwned<'_>>;
+
+    fn call_void_method(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        method_name: &str,
+        args: &[JValue],
+    ) -> anyhow::Result<()>;
+
+    fn get_static_field(
+        &self,
+        class_type: MinecraftClassType,
+        field_name: &str,
+        field_type: FieldType,
+    ) -> anyhow::Result<JValueOwned<'_>>;
+
+    fn get_field(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        field_name: &str,
+        field_type: FieldType,
+    ) -> anyhow::Result<JValueOwned<'_>>;
+
+    fn set_field(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        field_name: &str,
+        field_type: FieldType,
+        value: JValue,
+    ) -> anyhow::Result<()>;
+
+    /// Writes a primitive field only if it holds another value, true if it was written.
+    /// For states set every tick this saves the JNI write and, for fields the game
+    /// syncs, the packet and anticheat flag a write can cause. Objects are always written
+    fn set_field_if_changed(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        field_name: &str,
+        field_type: FieldType,
+        value: JValue,
+    ) -> anyhow::Result<bool> {
+        if !matches!(value, JValue::Object(_)) {
+            let current = self.get_field(class_type, instance, field_name, field_type)?;
+            if same_primitive(&current.borrow(), &value) {
+                return Ok(false);
+            }
+        }
+
+        self.set_field(class_type, instance, field_name, field_type, value)?;
+        Ok(true)
+    }
+
+    /// The game's `Minecraft` instance
+    fn minecraft(&self) -> anyhow::Result<JObject<'_>> {
+        Ok(self
+            .call_static_method(MinecraftClassType::Minecraft, "getInstance", &[])?
+            .l()?)
+    }
+
+    /// Reads an object field, None if it's null
+    fn get_field_optional(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        field_name: &str,
+        field_type: FieldType,
+    ) -> anyhow::Result<Option<GlobalRef>> {
+        let value = self
+            .get_field(class_type, instance, field_name, field_type)?
+            .l()?;
+        if value.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(self.new_global_ref(&value)?))
+    }
+
+    /// Screen open in `minecraft`, None while playing without any menu
+    fn screen(&self, minecraft: &JObject) -> anyhow::Result<Option<GlobalRef>> {
+        self.get_field_optional(
+            MinecraftClassType::Minecraft,
+            minecraft,
+            "screen",
+            FieldType::Object(MinecraftClassType::Screen, self.mapping()),
+        )
+    }
+
+    /// The `MultiPlayerGameMode` of `minecraft` handling block breaking and item use,
+    /// also in singleplayer. None without a world
+    fn game_mode(&self, minecraft: &JObject) -> anyhow::Result<Option<GlobalRef>> {
+        self.get_field_optional(
+            MinecraftClassType::Minecraft,
+            minecraft,
+            "gameMode",
+            FieldType::Object(MinecraftClassType::MultiPlayerGameMode, self.mapping()),
+        )
+    }
+
+    /// Connection of `minecraft` still logging in to a server, None once joined or when
+    /// not connecting
+    fn pending_connection(&self, minecraft: &JObject) -> anyhow::Result<Option<GlobalRef>> {
+        self.get_field_optional(
+            MinecraftClassType::Minecraft,
+            minecraft,
+            "pendingConnection",
+            FieldType::Object(MinecraftClassType::Connection, self.mapping()),
+        )
+    }
+
+    /// Data of the multiplayer server `minecraft` is connected to, None in singleplayer
+    /// and menus
+    fn current_server(&self, minecraft: &JObject) -> anyhow::Result<Option<GlobalRef>> {
+        let server = self
+            .call_method(
+                MinecraftClassType::Minecraft,
+                minecraft,
+                "getCurrentServer",
+                &[],
+            )?
+            .l()?;
+        if server.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(self.new_global_ref(&server)?))
+    }
+
+    /// What the crosshair of `minecraft` points at, see `HitResult::read`
+    fn hit_result(&self, minecraft: &JObject) -> anyhow::Result<Option<HitResult>> {
+        HitResult::read(self, minecraft)
+    }
+
+    /// Whether the key or mouse button bound to `key_mapping`, a `KeyMapping` field of
+    /// `Options` such as "keyAttack", is held in `minecraft`. Follows the bindings of the
+    /// player
+    fn is_key_mapping_down(&self, minecraft: &JObject, key_mapping: &str) -> anyhow::Result<bool> {
+        let options = self
+            .get_field(
+                MinecraftClassType::Minecraft,
+                minecraft,
+                "options",
+                FieldType::Object(MinecraftClassType::Options, self.mapping()),
+            )?
+            .l()?;
+        let key_mapping = self
+            .get_field(
+                MinecraftClassType::Options,
+                &options,
+                key_mapping,
+                FieldType::Object(MinecraftClassType::KeyMapping, self.mapping()),
+            )?
+            .l()?;
+        Ok(self
+            .call_method(MinecraftClassType::KeyMapping, &key_mapping, "isDown", &[])?
+            .z()?)
+    }
+}
+
+// Whether two primitive values are equal, NaN never is
+fn same_primitive(a: &JValue, b: &JValue) -> bool {
+    match (a, b) {
+        (JValue::Bool(a), JValue::Bool(b)) => a == b,
+        (JValue::Byte(a), JValue::Byte(b)) => a == b,
+        (JValue::Char(a), JValue::Char(b)) => a == b,
+        (JValue::Short(a), JValue::Short(b)) => a == b,
+        (JValue::Int(a), JValue::Int(b)) => a == b,
+        (JValue::Long(a), JValue::Long(b)) => a == b,
+        (JValue::Float(a), JValue::Float(b)) => a == b,
+        (JValue::Double(a), JValue::Double(b)) => a == b,
+        _ => false,
+    }
+}
+
+impl JvmAccess for Mapping {
+    fn mapping(&self) -> &Mapping {
+        self
+    }
+
+    fn player(&self) -> anyhow::Result<JObject<'_>> {
+        let minecraft = self.minecraft()?;
+        let player = Mapping::get_field(
+            self,
+            MinecraftClassType::Minecraft,
+            &minecraft,
+            "player",
+            FieldType::Object(MinecraftClassType::LocalPlayer, self),
+        )?
+        .l()?;
+        if player.is_null() {
+            return Err(anyhow::anyhow!("No player, not in a world yet"));
+        }
+        Ok(player)
+    }
+
+    fn new_global_ref(&self, object: &JObject) -> anyhow::Result<GlobalRef> {
+        Ok(self.get_env()?.new_global_ref(object)?)
+    }
+
+    fn is_same_object(&self, a: &JObject, b: &JObject) -> anyhow::Result<bool> {
+        Ok(self.get_env()?.is_same_object(a, b)?)
+    }
+
+    fn ensure_packet_hook(&self, connection: &JObject) -> anyhow::Result<()> {
+        packet::ensure_connection_hook(connection)
+    }
+
+    fn call_static_method(
+        &self,
+        class_type: MinecraftClassType,
+        method_name: &str,
+        args: &[JValue],
+    ) -> anyhow::Result<JValueOwned<'_>> {
+        Mapping::call_static_method(self, class_type, method_name, args)
+    }
+
+    fn call_method(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        method_name: &str,
+        args: &[JValue],
+    ) -> anyhow::Result<JValueOwned<'_>> {
+        Mapping::call_method(self, class_type, instance, method_name, args)
+    }
+
+    fn call_void_method(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        method_name: &str,
+        args: &[JValue],
+    ) -> anyhow::Result<()> {
+        Mapping::call_void_method(self, class_type, instance, method_name, args)
+    }
+
+    fn get_static_field(
+        &self,
+        class_type: MinecraftClassType,
+        field_name: &str,
+        field_type: FieldType,
+    ) -> anyhow::Result<JValueOwned<'_>> {
+        Mapping::get_static_field(self, class_type, field_name, field_type)
+    }
+
+    fn get_field(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        field_name: &str,
+        field_type: FieldType,
+    ) -> anyhow::Result<JValueOwned<'_>> {
+        Mapping::get_field(self, class_type, instance, field_name, field_type)
+    }
+
+    fn set_field(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        field_name: &str,
+        field_type: FieldType,
+        value: JValue,
+    ) -> anyhow::Result<()> {
+        Mapping::set_field(self, class_type, instance, field_name, field_type, value)
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// `JvmAccess` without a JVM. Every method and field is resolved in the real mapping,
+    /// so a name missing from mappings.json fails like in game, then the access is
+    /// recorded and a zero value of the mapped return type is returned. Fields read the
+    /// last primitive value set, whatever the instance.
+    ///
+    /// Objects are null: optional ones read as absent, while the player is always there
+    #[derive(Debug)]
+    pub struct FakeJvm {
+        mapping: Mapping,
+        calls: Mutex<Vec<String>>,
+        fields: Mutex<HashMap<String, JValueOwned<'static>>>,
+    }
+
+    impl FakeJvm {
+        pub fn new() -> Self {
+            Self {
+                mapping: Mapping::new().expect("mappings.json should parse"),
+                calls: Mutex::new(Vec::new()),
+                fields: Mutex::new(HashMap::new()),
+            }
+        }
+
+        /// Accesses made so far, e.g. `Abilities.setFlyingSpeed([Float(0.05)])`
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn resolve_method(
+            &self,
+            class_type: MinecraftClassType,
+            method_name: &str,
+            args: &[JValue],
+        ) -> anyhow::Result<JValueOwned<'static>> {
+            let class = self.mapping.get_class(class_type.get_name())?;
+            let method = class.get_method_by_args(method_name, args)?;
+            self.record(format!("{:?}.{}({:?})", class_type, method_name, args));
+
+            let return_type = method
+                .signature
+                .split(')')
+                .nth(1)
+                .ok_or_else(|| anyhow::anyhow!("Invalid signature {}", method.signature))?;
+            Ok(zero_value(return_type))
+        }
+
+        fn resolve_field(
+            &self,
+            class_type: MinecraftClassType,
+            field_name: &str,
+            field_type: FieldType,
+        ) -> anyhow::Result<JValueOwned<'static>> {
+            let class = self.mapping.get_class(class_type.get_name())?;
+            class.get_field(field_name)?;
+            Ok(zero_value(&field_type.get_signature()?))
+        }
+    }
+
+    fn copy_primitive(value: &JValue) -> Option<JValueOwned<'static>> {
+        match *value {
+            JValue::Bool(v) => Some(JValueOwned::Bool(v)),
+            JValue::Byte(v) => Some(JValueOwned::Byte(v)),
+            JValue::Char(v) => Some(JValueOwned::Char(v)),
+            JValue::Short(v) => Some(JValueOwned::Short(v)),
+            JValue::Int(v) => Some(JValueOwned::Int(v)),
+            JValue::Long(v) => Some(JValueOwned::Long(v)),
+            JValue::Float(v) => Some(JValueOwned::Float(v)),
+            JValue::Double(v) => Some(JValueOwned::Double(v)),
+            _ => None,
+        }
+    }
+
+    fn zero_value(descriptor: &str) -> JValueOwned<'static> {
+        match descriptor.chars().next() {
+            Some('Z') => JValueOwned::Bool(0),
+            Some('B') => JValueOwned::Byte(0),
+            Some('C') => JValueOwned::Char(0),
+            Some('S') => JValueOwned::Short(0),
+            Some('I') => JValueOwned::Int(0),
+            Some('J') => JValueOwned::Long(0),
+            Some('F') => JValueOwned::Float(0.0),
+            Some('D') => JValueOwned::Double(0.0),
+            Some('V') => JValueOwned::Void,
+            _ => JValueOwned::Object(JObject::null()),
+        }
+    }
+
+    impl JvmAccess for FakeJvm {
+        fn mapping(&self) -> &Mapping {
+            &self.mapping
+        }
+
+        fn player(&self) -> anyhow::Result<JObject<'_>> {
+            let minecraft = self.minecraft()?;
+            let field_type = FieldType::Object(MinecraftClassType::LocalPlayer, &self.mapping);
+            Ok(self
+                .get_field(
+                    MinecraftClassType::Minecraft,
+                    &minecraft,
+                    "player",
+                    field_type,
+                )?
+                .l()?)
+        }
+
+        fn new_global_ref(&self, _object: &JObject) -> anyhow::Result<GlobalRef> {
+            Err(anyhow::anyhow!("Global references need a JVM"))
+        }
+
+        fn is_same_object(&self, a: &JObject, b: &JObject) -> anyhow::Result<bool> {
+            Ok(a.as_raw() == b.as_raw())
+        }
+
+        fn ensure_packet_hook(&self, _connection: &JObject) -> anyhow::Result<()> {
+            self.record("ensure_packet_hook".to_string());
+            Ok(())
+        }
+
+        fn call_static_method(
+            &self,
+            class_type: MinecraftClassType,
+            method_name: &str,
+            args: &[JValue],
+        ) -> anyhow::Result<JValueOwned<'_>> {
+            self.resolve_method(class_type, method_name, args)
+        }
+
+        fn call_method(
+            &self,
+            class_type: MinecraftClassType,
+            _instance: &JObject,
+            method_name: &str,
+            args: &[JValue],
+        ) -> anyhow::Result<JValueOwned<'_>> {
+            self.resolve_method(class_type, method_name, args)
+        }
+
+        fn call_void_method(
+            &self,
+            class_type: MinecraftClassType,
+            _instance: &JObject,
+            method_name: &str,
+            args: &[JValue],
+        ) -> anyhow::Result<()> {
+            self.resolve_method(class_type, method_name, args)?;
+            Ok(())
+        }
+
+        fn get_static_field(
+            &self,
+            class_type: MinecraftClassType,
+            field_name: &str,
+            field_type: FieldType,
+        ) -> anyhow::Result<JValueOwned<'_>> {
+            let value = self.resolve_field(class_type, field_name, field_type)?;
+            self.record(format!("{:?}.{}", class_type, field_name));
+            Ok(value)
+        }
+
+        fn get_field(
+            &self,
+            class_type: MinecraftClassType,
+            _instance: &JObject,
+            field_name: &str,
+            field_type: FieldType,
+        ) -> anyhow::Result<JValueOwned<'_>> {
+            let value = self.resolve_field(class_type, field_name, field_type)?;
+            let name = format!("{:?}.{}", class_type, field_name);
+            let set = self
+                .fields
+                .lock()
+                .unwrap()
+                .get(&name)
+                .and_then(|set| copy_primitive(&set.borrow()));
+            self.record(name);
+            Ok(set.unwrap_or(value))
+        }
+
+        fn set_field(
+            &self,
+            class_type: MinecraftClassType,
+            _instance: &JObject,
+            field_name: &str,
+            field_type: FieldType,
+            value: JValue,
+        ) -> anyhow::Result<()> {
+            self.resolve_field(class_type, field_name, field_type)?;
+            self.record(format!("{:?}.{} = {:?}", class_type, field_name, value));
+            if let Some(value) = copy_primitive(&value) {
+                self.fields
+                    .lock()
+                    .unwrap()
+                    .insert(format!("{:?}.{}", class_type, field_name), value);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::FakeJvm;
+    use super::*;
+
+    #[test]
+    fn test_fake_jvm_returns_zero_of_mapped_type() {
+        let jvm = FakeJvm::new();
+
+        let speed = jvm
+            .call_method(
+                MinecraftClassType::Abilities,
+                &JObject::null(),
+                "getFlyingSpeed",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(speed.f().unwrap(), 0.0);
+        assert_eq!(jvm.calls(), vec!["Abilities.getFlyingSpeed([])"]);
+    }
+
+    #[test]
+    fn test_fake_jvm_rejects_unmapped_names() {
+        let jvm = FakeJvm::new();
+
+        assert!(jvm
+            .call_method(
+                MinecraftClassType::Abilities,
+                &JObject::null(),
+                "notAMethod",
+                &[]
+            )
+            .is_err());
+        assert!(jvm
+            .get_field(
+                MinecraftClassType::Abilities,
+                &JObject::null(),
+                "notAField",
+                FieldType::Boolean
+            )
+            .is_err());
+        assert!(jvm.calls().is_empty());
+    }
+}
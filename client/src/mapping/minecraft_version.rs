@@ -1,6 +1,10 @@
-use serde::{Deserialize, Deserializer};
+use crate::client::DarkClient;
+use jni::objects::{JByteArray, JValue};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct MinecraftVersion {
     pub major: u32,
     pub minor: u32,
@@ -15,36 +19,50 @@ impl MinecraftVersion {
             patch,
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("{}.{}.{}", self.major, self.minor, self.patch)
+impl fmt::Display for MinecraftVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
     }
 }
 
-impl<'de> Deserialize<'de> for MinecraftVersion {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+impl Serialize for MinecraftVersion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
-        D: Deserializer<'de>,
+        S: Serializer,
     {
-        let s = String::deserialize(deserializer)?;
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for MinecraftVersion {
+    type Err = anyhow::Error;
+
+    /// Parses `major.minor.patch`, or `major.minor` for the first release of a minor
+    /// version (e.g. `1.21` is `1.21.0`)
+    fn from_str(s: &str) -> anyhow::Result<MinecraftVersion> {
         let parts: Vec<&str> = s.split('.').collect();
 
-        if parts.len() != 3 {
-            return Err(serde::de::Error::custom(format!(
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(anyhow::anyhow!(
                 "Invalid version format: {} (expected major.minor.patch)",
                 s
-            )));
+            ));
         }
 
         let major = parts[0]
             .parse::<u32>()
-            .map_err(|_| serde::de::Error::custom("Invalid major version"))?;
+            .map_err(|_| anyhow::anyhow!("Invalid major version"))?;
         let minor = parts[1]
             .parse::<u32>()
-            .map_err(|_| serde::de::Error::custom("Invalid minor version"))?;
-        let patch = parts[2]
-            .parse::<u32>()
-            .map_err(|_| serde::de::Error::custom("Invalid patch version"))?;
+            .map_err(|_| anyhow::anyhow!("Invalid minor version"))?;
+        let patch = match parts.get(2) {
+            Some(patch) => patch
+                .parse::<u32>()
+                .map_err(|_| anyhow::anyhow!("Invalid patch version"))?,
+            None => 0,
+        };
 
         Ok(MinecraftVersion {
             major,
@@ -53,3 +71,86 @@ impl<'de> Deserialize<'de> for MinecraftVersion {
         })
     }
 }
+
+impl<'de> Deserialize<'de> for MinecraftVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Version of the running game, read from the `version.json` bundled in the client jar
+pub fn detect_game_version() -> anyhow::Result<MinecraftVersion> {
+    let client = DarkClient::instance()?;
+    let contents = client.with_local_frame(|| {
+        let mut env = client.get_env()?;
+        let name = env.new_string("version.json")?;
+        let stream = env
+            .call_static_method(
+                "java/lang/ClassLoader",
+                "getSystemResourceAsStream",
+                "(Ljava/lang/String;)Ljava/io/InputStream;",
+                &[JValue::Object(&name)],
+            )?
+            .l()?;
+        if stream.is_null() {
+            return Err(anyhow::anyhow!("version.json not found in the game"));
+        }
+
+        let bytes = env.call_method(&stream, "readAllBytes", "()[B", &[])?.l()?;
+        env.call_method(&stream, "close", "()V", &[])?;
+        Ok(env.convert_byte_array(JByteArray::from(bytes))?)
+    });
+
+    match contents {
+        Ok(contents) => parse_version_json(&contents),
+        Err(e) => {
+            // Clear the IOException so the JVM isn't left with a pending exception
+            let _ = client.get_env()?.exception_clear();
+            Err(e)
+        }
+    }
+}
+
+// Reads the release from the `id` of version.json, e.g. `{"id": "1.21.10", ...}`
+fn parse_version_json(contents: &[u8]) -> anyhow::Result<MinecraftVersion> {
+    let json: serde_json::Value = serde_json::from_slice(contents)?;
+    let id = json["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("version.json has no id"))?;
+
+    id.parse()
+        .map_err(|e| anyhow::anyhow!("Unsupported game version {}: {}", id, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            "1.21.10".parse::<MinecraftVersion>().unwrap(),
+            MinecraftVersion::new(1, 21, 10)
+        );
+        assert_eq!(
+            "1.21".parse::<MinecraftVersion>().unwrap(),
+            MinecraftVersion::new(1, 21, 0)
+        );
+        assert!("25w14a".parse::<MinecraftVersion>().is_err());
+    }
+
+    #[test]
+    fn test_parse_version_json() {
+        let contents = br#"{"id": "1.21.10", "name": "1.21.10", "world_version": 4556}"#;
+        assert_eq!(
+            parse_version_json(contents).unwrap(),
+            MinecraftVersion::new(1, 21, 10)
+        );
+        assert!(parse_version_json(br#"{"id": "25w14a"}"#).is_err());
+        assert!(parse_version_json(b"{}").is_err());
+    }
+}
@@ -1,40 +1,65 @@
 use crate::client::DarkClient;
+use crate::logging::log_limited;
 use crate::mapping::class::MinecraftClass;
 use crate::mapping::class_type::MinecraftClassType;
 use crate::mapping::client::minecraft::Minecraft;
-use crate::mapping::minecraft_version::MinecraftVersion;
-use jni::objects::{GlobalRef, JObject, JString, JValue, JValueOwned};
+use crate::mapping::java::JavaArray;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::mapping::minecraft_version::{detect_game_version, MinecraftVersion};
+use jni::objects::{GlobalRef, JClass, JObject, JString, JValue, JValueOwned};
 use jni::JNIEnv;
-use log::error;
-use serde::Deserialize;
+use log::{error, info, Level};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 pub mod class;
 pub mod class_type;
 pub mod client;
 pub mod entity;
 pub mod java;
+pub mod jvm_access;
 mod method;
 mod minecraft_version;
 
 pub trait GameContext {
-    fn minecraft(&self) -> &'static Minecraft {
+    fn minecraft(&self) -> anyhow::Result<&'static Minecraft> {
         Minecraft::instance()
     }
 
-    fn mapping(&self) -> &'static Mapping {
-        self.minecraft().get_mapping()
+    fn mapping(&self) -> anyhow::Result<&'static Mapping> {
+        Ok(self.minecraft()?.get_mapping())
+    }
+
+    /// The mapping as a `JvmAccess`, for code that can also run on a fake in tests
+    fn jvm(&self) -> anyhow::Result<&'static dyn JvmAccess> {
+        Ok(self.mapping()?)
     }
 }
 
-/// Root structure containing all mapped Minecraft classes
-#[derive(Debug, Deserialize)]
+// Folder of the per-version mappings, `<version>.json` files
+const MAPPINGS_DIRECTORY_VAR: &str = "DARKCLIENT_MAPPINGS_DIR";
+// Relative to the working directory, the game directory
+const DEFAULT_MAPPINGS_DIRECTORY: &str = "mappings";
+
+/// Root structure containing all mapped Minecraft classes.
+///
+/// `mappings.json` has the shape
+/// `{"version": "1.21.10", "classes": {"<deobfuscated internal name>": {"name": ..., "methods": ..., "fields": ...}}}`,
+/// where each method name maps to `{"name": ..., "signature": ...}` or to an array of them
+/// for overloads, and each field name maps to `{"name": ...}`
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Mapping {
     version: MinecraftVersion,
+    #[serde(serialize_with = "class::serialize_sorted")]
     classes: HashMap<String, MinecraftClass>,
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Copy)]
 pub enum FieldType<'local> {
     Boolean,
     Byte,
@@ -70,18 +95,134 @@ impl FieldType<'_> {
 
 #[allow(dead_code)]
 impl Mapping {
+    /// The mapping embedded in the client, built from `mappings.json`
     pub fn new() -> anyhow::Result<Mapping> {
-        let contents = include_str!("../../../mappings.json");
-        let mapping: Mapping = serde_json::from_str(contents)?;
-        Ok(mapping)
+        include_str!("../../../mappings.json").parse()
+    }
+
+    /// Reads a mapping file in the `mappings.json` format
+    pub fn from_path(path: &Path) -> anyhow::Result<Mapping> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        contents
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid mapping {}: {}", path.display(), e))
+    }
+
+    /// The mapping for the running game: `<version>.json` in the mappings directory, or
+    /// the closest lower version with a warning. The embedded mapping takes part as one
+    /// more version, and is used as is when the game version can't be detected or the
+    /// file can't be loaded. Only an invalid embedded mapping is an error
+    pub fn load() -> anyhow::Result<Mapping> {
+        let embedded = Mapping::new()
+            .map_err(|e| anyhow::anyhow!("The embedded mapping is invalid: {}", e))?;
+        let game_version = match detect_game_version() {
+            Ok(version) => version,
+            Err(e) => {
+                log_limited(
+                    Level::Warn,
+                    &format!(
+                        "Failed to detect the game version, using the embedded mapping for {}: {}",
+                        embedded.version, e
+                    ),
+                );
+                return Ok(embedded);
+            }
+        };
+
+        Ok(Self::select(embedded, game_version, &mappings_directory()))
+    }
+
+    // The mapping of `directory` closest to `game_version`, the embedded one when it's
+    // closer or when the file fails to load
+    fn select(embedded: Mapping, game_version: MinecraftVersion, directory: &Path) -> Mapping {
+        if std::env::var_os(MAPPINGS_DIRECTORY_VAR).is_some() && !directory.is_dir() {
+            log_limited(
+                Level::Warn,
+                &format!(
+                    "{} is set but {} is not a directory",
+                    MAPPINGS_DIRECTORY_VAR,
+                    directory.display()
+                ),
+            );
+        }
+
+        let files = mapping_files(directory);
+        let mut versions: Vec<MinecraftVersion> = files.keys().copied().collect();
+        versions.push(embedded.version);
+
+        let Some(version) = closest_version(&versions, game_version) else {
+            log_limited(
+                Level::Warn,
+                &format!(
+                    "No mapping for Minecraft {} or older in {}, using the embedded mapping for {}",
+                    game_version,
+                    directory.display(),
+                    embedded.version
+                ),
+            );
+            return embedded;
+        };
+        if version != game_version {
+            log_limited(
+                Level::Warn,
+                &format!(
+                    "No mapping for Minecraft {}, using the closest lower version {}",
+                    game_version, version
+                ),
+            );
+        }
+
+        // A file replaces the embedded mapping of the same version
+        let Some(path) = files.get(&version) else {
+            return embedded;
+        };
+        match Mapping::from_path(path) {
+            Ok(mapping) => {
+                info!("Loaded the mapping {}", path.display());
+                mapping
+            }
+            // The file may have been removed since the directory was listed
+            Err(e) => {
+                log_limited(
+                    Level::Warn,
+                    &format!("{}, using the embedded mapping for {}", e, embedded.version),
+                );
+                embedded
+            }
+        }
+    }
+
+    /// Creates a mapping without classes, for tools generating a mapping file
+    pub fn empty(version: MinecraftVersion) -> Mapping {
+        Mapping {
+            version,
+            classes: HashMap::new(),
+        }
+    }
+
+    /// Adds a class keyed by its deobfuscated internal name (e.g. `net/minecraft/world/entity/Entity`)
+    pub fn class(mut self, name: &str, class: MinecraftClass) -> Mapping {
+        self.classes.insert(name.to_string(), class);
+        self
+    }
+
+    /// Serializes the mapping in the `mappings.json` format, parsed back by `from_str`.
+    /// Classes, methods and fields are sorted by name
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+        self.serialize(&mut serializer)?;
+        Ok(String::from_utf8(out)?)
     }
 
-    fn get_client(&self) -> &DarkClient {
+    fn get_client(&self) -> anyhow::Result<&'static DarkClient> {
         DarkClient::instance()
     }
 
     fn get_env(&'_ self) -> anyhow::Result<JNIEnv<'_>> {
-        Ok(self.get_client().get_env()?)
+        Ok(self.get_client()?.get_env()?)
     }
 
     pub fn get_version(&self) -> MinecraftVersion {
@@ -103,7 +244,7 @@ impl Mapping {
             .map(|(deobfuscated_name, _)| deobfuscated_name.as_str())
     }
 
-    fn translate_type_descriptor<'a>(&self, descriptor: &mut &'a str) -> String {
+    fn translate_type_descriptor(&self, descriptor: &mut &str) -> String {
         let mut array_brackets = String::new();
         while descriptor.starts_with('[') {
             array_brackets.push_str("[]");
@@ -163,6 +304,136 @@ impl Mapping {
         }
     }
 
+    /// Finds the JVM class of a class type through its mapped name, the name the class has
+    /// in the running game, never through the deobfuscated `get_name`
+    pub fn find_class<'local>(
+        &self,
+        env: &mut JNIEnv<'local>,
+        class_type: MinecraftClassType,
+    ) -> anyhow::Result<JClass<'local>> {
+        let class = self.get_class(class_type.get_name())?;
+        match env.find_class(&class.name) {
+            Ok(jclass) => Ok(jclass),
+            Err(_) => {
+                // Clear the NoClassDefFoundError so the JVM isn't left with a pending exception
+                let _ = env.exception_clear();
+                Err(anyhow::anyhow!(
+                    "Class {} ({}) not found",
+                    class_type.get_name(),
+                    class.name
+                ))
+            }
+        }
+    }
+
+    /// Checks that the mapping matches the running game by resolving the `Minecraft`
+    /// class, so a wrong version fails once with a clear error instead of on every call
+    pub fn check_supported(&self) -> anyhow::Result<()> {
+        let class_type = MinecraftClassType::Minecraft;
+        let class = self.get_class(class_type.get_name()).map_err(|_| {
+            anyhow::anyhow!(
+                "Unsupported mapping: {} is not mapped for version {}",
+                class_type.get_name(),
+                self.version
+            )
+        })?;
+
+        let mut env = self.get_env()?;
+        match self.find_class(&mut env, class_type) {
+            Ok(jclass) => {
+                env.delete_local_ref(jclass)?;
+                Ok(())
+            }
+            Err(_) => Err(anyhow::anyhow!(
+                "Unsupported or obfuscated mapping: {} ({}) was not found in the game, \
+                 the mapping is for Minecraft {}, check that it matches the running version",
+                class_type.get_name(),
+                class.name,
+                self.version
+            )),
+        }
+    }
+
+    /// Returns the class types of `class_types` that aren't mapped or can't be found in the
+    /// game, empty if the mapping supports all of them
+    pub fn validate(
+        &self,
+        class_types: &[MinecraftClassType],
+    ) -> anyhow::Result<Vec<MinecraftClassType>> {
+        let mut env = self.get_env()?;
+        let missing = class_types
+            .iter()
+            .copied()
+            .filter(|class_type| match self.find_class(&mut env, *class_type) {
+                Ok(jclass) => {
+                    let _ = env.delete_local_ref(jclass);
+                    false
+                }
+                Err(_) => true,
+            })
+            .collect();
+
+        Ok(missing)
+    }
+
+    /// Lists how each `MinecraftClassType` resolves at runtime: its obfuscated name,
+    /// whether the JVM can find it, and every mapped method with its translated signature
+    pub fn dump_resolved(&self) -> String {
+        let mut env = match self.get_env() {
+            Ok(env) => Some(env),
+            Err(e) => {
+                error!("Failed to get env for the mapping dump: {}", e);
+                None
+            }
+        };
+
+        let mut dump = format!("Mapping version {}\n", self.version);
+        for class_type in MinecraftClassType::ALL {
+            let class = match self.get_class(class_type.get_name()) {
+                Ok(class) => class,
+                Err(_) => {
+                    let _ = writeln!(dump, "{} -> <not mapped>", class_type.get_name());
+                    continue;
+                }
+            };
+
+            let status = match env.as_mut() {
+                Some(env) => match self.find_class(env, class_type) {
+                    Ok(jclass) => {
+                        let _ = env.delete_local_ref(jclass);
+                        "found"
+                    }
+                    Err(_) => "not found",
+                },
+                None => "unknown",
+            };
+            let _ = writeln!(
+                dump,
+                "{} -> {} [{}]",
+                class_type.get_name(),
+                class.name,
+                status
+            );
+
+            let mut methods: Vec<_> = class.get_all_methods().iter().collect();
+            methods.sort_by_key(|(name, _)| name.as_str());
+            for (name, overloads) in methods {
+                for method in overloads {
+                    let _ = writeln!(
+                        dump,
+                        "    {} ({}) {} {}",
+                        name,
+                        method.name,
+                        method.signature,
+                        self.translate_signature(&method.signature)
+                    );
+                }
+            }
+        }
+
+        dump
+    }
+
     pub fn call_static_method(
         &'_ self,
         class_type: MinecraftClassType,
@@ -172,10 +443,7 @@ impl Mapping {
         let mut env = self.get_env()?;
 
         let class = self.get_class(class_type.get_name())?;
-        let jclass = match env.find_class(&class.name) {
-            Ok(jclass) => jclass,
-            Err(_) => return Err(anyhow::anyhow!("Class {} ({}) not found", class_type.get_name(), class.name)),
-        };
+        let jclass = self.find_class(&mut env, class_type)?;
         let method = class.get_method_by_args(method_name, args)?;
         match env.call_static_method(jclass, &method.name, &method.signature, args) {
             Ok(value) => Ok(value),
@@ -222,6 +490,23 @@ impl Mapping {
         }
     }
 
+    /// Calls a method discarding its return value, deleting the local reference if the
+    /// method returns an object so it doesn't pile up in the current JNI frame
+    pub fn call_void_method(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        method_name: &str,
+        args: &[JValue],
+    ) -> anyhow::Result<()> {
+        let value = self.call_method(class_type, instance, method_name, args)?;
+        if let JValueOwned::Object(object) = value {
+            self.get_env()?.delete_local_ref(object)?;
+        }
+
+        Ok(())
+    }
+
     pub fn get_static_field(
         &'_ self,
         class_type: MinecraftClassType,
@@ -231,10 +516,7 @@ impl Mapping {
         let mut env = self.get_env()?;
 
         let class = self.get_class(class_type.get_name())?;
-        let jclass = match env.find_class(&class.name) {
-            Ok(jclass) => jclass,
-            Err(_) => return Err(anyhow::anyhow!("Class {} ({}) not found", class_type.get_name(), class.name)),
-        };
+        let jclass = self.find_class(&mut env, class_type)?;
         let field = class.get_field(field_name)?;
         match env.get_static_field(jclass, &field.name, field_type.get_signature()?) {
             Ok(value) => Ok(value),
@@ -276,6 +558,34 @@ impl Mapping {
         }
     }
 
+    /// Reads an object field that can legitimately be null, e.g. `Minecraft.player` at the
+    /// title screen, `screen` while playing or `hitResult` before the first tick.
+    /// Returns None for null instead of a reference failing further down
+    pub fn get_field_optional(
+        &self,
+        class_type: MinecraftClassType,
+        instance: &JObject,
+        field_name: &str,
+        field_type: FieldType,
+    ) -> anyhow::Result<Option<GlobalRef>> {
+        if !matches!(field_type, FieldType::Object(..) | FieldType::String) {
+            return Err(anyhow::anyhow!(
+                "Field {} of class {} is a primitive, it can't be null",
+                field_name,
+                class_type.get_name()
+            ));
+        }
+
+        let value = self
+            .get_field(class_type, instance, field_name, field_type)?
+            .l()?;
+        if value.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.new_global_ref(value)?))
+    }
+
     pub fn set_field(
         &self,
         class_type: MinecraftClassType,
@@ -307,6 +617,113 @@ impl Mapping {
         Ok(env.new_global_ref(obj)?)
     }
 
+    pub fn new_int_array(&self, values: &[i32]) -> anyhow::Result<JavaArray> {
+        let env = self.get_env()?;
+        let array = env.new_int_array(values.len() as i32)?;
+        let filled = env.set_int_array_region(&array, 0, values);
+        self.new_array(JObject::from(array), values.len(), filled)
+    }
+
+    pub fn new_float_array(&self, values: &[f32]) -> anyhow::Result<JavaArray> {
+        let env = self.get_env()?;
+        let array = env.new_float_array(values.len() as i32)?;
+        let filled = env.set_float_array_region(&array, 0, values);
+        self.new_array(JObject::from(array), values.len(), filled)
+    }
+
+    pub fn new_double_array(&self, values: &[f64]) -> anyhow::Result<JavaArray> {
+        let env = self.get_env()?;
+        let array = env.new_double_array(values.len() as i32)?;
+        let filled = env.set_double_array_region(&array, 0, values);
+        self.new_array(JObject::from(array), values.len(), filled)
+    }
+
+    /// Creates an array of the mapped class `class_type` containing the given elements
+    pub fn new_object_array(
+        &self,
+        class_type: MinecraftClassType,
+        elements: &[&JObject],
+    ) -> anyhow::Result<JavaArray> {
+        let mut env = self.get_env()?;
+
+        let jclass = self.find_class(&mut env, class_type)?;
+
+        let array = env.new_object_array(elements.len() as i32, &jclass, JObject::null());
+        env.delete_local_ref(jclass)?;
+        let array = array?;
+        let filled = elements
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, element)| {
+                env.set_object_array_element(&array, index as i32, element)
+            });
+        self.new_array(JObject::from(array), elements.len(), filled)
+    }
+
+    // Keeps `array` as a global reference if it was filled, the local reference is deleted
+    // either way so callers in a loop don't run out of them
+    fn new_array(
+        &self,
+        array: JObject,
+        length: usize,
+        filled: jni::errors::Result<()>,
+    ) -> anyhow::Result<JavaArray> {
+        let env = self.get_env()?;
+        let jni_ref = filled.and_then(|_| env.new_global_ref(&array));
+        env.delete_local_ref(array)?;
+        Ok(JavaArray {
+            jni_ref: jni_ref?,
+            length,
+        })
+    }
+
+    /// Creates an instance of the mapped class `class_type`, picking the constructor
+    /// matching `args`
+    pub fn new_object<'local>(
+        &self,
+        env: &mut JNIEnv<'local>,
+        class_type: MinecraftClassType,
+        args: &[JValue],
+    ) -> anyhow::Result<JObject<'local>> {
+        let class = self.get_class(class_type.get_name())?;
+        let constructor = class.get_method_by_args("<init>", args)?;
+        let jclass = self.find_class(env, class_type)?;
+
+        env.new_object(jclass, &constructor.signature, args)
+            .map_err(|e| {
+                let _ = env.exception_clear();
+                anyhow::anyhow!(
+                    "Error creating {} ({}) with signature {}: {}",
+                    class_type.get_name(),
+                    class.name,
+                    self.translate_signature(&constructor.signature),
+                    e
+                )
+            })
+    }
+
+    pub fn new_string(&self, value: &str) -> anyhow::Result<JObject<'_>> {
+        let env = self.get_env()?;
+        Ok(JObject::from(env.new_string(value)?))
+    }
+
+    /// Deobfuscated name of the class of `obj` (e.g. `net/minecraft/client/gui/screens/TitleScreen`),
+    /// or its runtime name if the class is not mapped
+    pub fn get_class_name(&self, obj: &JObject) -> anyhow::Result<String> {
+        let mut env = self.get_env()?;
+
+        let class = env.get_object_class(obj)?;
+        let name = env
+            .call_method(&class, "getName", "()Ljava/lang/String;", &[])?
+            .l()?;
+        let name = self.get_string(name)?.replace('.', "/");
+
+        Ok(self
+            .find_class_by_obfuscated_name(&name)
+            .map(str::to_string)
+            .unwrap_or(name))
+    }
+
     pub fn get_string(&self, obj: JObject) -> anyhow::Result<String> {
         let env = self.get_env()?;
         let jstring = JString::from(obj);
@@ -320,11 +737,210 @@ impl Mapping {
     }
 }
 
+/// Folder of the per-version mapping files, `DARKCLIENT_MAPPINGS_DIR` or `mappings`
+/// in the game directory
+pub fn mappings_directory() -> PathBuf {
+    std::env::var_os(MAPPINGS_DIRECTORY_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_MAPPINGS_DIRECTORY))
+}
+
+// The `<version>.json` files of `directory` by version, empty if it doesn't exist.
+// Files whose name isn't a version are ignored
+fn mapping_files(directory: &Path) -> HashMap<MinecraftVersion, PathBuf> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return HashMap::new();
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|path| {
+            let version = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((version, path))
+        })
+        .collect()
+}
+
+// `game_version` if available, otherwise the highest version below it
+fn closest_version(
+    available: &[MinecraftVersion],
+    game_version: MinecraftVersion,
+) -> Option<MinecraftVersion> {
+    available
+        .iter()
+        .copied()
+        .filter(|version| *version <= game_version)
+        .max()
+}
+
+impl FromStr for Mapping {
+    type Err = anyhow::Error;
+
+    fn from_str(contents: &str) -> anyhow::Result<Mapping> {
+        Ok(serde_json::from_str(contents)?)
+    }
+}
+
 impl Default for Mapping {
     fn default() -> Self {
-        Self::new().unwrap_or_else(|_| {
-            error!("Failed to load mappings");
-            panic!("Failed to load mappings");
+        Self::new().unwrap_or_else(|e| {
+            error!("The embedded mapping is invalid: {}", e);
+            panic!("The embedded mapping is invalid: {}", e);
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_round_trips() {
+        let mapping = Mapping::empty(MinecraftVersion::new(1, 21, 10)).class(
+            "net/minecraft/world/entity/Entity",
+            MinecraftClass::new("bum")
+                .method("position", "dt", "()Lftm;")
+                .field("fallDistance", "ak"),
+        );
+
+        let json = mapping.to_json().unwrap();
+        let parsed: Mapping = json.parse().unwrap();
+
+        assert_eq!(parsed.get_version(), mapping.get_version());
+        let entity = parsed
+            .get_class("net/minecraft/world/entity/Entity")
+            .unwrap();
+        assert_eq!(entity.name, "bum");
+        assert_eq!(entity.get_method("position").unwrap().signature, "()Lftm;");
+        assert_eq!(parsed.to_json().unwrap(), json);
+    }
+
+    // Entity is obfuscated as `bum` and Vec3 as `ftm`
+    fn fixture_mapping() -> Mapping {
+        Mapping::empty(MinecraftVersion::new(1, 21, 10))
+            .class(
+                "net/minecraft/world/entity/Entity",
+                MinecraftClass::new("bum"),
+            )
+            .class("net/minecraft/world/phys/Vec3", MinecraftClass::new("ftm"))
+    }
+
+    #[test]
+    fn test_translate_signature_primitives_and_arrays() {
+        let mapping = fixture_mapping();
+
+        assert_eq!(
+            mapping.translate_signature("(ILjava/lang/String;[I)V"),
+            "(int, java/lang/String, int[]) -> void"
+        );
+        assert_eq!(
+            mapping.translate_signature("([[D[[Ljava/lang/Object;JZ)[[[F"),
+            "(double[][], java/lang/Object[][], long, boolean) -> float[][][]"
+        );
+        assert_eq!(mapping.translate_signature("()V"), "() -> void");
+    }
+
+    #[test]
+    fn test_translate_signature_deobfuscates_mapped_classes() {
+        let mapping = fixture_mapping();
+
+        assert_eq!(
+            mapping.translate_signature("(Lbum;[Lftm;F)Lftm;"),
+            "(net/minecraft/world/entity/Entity, net/minecraft/world/phys/Vec3[], float) \
+             -> net/minecraft/world/phys/Vec3"
+        );
+        // Unmapped obfuscated names are kept as is
+        assert_eq!(mapping.translate_signature("(Lxyz;)V"), "(xyz) -> void");
+    }
+
+    #[test]
+    fn test_translate_type_descriptor_consumes_one_type() {
+        let mapping = fixture_mapping();
+        let mut descriptor = "[LbumI";
+
+        // Malformed object type without `;`, the rest is returned
+        assert_eq!(
+            mapping.translate_type_descriptor(&mut descriptor),
+            "LbumI[]"
+        );
+        assert_eq!(descriptor, "");
+
+        let mut descriptor = "Lbum;I";
+        assert_eq!(
+            mapping.translate_type_descriptor(&mut descriptor),
+            "net/minecraft/world/entity/Entity"
+        );
+        assert_eq!(descriptor, "I");
+        assert_eq!(mapping.translate_type_descriptor(&mut descriptor), "int");
+        assert_eq!(descriptor, "");
+    }
+
+    #[test]
+    fn test_translate_signature_keeps_invalid_signatures() {
+        assert_eq!(fixture_mapping().translate_signature("I"), "I");
+    }
+
+    #[test]
+    fn test_closest_version_prefers_exact_then_lower() {
+        let available = [
+            MinecraftVersion::new(1, 20, 4),
+            MinecraftVersion::new(1, 21, 10),
+            MinecraftVersion::new(1, 21, 4),
+        ];
+
+        assert_eq!(
+            closest_version(&available, MinecraftVersion::new(1, 21, 4)),
+            Some(MinecraftVersion::new(1, 21, 4))
+        );
+        assert_eq!(
+            closest_version(&available, MinecraftVersion::new(1, 21, 8)),
+            Some(MinecraftVersion::new(1, 21, 4))
+        );
+        assert_eq!(
+            closest_version(&available, MinecraftVersion::new(1, 22, 0)),
+            Some(MinecraftVersion::new(1, 21, 10))
+        );
+        assert_eq!(
+            closest_version(&available, MinecraftVersion::new(1, 19, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_select_falls_back_to_embedded() {
+        let directory =
+            std::env::temp_dir().join(format!("darkclient_mappings_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let newer = MinecraftVersion::new(1, 21, 11);
+        fs::write(
+            directory.join("1.21.11.json"),
+            Mapping::empty(newer).to_json().unwrap(),
+        )
+        .unwrap();
+        fs::write(directory.join("1.21.12.json"), "{\"version\": ").unwrap();
+        let embedded = || Mapping::new().unwrap();
+
+        let selected = Mapping::select(embedded(), newer, &directory);
+        assert_eq!(selected.version, newer);
+        // A malformed file doesn't fail the load
+        let selected = Mapping::select(embedded(), MinecraftVersion::new(1, 21, 12), &directory);
+        assert_eq!(selected.version, embedded().version);
+        let selected = Mapping::select(embedded(), newer, &directory.join("missing"));
+        assert_eq!(selected.version, embedded().version);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_bundled_mapping_round_trips() {
+        let mapping = Mapping::new().unwrap();
+        let json = mapping.to_json().unwrap();
+
+        assert_eq!(json.parse::<Mapping>().unwrap().to_json().unwrap(), json);
+    }
+}
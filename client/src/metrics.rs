@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Weight of the newest sample in the rolling average, roughly the last 20 ticks
+const AVERAGE_WEIGHT: f64 = 0.1;
+
+// Off by default, the tick loop doesn't even read the clock while disabled
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TICK_METRICS: Mutex<Option<HashMap<String, TickMetric>>> = Mutex::new(None);
+
+/// Time spent in the `on_tick` of a module
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TickMetric {
+    // Rolling average in milliseconds
+    pub average_ms: f64,
+    pub max_ms: f64,
+    pub samples: u64,
+}
+
+impl TickMetric {
+    fn record(&mut self, elapsed: Duration) {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        self.average_ms = if self.samples == 0 {
+            elapsed_ms
+        } else {
+            self.average_ms + (elapsed_ms - self.average_ms) * AVERAGE_WEIGHT
+        };
+        self.max_ms = self.max_ms.max(elapsed_ms);
+        self.samples += 1;
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Starts or stops recording tick times, previous measurements are cleared when enabling
+pub fn set_enabled(enabled: bool) {
+    if enabled && !ENABLED.load(Ordering::Relaxed) {
+        *TICK_METRICS.lock().unwrap() = None;
+    }
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Records the duration of a module's `on_tick`
+pub fn record_tick(module_name: &str, elapsed: Duration) {
+    let mut metrics = TICK_METRICS.lock().unwrap();
+    let metrics = metrics.get_or_insert_with(HashMap::new);
+    match metrics.get_mut(module_name) {
+        Some(metric) => metric.record(elapsed),
+        None => {
+            let mut metric = TickMetric::default();
+            metric.record(elapsed);
+            metrics.insert(module_name.to_string(), metric);
+        }
+    }
+}
+
+/// Tick times of every module ticked since metrics were enabled, slowest first
+pub fn tick_metrics() -> Vec<(String, TickMetric)> {
+    let mut metrics: Vec<_> = TICK_METRICS
+        .lock()
+        .unwrap()
+        .iter()
+        .flatten()
+        .map(|(name, metric)| (name.clone(), *metric))
+        .collect();
+    metrics.sort_by(|a, b| b.1.average_ms.total_cmp(&a.1.average_ms));
+    metrics
+}
+
+/// Human readable report of the tick times, one module per line
+pub fn report() -> String {
+    if !is_enabled() {
+        return String::from("Metrics are disabled, enable them with `metrics on`\n");
+    }
+
+    let metrics = tick_metrics();
+    if metrics.is_empty() {
+        return String::from("No module ticked yet\n");
+    }
+
+    metrics
+        .iter()
+        .map(|(name, metric)| {
+            format!(
+                "{}: avg {:.3} ms, max {:.3} ms, {} ticks\n",
+                name, metric.average_ms, metric.max_ms, metric.samples
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_metric_rolling_average() {
+        let mut metric = TickMetric::default();
+
+        metric.record(Duration::from_millis(10));
+        assert_eq!(metric.average_ms, 10.0);
+
+        metric.record(Duration::from_millis(20));
+        assert!((metric.average_ms - 11.0).abs() < 1e-9);
+        assert_eq!(metric.max_ms, 20.0);
+        assert_eq!(metric.samples, 2);
+    }
+}
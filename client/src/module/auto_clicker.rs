@@ -0,0 +1,244 @@
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::module::{
+    KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting, DEFAULT_PRIORITY,
+};
+use crate::random::jitter;
+use crate::render::RenderContext;
+use jni::objects::JObject;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ClickButton {
+    Left,
+    Right,
+}
+
+impl ClickButton {
+    const ALL: [ClickButton; 2] = [ClickButton::Left, ClickButton::Right];
+
+    fn setting_name(self) -> &'static str {
+        match self {
+            ClickButton::Left => "Left click",
+            ClickButton::Right => "Right click",
+        }
+    }
+
+    // Field of Options holding the KeyMapping, the mouse button unless rebound
+    fn key_mapping(self) -> &'static str {
+        match self {
+            ClickButton::Left => "keyAttack",
+            ClickButton::Right => "keyUse",
+        }
+    }
+}
+
+/// Clicks for the player while the attack or use button is held, at a random rate
+/// between Min CPS and Max CPS. Clicks go through `Minecraft.startAttack` and
+/// `Minecraft.startUseItem`, like a real click, on the render thread every frame so
+/// the rate is capped by the frame rate
+#[derive(Debug)]
+pub struct AutoClickerModule {
+    pub module: ModuleData,
+    jvm: &'static dyn JvmAccess,
+    // When each button clicks next, None while it isn't held
+    next_clicks: Mutex<[Option<Instant>; 2]>,
+}
+
+impl AutoClickerModule {
+    pub fn new(jvm: &'static dyn JvmAccess) -> Self {
+        Self {
+            jvm,
+            next_clicks: Mutex::new([None; 2]),
+            module: ModuleData {
+                id: "AutoClicker",
+                name: "AutoClicker".to_string(),
+                description: "Keeps clicking while a mouse button is held".to_string(),
+                category: ModuleCategory::COMBAT,
+                key_bind: KeyboardKey::KeyNone,
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![
+                    ModuleSetting::Slider {
+                        name: "Min CPS".to_string(),
+                        value: 8.0,
+                        min: 1.0,
+                        max: 20.0,
+                        step: Some(0.5),
+                    },
+                    ModuleSetting::Slider {
+                        name: "Max CPS".to_string(),
+                        value: 12.0,
+                        min: 1.0,
+                        max: 20.0,
+                        step: Some(0.5),
+                    },
+                    ModuleSetting::Toggle {
+                        name: ClickButton::Left.setting_name().to_string(),
+                        value: true,
+                    },
+                    ModuleSetting::Toggle {
+                        name: ClickButton::Right.setting_name().to_string(),
+                        value: false,
+                    },
+                ],
+            },
+        }
+    }
+
+    fn get_slider(&self, name: &str, default: f32) -> f32 {
+        self.module
+            .get_setting(name)
+            .and_then(|s| s.get_slider_value())
+            .unwrap_or(default)
+    }
+
+    fn get_toggle(&self, name: &str) -> bool {
+        self.module
+            .get_setting(name)
+            .and_then(|s| s.get_toggle_value())
+            .unwrap_or(false)
+    }
+
+    fn next_interval(&self) -> Duration {
+        click_interval(
+            self.get_slider("Min CPS", 8.0),
+            self.get_slider("Max CPS", 12.0),
+        )
+    }
+}
+
+// Time until the next click, at a random rate between `min_cps` and `max_cps` in
+// either order
+fn click_interval(min_cps: f32, max_cps: f32) -> Duration {
+    let middle = (min_cps as f64 + max_cps as f64) / 2.0;
+    let spread = (max_cps as f64 - min_cps as f64) / 2.0;
+    let cps = jitter(middle, spread).max(1.0);
+    Duration::from_secs_f64(1.0 / cps)
+}
+
+fn click(jvm: &dyn JvmAccess, minecraft: &JObject, button: ClickButton) -> anyhow::Result<()> {
+    match button {
+        ClickButton::Left => {
+            jvm.call_method(MinecraftClassType::Minecraft, minecraft, "startAttack", &[])?;
+        }
+        ClickButton::Right => {
+            jvm.call_void_method(
+                MinecraftClassType::Minecraft,
+                minecraft,
+                "startUseItem",
+                &[],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+impl Module for AutoClickerModule {
+    fn on_start(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_stop(&self) -> anyhow::Result<()> {
+        *self.next_clicks.lock().unwrap() = [None; 2];
+        Ok(())
+    }
+
+    fn on_tick(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // Clicking must happen on the game thread, the render thread is the one
+    fn on_render(&self, _context: &RenderContext) -> anyhow::Result<()> {
+        let minecraft = self.jvm.minecraft()?;
+        let mut next_clicks = self.next_clicks.lock().unwrap();
+        // Buttons held in a menu click the menu, not the world
+        if self.jvm.screen(&minecraft)?.is_some() {
+            *next_clicks = [None; 2];
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        for button in ClickButton::ALL {
+            let next_click = &mut next_clicks[button as usize];
+            if !self.get_toggle(button.setting_name())
+                || !self
+                    .jvm
+                    .is_key_mapping_down(&minecraft, button.key_mapping())?
+            {
+                *next_click = None;
+                continue;
+            }
+            // Holding attack on a block mines it, a click would restart the mining
+            let looking_at_block = self
+                .jvm
+                .hit_result(&minecraft)?
+                .is_some_and(|hit| hit.is_block());
+            if button == ClickButton::Left && looking_at_block {
+                *next_click = None;
+                continue;
+            }
+
+            match *next_click {
+                // The press itself was the first click
+                None => {}
+                Some(time) if now < time => continue,
+                Some(_) => click(self.jvm, &minecraft, button)?,
+            }
+            *next_click = Some(now + self.next_interval());
+        }
+        Ok(())
+    }
+
+    fn requires_player(&self) -> bool {
+        true
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[
+            MinecraftClassType::Options,
+            MinecraftClassType::KeyMapping,
+            MinecraftClassType::BlockHitResult,
+            MinecraftClassType::Vec3i,
+        ]
+    }
+
+    fn get_module_data(&self) -> &ModuleData {
+        &self.module
+    }
+
+    fn get_module_data_mut(&mut self) -> &mut ModuleData {
+        &mut self.module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::jvm_access::fake::FakeJvm;
+
+    #[test]
+    fn test_click_interval_stays_in_cps_range() {
+        assert_eq!(click_interval(10.0, 10.0), Duration::from_millis(100));
+        for _ in 0..100 {
+            // Crossed sliders behave like ordered ones
+            for interval in [click_interval(10.0, 20.0), click_interval(20.0, 10.0)] {
+                assert!(interval >= Duration::from_millis(50));
+                assert!(interval <= Duration::from_millis(100));
+            }
+        }
+    }
+
+    #[test]
+    fn test_click_calls_attack_and_use() {
+        let jvm = FakeJvm::new();
+        click(&jvm, &JObject::null(), ClickButton::Left).unwrap();
+        click(&jvm, &JObject::null(), ClickButton::Right).unwrap();
+        assert_eq!(
+            jvm.calls(),
+            vec!["Minecraft.startAttack([])", "Minecraft.startUseItem([])"]
+        );
+    }
+}
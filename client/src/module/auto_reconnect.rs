@@ -0,0 +1,202 @@
+use crate::client::DarkClient;
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::client::minecraft::Minecraft;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::mapping::FieldType;
+use crate::module::{
+    KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting, DEFAULT_PRIORITY,
+};
+use crate::time::GameTime;
+use jni::objects::GlobalRef;
+use log::{error, info, warn};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct ReconnectState {
+    // Server data of the last multiplayer server we were connected to
+    last_server: Option<GlobalRef>,
+    // Started when the disconnect screen was first seen
+    disconnected_since: Option<GameTime>,
+    // Reconnects attempted since the last successful connection
+    attempts: u32,
+    // Whether the retry limit was reached, until the next successful connection
+    gave_up: bool,
+}
+
+/// Reconnects to the last server after being disconnected, giving up after a number of
+/// failed attempts so a server that is down doesn't get hammered forever
+#[derive(Debug)]
+pub struct AutoReconnectModule {
+    pub module: ModuleData,
+    jvm: &'static dyn JvmAccess,
+    state: Mutex<ReconnectState>,
+}
+
+impl AutoReconnectModule {
+    pub fn new(jvm: &'static dyn JvmAccess) -> Self {
+        Self {
+            jvm,
+            module: ModuleData {
+                id: "AutoReconnect",
+                name: "AutoReconnect".to_string(),
+                description: "Reconnects to the last server when disconnected".to_string(),
+                category: ModuleCategory::MISC,
+                key_bind: KeyboardKey::KeyNone,
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![
+                    ModuleSetting::Slider {
+                        name: "Delay".to_string(),
+                        value: 5.0,
+                        min: 1.0,
+                        max: 30.0,
+                        step: Some(0.5),
+                    },
+                    ModuleSetting::IntSlider {
+                        name: "Retries".to_string(),
+                        value: 3,
+                        min: 1,
+                        max: 10,
+                    },
+                ],
+            },
+            state: Mutex::new(ReconnectState::default()),
+        }
+    }
+
+    pub fn get_delay(&self) -> Duration {
+        let seconds = self
+            .module
+            .get_setting("Delay")
+            .and_then(|s| s.get_slider_value())
+            .unwrap_or(5.0);
+        Duration::from_secs_f32(seconds.max(0.0))
+    }
+
+    pub fn get_retries(&self) -> u32 {
+        self.module
+            .get_setting("Retries")
+            .and_then(|s| s.get_int_value())
+            .map(|value| value.max(0) as u32)
+            .unwrap_or(3)
+    }
+
+    fn is_screen(
+        &self,
+        screen: &GlobalRef,
+        class_type: MinecraftClassType,
+    ) -> anyhow::Result<bool> {
+        Ok(self.jvm.mapping().get_class_name(screen.as_obj())? == class_type.get_name())
+    }
+
+    fn reconnect(&self, screen: GlobalRef, server: GlobalRef) -> anyhow::Result<()> {
+        DarkClient::instance()?.run_on_render_thread(move || {
+            // The connect screen goes back where the disconnect screen would have
+            let result = Minecraft::instance().and_then(|minecraft| {
+                let parent = minecraft
+                    .get_mapping()
+                    .get_field(
+                        MinecraftClassType::DisconnectedScreen,
+                        screen.as_obj(),
+                        "parent",
+                        FieldType::Object(MinecraftClassType::Screen, minecraft.get_mapping()),
+                    )?
+                    .l()?;
+                minecraft.connect_to_server(&parent, &server)
+            });
+
+            if let Err(e) = result {
+                error!("Failed to reconnect: {}", e);
+            }
+        })
+    }
+}
+
+impl Module for AutoReconnectModule {
+    fn on_start(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.disconnected_since = None;
+        state.attempts = 0;
+        state.gave_up = false;
+        Ok(())
+    }
+
+    fn on_stop(&self) -> anyhow::Result<()> {
+        *self.state.lock().unwrap() = ReconnectState::default();
+        Ok(())
+    }
+
+    fn on_tick(&self) -> anyhow::Result<()> {
+        let minecraft = self.jvm.minecraft()?;
+        let mut state = self.state.lock().unwrap();
+
+        let screen = self.jvm.screen(&minecraft)?;
+        let disconnected = match &screen {
+            Some(screen) => self.is_screen(screen, MinecraftClassType::DisconnectedScreen)?,
+            None => false,
+        };
+
+        if !disconnected {
+            state.disconnected_since = None;
+
+            let connecting = match &screen {
+                Some(screen) => self.is_screen(screen, MinecraftClassType::ConnectScreen)?,
+                None => false,
+            };
+            if !connecting {
+                if let Some(server) = self.jvm.current_server(&minecraft)? {
+                    // Connected, remember the server and start counting attempts again
+                    state.last_server = Some(server);
+                    state.attempts = 0;
+                    state.gave_up = false;
+                }
+            }
+            return Ok(());
+        }
+
+        let (Some(screen), Some(server)) = (screen, state.last_server.clone()) else {
+            // Disconnected from singleplayer or before we saw a server
+            return Ok(());
+        };
+
+        let retries = self.get_retries();
+        if state.attempts >= retries {
+            if !state.gave_up {
+                warn!("Giving up reconnecting after {} attempts", state.attempts);
+                state.gave_up = true;
+            }
+            return Ok(());
+        }
+
+        let delay = self.get_delay();
+        let timer = state.disconnected_since.get_or_insert_with(GameTime::start);
+        if !timer.has_elapsed(delay) {
+            return Ok(());
+        }
+
+        state.attempts += 1;
+        state.disconnected_since = None;
+        info!("Reconnecting, attempt {}/{}", state.attempts, retries);
+        self.reconnect(screen, server)
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[
+            MinecraftClassType::Screen,
+            MinecraftClassType::DisconnectedScreen,
+            MinecraftClassType::ConnectScreen,
+            MinecraftClassType::ServerData,
+            MinecraftClassType::ServerAddress,
+        ]
+    }
+
+    fn get_module_data(&self) -> &ModuleData {
+        &self.module
+    }
+
+    fn get_module_data_mut(&mut self) -> &mut ModuleData {
+        &mut self.module
+    }
+}
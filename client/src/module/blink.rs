@@ -0,0 +1,111 @@
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::mapping::FieldType;
+use crate::module::{KeyboardKey, Module, ModuleCategory, ModuleData, DEFAULT_PRIORITY};
+use crate::packet;
+use log::info;
+
+/// Holds back movement packets while enabled and sends them all at once when disabled,
+/// so other players see the player freeze and then teleport to its new position.
+///
+/// Servers kick players that don't send movement for too long, so it's meant for short bursts
+#[derive(Debug)]
+pub struct BlinkModule {
+    pub module: ModuleData,
+    jvm: &'static dyn JvmAccess,
+}
+
+impl BlinkModule {
+    pub fn new(jvm: &'static dyn JvmAccess) -> Self {
+        Self {
+            jvm,
+            module: ModuleData {
+                id: "Blink",
+                name: "Blink".to_string(),
+                description: "Holds back movement packets until disabled".to_string(),
+                category: ModuleCategory::PLAYER,
+                key_bind: KeyboardKey::KeyNone,
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![],
+            },
+        }
+    }
+
+    // Hooks the Connection wrapped by the player's packet listener
+    fn hook_player_connection(&self) -> anyhow::Result<()> {
+        let player = self.jvm.player()?;
+        let listener = self
+            .jvm
+            .get_field(
+                MinecraftClassType::LocalPlayer,
+                &player,
+                "connection",
+                FieldType::Object(MinecraftClassType::ClientPacketListener, self.jvm.mapping()),
+            )?
+            .l()?;
+        let connection = self
+            .jvm
+            .call_method(
+                MinecraftClassType::ClientPacketListener,
+                &listener,
+                "getConnection",
+                &[],
+            )?
+            .l()?;
+        self.jvm.ensure_packet_hook(&connection)
+    }
+}
+
+impl Module for BlinkModule {
+    fn on_start(&self) -> anyhow::Result<()> {
+        // The connection changes on every join, hook the current one
+        self.hook_player_connection()?;
+        packet::set_holding(true);
+        Ok(())
+    }
+
+    fn on_stop(&self) -> anyhow::Result<()> {
+        let sent = packet::release_held_packets()?;
+        info!("Blink released {} packets", sent);
+        // Chat listeners and brand spoofing keep using the hook
+        packet::uninstall_unused_packet_hook();
+        Ok(())
+    }
+
+    fn on_tick(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // A new world comes with a new connection, hold back its packets too. Replacing the
+    // hook releases the held packets and stops holding, start again
+    fn on_player_changed(&self) -> anyhow::Result<()> {
+        self.hook_player_connection()?;
+        packet::set_holding(true);
+        Ok(())
+    }
+
+    fn requires_player(&self) -> bool {
+        true
+    }
+
+    fn get_status(&self) -> Option<String> {
+        Some(format!("{} packets held", packet::held_packet_count()))
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[
+            MinecraftClassType::Connection,
+            MinecraftClassType::ServerboundMovePlayerPacket,
+        ]
+    }
+
+    fn get_module_data(&self) -> &ModuleData {
+        &self.module
+    }
+
+    fn get_module_data_mut(&mut self) -> &mut ModuleData {
+        &mut self.module
+    }
+}
@@ -0,0 +1,97 @@
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::module::{
+    KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting, DEFAULT_PRIORITY,
+};
+use crate::packet;
+
+const DEFAULT_BRAND: &str = "vanilla";
+
+/// Replaces the client brand sent to servers when logging in, so modded clients can
+/// look like vanilla.
+///
+/// The brand is sent as soon as the login finishes, the connection is hooked while it's
+/// still pending so it must be enabled before joining. Joining very fast servers can still
+/// race the first tick and send the real brand
+#[derive(Debug)]
+pub struct BrandSpoofModule {
+    pub module: ModuleData,
+    jvm: &'static dyn JvmAccess,
+}
+
+impl BrandSpoofModule {
+    pub fn new(jvm: &'static dyn JvmAccess) -> Self {
+        Self {
+            jvm,
+            module: ModuleData {
+                id: "BrandSpoof",
+                name: "BrandSpoof".to_string(),
+                description: "Sends another client brand to servers".to_string(),
+                category: ModuleCategory::MISC,
+                key_bind: KeyboardKey::KeyNone,
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![ModuleSetting::Text {
+                    name: "Brand".to_string(),
+                    value: DEFAULT_BRAND.to_string(),
+                }],
+            },
+        }
+    }
+
+    pub fn get_brand(&self) -> String {
+        self.module
+            .get_setting("Brand")
+            .and_then(|s| s.get_text_value())
+            .map(str::trim)
+            .filter(|brand| !brand.is_empty())
+            .unwrap_or(DEFAULT_BRAND)
+            .to_string()
+    }
+}
+
+impl Module for BrandSpoofModule {
+    fn on_start(&self) -> anyhow::Result<()> {
+        packet::set_spoofed_brand(Some(self.get_brand()));
+        Ok(())
+    }
+
+    fn on_stop(&self) -> anyhow::Result<()> {
+        packet::set_spoofed_brand(None);
+        packet::uninstall_unused_packet_hook();
+        Ok(())
+    }
+
+    fn on_tick(&self) -> anyhow::Result<()> {
+        // Every new connection has to be hooked before its login finishes
+        let minecraft = self.jvm.minecraft()?;
+        if let Some(connection) = self.jvm.pending_connection(&minecraft)? {
+            self.jvm.ensure_packet_hook(connection.as_obj())?;
+        }
+        Ok(())
+    }
+
+    fn on_setting_changed(&mut self, _setting_name: &str) -> anyhow::Result<()> {
+        if self.module.enabled {
+            packet::set_spoofed_brand(Some(self.get_brand()));
+        }
+        Ok(())
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[
+            MinecraftClassType::Connection,
+            MinecraftClassType::ServerboundCustomPayloadPacket,
+            MinecraftClassType::BrandPayload,
+        ]
+    }
+
+    fn get_module_data(&self) -> &ModuleData {
+        &self.module
+    }
+
+    fn get_module_data_mut(&mut self) -> &mut ModuleData {
+        &mut self.module
+    }
+}
@@ -0,0 +1,114 @@
+use crate::module::Module;
+use anyhow::Context;
+
+/// Toggles a module on, ticks it and toggles it off like the client would, without
+/// rendering. Modules built on a `FakeJvm` can be checked this way without a game,
+/// every JNI access they make is resolved against the real mapping
+pub fn dry_run(module: &dyn Module, ticks: usize) -> anyhow::Result<()> {
+    module.on_start().context("on_start failed")?;
+
+    for tick in 0..ticks {
+        if let Err(e) = module.on_tick() {
+            // The client stops a module that failed to tick, stopping must work then too
+            module
+                .on_stop()
+                .context("on_stop failed after a tick error")?;
+            return Err(e).with_context(|| format!("on_tick {} failed", tick));
+        }
+    }
+
+    module.on_stop().context("on_stop failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::class_type::MinecraftClassType;
+    use crate::mapping::jvm_access::fake::FakeJvm;
+    use crate::mapping::jvm_access::JvmAccess;
+    use crate::mapping::FieldType;
+    use crate::module::ModuleData;
+    use jni::objects::{JObject, JValue};
+
+    // Minimal module making the same kind of accesses as Fly
+    #[derive(Debug)]
+    struct ProbeModule {
+        jvm: FakeJvm,
+        tick_method: &'static str,
+    }
+
+    impl ProbeModule {
+        fn new(tick_method: &'static str) -> Self {
+            Self {
+                jvm: FakeJvm::new(),
+                tick_method,
+            }
+        }
+
+        fn set_flying(&self, value: bool) -> anyhow::Result<()> {
+            self.jvm.set_field(
+                MinecraftClassType::Abilities,
+                &JObject::null(),
+                "flying",
+                FieldType::Boolean,
+                JValue::Bool(value as u8),
+            )
+        }
+    }
+
+    impl Module for ProbeModule {
+        fn on_start(&self) -> anyhow::Result<()> {
+            self.set_flying(true)
+        }
+
+        fn on_stop(&self) -> anyhow::Result<()> {
+            self.set_flying(false)
+        }
+
+        fn on_tick(&self) -> anyhow::Result<()> {
+            self.jvm.call_void_method(
+                MinecraftClassType::Abilities,
+                &JObject::null(),
+                self.tick_method,
+                &[JValue::Float(0.05)],
+            )
+        }
+
+        fn get_module_data(&self) -> &ModuleData {
+            unreachable!("Not used by the dry run")
+        }
+
+        fn get_module_data_mut(&mut self) -> &mut ModuleData {
+            unreachable!("Not used by the dry run")
+        }
+    }
+
+    #[test]
+    fn test_dry_run_runs_the_whole_lifecycle() {
+        let module = ProbeModule::new("setFlyingSpeed");
+
+        dry_run(&module, 2).unwrap();
+        assert_eq!(
+            module.jvm.calls(),
+            vec![
+                "Abilities.flying = Bool(1)",
+                "Abilities.setFlyingSpeed([Float(0.05)])",
+                "Abilities.setFlyingSpeed([Float(0.05)])",
+                "Abilities.flying = Bool(0)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dry_run_reports_unmapped_access() {
+        let module = ProbeModule::new("setFlyingSpeedTypo");
+
+        let error = dry_run(&module, 2).unwrap_err();
+        assert_eq!(error.to_string(), "on_tick 0 failed");
+        // Stopped like the client would after a tick error
+        assert_eq!(
+            module.jvm.calls().last().unwrap(),
+            "Abilities.flying = Bool(0)"
+        );
+    }
+}
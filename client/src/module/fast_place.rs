@@ -0,0 +1,241 @@
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::mapping::FieldType;
+use crate::module::{
+    KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting, DEFAULT_PRIORITY,
+};
+use jni::objects::{JObject, JValue};
+
+// Ticks vanilla waits after using an item before the next use, and after breaking a
+// block before hitting the next one
+const VANILLA_PLACE_DELAY: i32 = 4;
+const VANILLA_BREAK_DELAY: i32 = 5;
+
+/// Shortens the delay between placing blocks and between breaking them by lowering the
+/// game's `rightClickDelay` and `destroyDelay` every tick while looking at a block.
+/// An Intensity of 1 removes the delays, 0 keeps the vanilla ones
+#[derive(Debug)]
+pub struct FastPlaceModule {
+    pub module: ModuleData,
+    jvm: &'static dyn JvmAccess,
+}
+
+impl FastPlaceModule {
+    pub fn new(jvm: &'static dyn JvmAccess) -> Self {
+        Self {
+            jvm,
+            module: ModuleData {
+                id: "FastPlace",
+                name: "FastPlace".to_string(),
+                description: "Removes the delay between placing and breaking blocks".to_string(),
+                category: ModuleCategory::WORLD,
+                key_bind: KeyboardKey::KeyNone,
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![
+                    ModuleSetting::Slider {
+                        name: "Intensity".to_string(),
+                        value: 1.0,
+                        min: 0.0,
+                        max: 1.0,
+                        step: Some(0.05),
+                    },
+                    ModuleSetting::Toggle {
+                        name: "Place".to_string(),
+                        value: true,
+                    },
+                    ModuleSetting::Toggle {
+                        name: "Break".to_string(),
+                        value: true,
+                    },
+                ],
+            },
+        }
+    }
+
+    pub fn get_intensity(&self) -> f32 {
+        self.module
+            .get_setting("Intensity")
+            .and_then(|s| s.get_slider_value())
+            .unwrap_or(1.0)
+    }
+
+    fn get_toggle(&self, name: &str) -> bool {
+        self.module
+            .get_setting(name)
+            .and_then(|s| s.get_toggle_value())
+            .unwrap_or(true)
+    }
+}
+
+// Longest delay allowed at the given intensity
+fn capped_delay(vanilla_delay: i32, intensity: f32) -> i32 {
+    (vanilla_delay as f32 * (1.0 - intensity.clamp(0.0, 1.0))).round() as i32
+}
+
+// Lowers the place delay of `minecraft` and the break delay of `game_mode` to their caps,
+// a None cap leaves the delay alone. Delays already shorter are kept
+fn apply_delays(
+    jvm: &dyn JvmAccess,
+    minecraft: &JObject,
+    game_mode: Option<&JObject>,
+    place_cap: Option<i32>,
+    break_cap: Option<i32>,
+) -> anyhow::Result<()> {
+    let mut delays = vec![];
+    if let Some(cap) = place_cap {
+        delays.push((
+            MinecraftClassType::Minecraft,
+            minecraft,
+            "rightClickDelay",
+            cap,
+        ));
+    }
+    if let (Some(cap), Some(game_mode)) = (break_cap, game_mode) {
+        delays.push((
+            MinecraftClassType::MultiPlayerGameMode,
+            game_mode,
+            "destroyDelay",
+            cap,
+        ));
+    }
+
+    for (class_type, instance, field, cap) in delays {
+        let delay = jvm
+            .get_field(class_type, instance, field, FieldType::Int)?
+            .i()?;
+        if delay > cap {
+            jvm.set_field(
+                class_type,
+                instance,
+                field,
+                FieldType::Int,
+                JValue::Int(cap),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+impl Module for FastPlaceModule {
+    fn on_start(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // The game counts the delays down by itself, it's back to vanilla from the next use
+    fn on_stop(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_tick(&self) -> anyhow::Result<()> {
+        let minecraft = self.jvm.minecraft()?;
+        // Using items in the air, e.g. eating, keeps its vanilla delay
+        let looking_at_block = self
+            .jvm
+            .hit_result(&minecraft)?
+            .is_some_and(|hit| hit.is_block());
+        if !looking_at_block {
+            return Ok(());
+        }
+
+        let intensity = self.get_intensity();
+        let place_cap = self
+            .get_toggle("Place")
+            .then(|| capped_delay(VANILLA_PLACE_DELAY, intensity));
+        let break_cap = self
+            .get_toggle("Break")
+            .then(|| capped_delay(VANILLA_BREAK_DELAY, intensity));
+
+        let game_mode = self.jvm.game_mode(&minecraft)?;
+        apply_delays(
+            self.jvm,
+            &minecraft,
+            game_mode.as_ref().map(|game_mode| game_mode.as_obj()),
+            place_cap,
+            break_cap,
+        )
+    }
+
+    fn requires_player(&self) -> bool {
+        true
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[
+            MinecraftClassType::MultiPlayerGameMode,
+            MinecraftClassType::BlockHitResult,
+            MinecraftClassType::Vec3i,
+        ]
+    }
+
+    fn get_module_data(&self) -> &ModuleData {
+        &self.module
+    }
+
+    fn get_module_data_mut(&mut self) -> &mut ModuleData {
+        &mut self.module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::jvm_access::fake::FakeJvm;
+
+    #[test]
+    fn test_capped_delay_scales_with_intensity() {
+        assert_eq!(capped_delay(VANILLA_PLACE_DELAY, 0.0), VANILLA_PLACE_DELAY);
+        assert_eq!(capped_delay(VANILLA_PLACE_DELAY, 0.5), 2);
+        assert_eq!(capped_delay(VANILLA_BREAK_DELAY, 1.0), 0);
+        assert_eq!(capped_delay(VANILLA_BREAK_DELAY, 3.0), 0);
+    }
+
+    #[test]
+    fn test_apply_delays_reads_enabled_delays() {
+        let jvm = FakeJvm::new();
+        let game_mode = JObject::null();
+
+        apply_delays(&jvm, &JObject::null(), Some(&game_mode), Some(0), None).unwrap();
+        apply_delays(&jvm, &JObject::null(), Some(&game_mode), None, Some(0)).unwrap();
+        // Without a game mode, e.g. while the world loads, only the place delay is read
+        apply_delays(&jvm, &JObject::null(), None, Some(0), Some(0)).unwrap();
+        // The fake delays are already 0, nothing is written
+        assert_eq!(
+            jvm.calls(),
+            vec![
+                "Minecraft.rightClickDelay",
+                "MultiPlayerGameMode.destroyDelay",
+                "Minecraft.rightClickDelay",
+            ]
+        );
+
+        let jvm = FakeJvm::new();
+        for (class_type, field) in [
+            (MinecraftClassType::Minecraft, "rightClickDelay"),
+            (MinecraftClassType::MultiPlayerGameMode, "destroyDelay"),
+        ] {
+            jvm.set_field(
+                class_type,
+                &JObject::null(),
+                field,
+                FieldType::Int,
+                JValue::Int(4),
+            )
+            .unwrap();
+        }
+        // The place delay is over its cap and lowered, the break delay is already under
+        apply_delays(&jvm, &JObject::null(), Some(&game_mode), Some(1), Some(10)).unwrap();
+        apply_delays(&jvm, &JObject::null(), Some(&game_mode), None, Some(2)).unwrap();
+        assert_eq!(
+            jvm.calls()[2..],
+            [
+                "Minecraft.rightClickDelay",
+                "Minecraft.rightClickDelay = Int(1)",
+                "MultiPlayerGameMode.destroyDelay",
+                "MultiPlayerGameMode.destroyDelay",
+                "MultiPlayerGameMode.destroyDelay = Int(2)",
+            ]
+        );
+    }
+}
@@ -1,27 +1,59 @@
-use crate::mapping::entity::player::LocalPlayer;
-use crate::module::{KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting};
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::entity::player::{Abilities, LocalPlayer, MoveInput};
+use crate::mapping::entity::Entity;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::module::{
+    KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting, DEFAULT_PRIORITY,
+};
+use jni::objects::JObject;
+
+// Vanilla flying speed, the Speed setting is a multiplier of it
+const DEFAULT_FLYING_SPEED: f32 = 0.05;
+// Blocks per tick moved in Motion mode at a Speed of 1, close to vanilla creative flight
+const MOTION_SPEED: f64 = 0.5;
+
+/// How the module makes the player fly
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlyMode {
+    /// Creative flight through the player abilities
+    Abilities,
+    /// Sets the velocity every tick from the movement keys, for servers resetting abilities
+    Motion,
+}
 
 #[derive(Debug)]
 pub struct FlyModule {
     pub module: ModuleData,
+    jvm: &'static dyn JvmAccess,
 }
 
 impl FlyModule {
-    pub fn new(player: LocalPlayer) -> Self {
+    pub fn new(jvm: &'static dyn JvmAccess) -> Self {
         Self {
+            jvm,
             module: ModuleData {
+                id: "Fly",
                 name: "Fly".to_string(),
                 description: "Enables flying".to_string(),
                 category: ModuleCategory::MOVEMENT,
                 key_bind: KeyboardKey::KeyF,
-                enabled: true,
-                player,
-                settings: vec![ModuleSetting::Slider {
-                    name: "Speed".to_string(),
-                    value: 1.0,
-                    min: 0.1,
-                    max: 3.0,
-                }],
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![
+                    ModuleSetting::Choice {
+                        name: "Mode".to_string(),
+                        value: 0,
+                        options: vec!["Abilities".to_string(), "Motion".to_string()],
+                    },
+                    ModuleSetting::Slider {
+                        name: "Speed".to_string(),
+                        value: 1.0,
+                        min: 0.1,
+                        max: 3.0,
+                        step: Some(0.1),
+                    },
+                ],
             },
         }
     }
@@ -32,24 +64,129 @@ impl FlyModule {
             .and_then(|s| s.get_slider_value())
             .unwrap_or(1.0)
     }
+
+    pub fn get_mode(&self) -> FlyMode {
+        match self.module.get_setting("Mode") {
+            Some(ModuleSetting::Choice { value: 1, .. }) => FlyMode::Motion,
+            _ => FlyMode::Abilities,
+        }
+    }
+
+    fn apply(&self, enabled: bool) -> anyhow::Result<()> {
+        let player = self.jvm.player()?;
+        let abilities = self
+            .jvm
+            .call_method(MinecraftClassType::Player, &player, "getAbilities", &[])?
+            .l()?;
+        apply_flying(self.jvm, &abilities, enabled, self.get_speed())
+    }
+}
+
+// Vanilla flying speed scaled by the Speed setting
+fn flying_speed(multiplier: f32) -> f32 {
+    DEFAULT_FLYING_SPEED * multiplier
+}
+
+// Turns flying on with the given speed multiplier, or off restoring the vanilla speed
+fn apply_flying(
+    jvm: &dyn JvmAccess,
+    abilities: &JObject,
+    enabled: bool,
+    speed: f32,
+) -> anyhow::Result<()> {
+    Abilities::fly(jvm, abilities, enabled)?;
+    let speed = if enabled {
+        flying_speed(speed)
+    } else {
+        DEFAULT_FLYING_SPEED
+    };
+    Abilities::set_flying_speed(jvm, abilities, speed)
+}
+
+// Velocity in blocks per tick moving towards the held keys relative to the player's
+// rotation `y_rot`, like vanilla's moveRelative. Jump goes up and shift down
+fn motion_velocity(input: &MoveInput, y_rot: f32, speed: f64) -> (f64, f64, f64) {
+    // The keys rather than the impulses, which vanilla slows down while sneaking to descend
+    let forward = input.forward_axis() as f64;
+    let strafe = input.strafe_axis() as f64;
+    let vertical = input.vertical_axis() as f64;
+
+    // Diagonals are as fast as straight lines
+    let length = (forward * forward + strafe * strafe).sqrt();
+    let (forward, strafe) = if length > 1.0 {
+        (forward / length, strafe / length)
+    } else {
+        (forward, strafe)
+    };
+
+    let (sin, cos) = (y_rot as f64).to_radians().sin_cos();
+    (
+        (strafe * cos - forward * sin) * speed,
+        vertical * speed,
+        (forward * cos + strafe * sin) * speed,
+    )
 }
 
 impl Module for FlyModule {
     fn on_start(&self) -> anyhow::Result<()> {
-        // Enables flying
-        self.module.player.abilities.fly(true)
+        match self.get_mode() {
+            FlyMode::Abilities => self.apply(true),
+            // Driven by on_tick
+            FlyMode::Motion => Ok(()),
+        }
     }
 
     fn on_stop(&self) -> anyhow::Result<()> {
-        // Disables flying
-        self.module.player.abilities.fly(false)
+        // The abilities went away with the world, the next player starts without flight
+        if self.jvm.player().is_err() {
+            return Ok(());
+        }
+        match self.get_mode() {
+            FlyMode::Abilities => self.apply(false),
+            FlyMode::Motion => Ok(()),
+        }
     }
 
     fn on_tick(&self) -> anyhow::Result<()> {
-        // No operation
+        if self.get_mode() != FlyMode::Motion {
+            return Ok(());
+        }
+
+        let player = self.jvm.player()?;
+        let input = LocalPlayer::get_input(self.jvm, &player)?;
+        let y_rot = Entity::get_y_rot(self.jvm, &player)?;
+        let speed = MOTION_SPEED * self.get_speed() as f64;
+        Entity::set_motion(self.jvm, &player, motion_velocity(&input, y_rot, speed))
+    }
+
+    fn on_setting_changed(&mut self, setting_name: &str) -> anyhow::Result<()> {
+        if !self.module.enabled {
+            return Ok(());
+        }
+
+        match setting_name {
+            // Apply the new speed right away instead of on the next start
+            "Speed" if self.get_mode() == FlyMode::Abilities => self.apply(true)?,
+            // Switch the abilities on or off, Motion mode doesn't use them
+            "Mode" => self.apply(self.get_mode() == FlyMode::Abilities)?,
+            _ => {}
+        }
+
         Ok(())
     }
 
+    fn on_player_changed(&self) -> anyhow::Result<()> {
+        self.on_start()
+    }
+
+    fn requires_player(&self) -> bool {
+        true
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[MinecraftClassType::Abilities]
+    }
+
     fn get_module_data(&self) -> &ModuleData {
         &self.module
     }
@@ -58,3 +195,107 @@ impl Module for FlyModule {
         &mut self.module
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::jvm_access::fake::FakeJvm;
+
+    #[test]
+    fn test_flying_speed_scales_vanilla_speed() {
+        assert_eq!(flying_speed(1.0), DEFAULT_FLYING_SPEED);
+        assert!((flying_speed(2.5) - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_motion_velocity_follows_rotation() {
+        let forward = MoveInput {
+            forward: true,
+            ..MoveInput::default()
+        };
+
+        // Facing south, forward is +Z
+        let (x, y, z) = motion_velocity(&forward, 0.0, 0.5);
+        assert!(x.abs() < 1e-9 && y == 0.0 && (z - 0.5).abs() < 1e-9);
+
+        // Facing west, forward is -X
+        let (x, _, z) = motion_velocity(&forward, 90.0, 0.5);
+        assert!((x + 0.5).abs() < 1e-9 && z.abs() < 1e-9);
+
+        // Left of south is east, +X
+        let left = MoveInput {
+            left: true,
+            ..MoveInput::default()
+        };
+        let (x, _, z) = motion_velocity(&left, 0.0, 0.5);
+        assert!((x - 0.5).abs() < 1e-9 && z.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_motion_velocity_diagonal_and_vertical() {
+        let input = MoveInput {
+            forward: true,
+            right: true,
+            jump: true,
+            ..MoveInput::default()
+        };
+
+        let (x, y, z) = motion_velocity(&input, 0.0, 1.0);
+        assert!(((x * x + z * z).sqrt() - 1.0).abs() < 1e-9);
+        assert_eq!(y, 1.0);
+
+        // Opposite keys cancel out, no key hovers in place
+        let opposite = MoveInput {
+            forward: true,
+            backward: true,
+            jump: true,
+            shift: true,
+            ..MoveInput::default()
+        };
+        assert_eq!(motion_velocity(&opposite, 0.0, 1.0), (0.0, 0.0, 0.0));
+        assert_eq!(
+            motion_velocity(&MoveInput::default(), 45.0, 1.0),
+            (0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_apply_flying() {
+        let jvm = FakeJvm::new();
+
+        apply_flying(&jvm, &JObject::null(), true, 2.0).unwrap();
+        apply_flying(&jvm, &JObject::null(), false, 2.0).unwrap();
+        assert_eq!(
+            jvm.calls(),
+            vec![
+                "Abilities.flying",
+                "Abilities.flying = Bool(1)",
+                "Abilities.mayfly",
+                "Abilities.mayfly = Bool(1)",
+                "Abilities.setFlyingSpeed([Float(0.1)])",
+                "Abilities.flying",
+                "Abilities.flying = Bool(0)",
+                "Abilities.mayfly",
+                "Abilities.mayfly = Bool(0)",
+                "Abilities.setFlyingSpeed([Float(0.05)])",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_flying_skips_unchanged_fields() {
+        let jvm = FakeJvm::new();
+
+        apply_flying(&jvm, &JObject::null(), true, 1.0).unwrap();
+        apply_flying(&jvm, &JObject::null(), true, 1.0).unwrap();
+        let writes: Vec<_> = jvm
+            .calls()
+            .into_iter()
+            .filter(|call| call.contains(" = "))
+            .collect();
+        assert_eq!(
+            writes,
+            vec!["Abilities.flying = Bool(1)", "Abilities.mayfly = Bool(1)"]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use crate::mapping::class_type::MinecraftClassType;
+use crate::module::{
+    KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting, DEFAULT_PRIORITY,
+};
+use crate::render::{to_argb, RenderContext};
+
+// Distance of the list from the screen border
+const MARGIN: i32 = 2;
+
+#[derive(Debug)]
+pub struct ModuleListHud {
+    pub module: ModuleData,
+}
+
+impl ModuleListHud {
+    pub fn new() -> Self {
+        Self {
+            module: ModuleData {
+                id: "ModuleList",
+                name: "ModuleList".to_string(),
+                description: "Shows the enabled modules on screen".to_string(),
+                category: ModuleCategory::RENDER,
+                key_bind: KeyboardKey::KeyNone,
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![
+                    ModuleSetting::Color {
+                        name: "Color".to_string(),
+                        value: [1.0, 1.0, 1.0, 1.0],
+                    },
+                    ModuleSetting::Choice {
+                        name: "Position".to_string(),
+                        value: 1,
+                        options: vec![
+                            "Top Left".to_string(),
+                            "Top Right".to_string(),
+                            "Bottom Left".to_string(),
+                            "Bottom Right".to_string(),
+                        ],
+                    },
+                    ModuleSetting::Toggle {
+                        name: "Shadow".to_string(),
+                        value: true,
+                    },
+                ],
+            },
+        }
+    }
+
+    pub fn get_color(&self) -> u32 {
+        match self.module.get_setting("Color") {
+            Some(ModuleSetting::Color { value, .. }) => to_argb(*value),
+            _ => 0xFFFFFFFF,
+        }
+    }
+
+    pub fn get_position(&self) -> usize {
+        match self.module.get_setting("Position") {
+            Some(ModuleSetting::Choice { value, .. }) => *value,
+            _ => 1,
+        }
+    }
+
+    pub fn get_shadow(&self) -> bool {
+        self.module
+            .get_setting("Shadow")
+            .and_then(|s| s.get_toggle_value())
+            .unwrap_or(true)
+    }
+}
+
+impl Module for ModuleListHud {
+    fn on_start(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_stop(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_tick(&self) -> anyhow::Result<()> {
+        // No operation
+        Ok(())
+    }
+
+    fn on_render(&self, context: &RenderContext) -> anyhow::Result<()> {
+        let mut entries = Vec::new();
+        for name in context.enabled_modules() {
+            if *name == self.module.name {
+                continue;
+            }
+            entries.push((context.text_width(name)?, name));
+        }
+
+        // Longest names first, so the list looks like a staircase
+        entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+        let position = self.get_position();
+        let right = position == 1 || position == 3;
+        let bottom = position == 2 || position == 3;
+
+        let line_height = context.line_height()? + 1;
+        let screen_width = context.gui_width()?;
+        let screen_height = context.gui_height()?;
+        let color = self.get_color();
+        let shadow = self.get_shadow();
+
+        for (index, (width, name)) in entries.iter().enumerate() {
+            let x = if right {
+                screen_width - width - MARGIN
+            } else {
+                MARGIN
+            };
+            let y = if bottom {
+                screen_height - MARGIN - line_height * (index as i32 + 1)
+            } else {
+                MARGIN + line_height * index as i32
+            };
+
+            context.draw_string(name, x, y, color, shadow)?;
+        }
+
+        Ok(())
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[MinecraftClassType::GuiGraphics, MinecraftClassType::Font]
+    }
+
+    fn get_module_data(&self) -> &ModuleData {
+        &self.module
+    }
+
+    fn get_module_data_mut(&mut self) -> &mut ModuleData {
+        &mut self.module
+    }
+}
@@ -1,23 +1,58 @@
-use crate::mapping::entity::player::LocalPlayer;
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::jvm_access::JvmAccess;
+use crate::notification;
+use crate::render::RenderContext;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
 
+pub mod auto_clicker;
+pub mod auto_reconnect;
+pub mod blink;
+pub mod brand_spoof;
+#[cfg(test)]
+mod dry_run;
+pub mod fast_place;
 pub mod fly;
+pub mod hud;
+pub mod reach;
 
 pub type ModuleType = dyn Module + Send + Sync;
 
+/// Priority of modules that don't depend on the order they tick in
+pub const DEFAULT_PRIORITY: i32 = 0;
+
+/// Longest value of a `ModuleSetting::Text`, in characters
+pub const MAX_TEXT_LENGTH: usize = 256;
+
 #[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ModuleCategory {
     COMBAT,
     MOVEMENT,
     RENDER,
     PLAYER,
     WORLD,
+    /// Category created by the user to group modules, see `ModuleCategory::from_name`
+    Custom(String),
+    // Categories added by a newer version fall back here when loading a config
+    #[serde(other)]
     MISC,
 }
 
 impl ModuleCategory {
-    #[allow(dead_code)]
+    /// Categories the client comes with, in their default order
+    pub const BUILT_IN: [ModuleCategory; 6] = [
+        ModuleCategory::COMBAT,
+        ModuleCategory::MOVEMENT,
+        ModuleCategory::RENDER,
+        ModuleCategory::PLAYER,
+        ModuleCategory::WORLD,
+        ModuleCategory::MISC,
+    ];
+
     pub fn display_name(&self) -> &str {
         match self {
             ModuleCategory::COMBAT => "Combat",
@@ -26,12 +61,55 @@ impl ModuleCategory {
             ModuleCategory::PLAYER => "Player",
             ModuleCategory::WORLD => "World",
             ModuleCategory::MISC => "Misc",
+            ModuleCategory::Custom(name) => name,
         }
     }
+
+    /// Category typed by the user: a built-in one if it has that display name, ignoring
+    /// case, otherwise a custom one. An empty name is Misc
+    pub fn from_name(name: &str) -> ModuleCategory {
+        let name = ModuleSetting::fit_text(name.trim());
+        if name.is_empty() {
+            return ModuleCategory::MISC;
+        }
+
+        ModuleCategory::BUILT_IN
+            .into_iter()
+            .find(|category| category.display_name().eq_ignore_ascii_case(&name))
+            .unwrap_or(ModuleCategory::Custom(name))
+    }
+}
+
+/// Sorts categories in display order: first the ones in `order`, the order set by the
+/// user, then the built-in ones in their default order, then the custom ones by name.
+/// The sort is stable, modules sorted by category keep their registration order
+pub fn sort_by_category<T>(
+    items: &mut [T],
+    order: &[ModuleCategory],
+    category: impl Fn(&T) -> &ModuleCategory,
+) {
+    items.sort_by_key(|item| {
+        let category = category(item);
+        let position = |categories: &[ModuleCategory]| {
+            categories
+                .iter()
+                .position(|other| other == category)
+                .unwrap_or(categories.len())
+        };
+        (
+            position(order),
+            position(&ModuleCategory::BUILT_IN),
+            category.display_name().to_lowercase(),
+        )
+    });
 }
 
 #[derive(Debug, Clone)]
 pub struct ModuleData {
+    // Key of the module in configs and the toggle command, never changed once released.
+    // Modules from before ids existed use the name they had then
+    pub id: &'static str,
+    // Shown to the user, free to change
     pub name: String,
     #[allow(dead_code)]
     pub description: String,
@@ -39,12 +117,15 @@ pub struct ModuleData {
     pub category: ModuleCategory,
     pub key_bind: KeyboardKey,
     pub enabled: bool,
-    pub player: LocalPlayer,
+    // Modules with a higher priority tick and render first, ties keep registration order
+    pub priority: i32,
+    // Shows a notification whenever the module is enabled or disabled
+    pub notify_on_toggle: bool,
     pub settings: Vec<ModuleSetting>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ModuleSetting {
     Toggle {
         name: String,
@@ -55,6 +136,15 @@ pub enum ModuleSetting {
         value: f32,
         min: f32,
         max: f32,
+        // Values snap to multiples of the step, if any
+        #[serde(default)]
+        step: Option<f32>,
+    },
+    IntSlider {
+        name: String,
+        value: i32,
+        min: i32,
+        max: i32,
     },
     Choice {
         name: String,
@@ -65,6 +155,11 @@ pub enum ModuleSetting {
         name: String,
         value: [f32; 4],
     },
+    // Single line of free text, at most MAX_TEXT_LENGTH characters
+    Text {
+        name: String,
+        value: String,
+    },
 }
 
 impl ModuleSetting {
@@ -72,8 +167,10 @@ impl ModuleSetting {
         match self {
             ModuleSetting::Toggle { name, .. } => name,
             ModuleSetting::Slider { name, .. } => name,
+            ModuleSetting::IntSlider { name, .. } => name,
             ModuleSetting::Choice { name, .. } => name,
             ModuleSetting::Color { name, .. } => name,
+            ModuleSetting::Text { name, .. } => name,
         }
     }
 
@@ -90,6 +187,22 @@ impl ModuleSetting {
         }
     }
 
+    pub fn get_int_value(&self) -> Option<i32> {
+        match self {
+            ModuleSetting::IntSlider { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn set_int_value(&mut self, new_value: i32) {
+        if let ModuleSetting::IntSlider {
+            value, min, max, ..
+        } = self
+        {
+            *value = new_value.clamp(*min, *max);
+        }
+    }
+
     pub fn get_toggle_value(&self) -> Option<bool> {
         match self {
             ModuleSetting::Toggle { value, .. } => Some(*value),
@@ -102,6 +215,35 @@ impl ModuleSetting {
             *value = new_value;
         }
     }
+
+    pub fn get_color_value(&self) -> Option<[f32; 4]> {
+        match self {
+            ModuleSetting::Color { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_text_value(&self) -> Option<&str> {
+        match self {
+            ModuleSetting::Text { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn set_text_value(&mut self, new_value: &str) {
+        if let ModuleSetting::Text { value, .. } = self {
+            *value = Self::fit_text(new_value);
+        }
+    }
+
+    /// `text` as a valid Text value: line breaks become spaces and it's cut to
+    /// MAX_TEXT_LENGTH characters
+    pub fn fit_text(text: &str) -> String {
+        text.chars()
+            .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+            .take(MAX_TEXT_LENGTH)
+            .collect()
+    }
 }
 
 impl ModuleData {
@@ -121,15 +263,72 @@ impl ModuleData {
 pub trait Module: Debug + Send + Sync {
     fn on_start(&self) -> anyhow::Result<()>;
     fn on_stop(&self) -> anyhow::Result<()>;
+    // Called on the tick thread, use DarkClient::run_on_render_thread for rendering state
     fn on_tick(&self) -> anyhow::Result<()>;
 
+    // Called every frame on the render thread while enabled
+    fn on_render(&self, _context: &RenderContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // Called by the GUI after the value of a setting has been edited, while the module
+    // is locked, so it must not lock itself again through DarkClient
+    fn on_setting_changed(&mut self, _setting_name: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // Short live status shown next to the module in the GUI, e.g. a counter
+    fn get_status(&self) -> Option<String> {
+        None
+    }
+
+    // Whether the module acts on the player, it can't be enabled without a world and
+    // doesn't tick or render while there is none
+    fn requires_player(&self) -> bool {
+        false
+    }
+
+    // Called before the tick of an enabled module when the game replaced the player, after
+    // joining a world, respawning or changing dimension. What on_start set on the old
+    // player is lost and has to be applied again
+    fn on_player_changed(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // Game classes used besides MinecraftClassType::CORE, checked at registration so a
+    // module the mapping can't support is kept disabled
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[]
+    }
+
+    // Message of the notification shown when the user toggles the module with
+    // notify_on_toggle set, modules can override it to tell more
+    fn get_toggle_message(&self, enabled: bool) -> String {
+        format!(
+            "{} {}",
+            self.get_module_data().name,
+            if enabled { "enabled" } else { "disabled" }
+        )
+    }
+
     fn get_module_data(&self) -> &ModuleData;
     fn get_module_data_mut(&mut self) -> &mut ModuleData;
 }
 
+/// Sets the state of a module toggled by the user, notifying them if they opted in and
+/// the state changed. States restored from the config or forced off, e.g. after an error,
+/// use `ModuleData::set_enabled` and stay silent
+pub fn set_enabled_by_user(module: &mut ModuleType, enabled: bool) {
+    let module_data = module.get_module_data();
+    if module_data.notify_on_toggle && module_data.enabled != enabled {
+        notification::notify_info(module.get_toggle_message(enabled));
+    }
+    module.get_module_data_mut().set_enabled(enabled);
+}
+
 // lwjgl key mapping
 #[repr(i32)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[allow(dead_code)]
 pub enum KeyboardKey {
     KeyNone = -1,
@@ -240,3 +439,572 @@ pub enum KeyboardKey {
     KeyInsert = 260,
     KeyDelete = 261,
 }
+
+impl KeyboardKey {
+    pub const ALL: [KeyboardKey; 107] = [
+        KeyboardKey::KeyNone,
+        KeyboardKey::KeyEscape,
+        KeyboardKey::Key1,
+        KeyboardKey::Key2,
+        KeyboardKey::Key3,
+        KeyboardKey::Key4,
+        KeyboardKey::Key5,
+        KeyboardKey::Key6,
+        KeyboardKey::Key7,
+        KeyboardKey::Key8,
+        KeyboardKey::Key9,
+        KeyboardKey::Key0,
+        KeyboardKey::KeyMinus,
+        KeyboardKey::KeyEquals,
+        KeyboardKey::KeyBack,
+        KeyboardKey::KeyTab,
+        KeyboardKey::KeyQ,
+        KeyboardKey::KeyW,
+        KeyboardKey::KeyE,
+        KeyboardKey::KeyR,
+        KeyboardKey::KeyT,
+        KeyboardKey::KeyY,
+        KeyboardKey::KeyU,
+        KeyboardKey::KeyI,
+        KeyboardKey::KeyO,
+        KeyboardKey::KeyP,
+        KeyboardKey::KeyLBracket,
+        KeyboardKey::KeyRBracket,
+        KeyboardKey::KeyReturn,
+        KeyboardKey::KeyLControl,
+        KeyboardKey::KeyA,
+        KeyboardKey::KeyS,
+        KeyboardKey::KeyD,
+        KeyboardKey::KeyF,
+        KeyboardKey::KeyG,
+        KeyboardKey::KeyH,
+        KeyboardKey::KeyJ,
+        KeyboardKey::KeyK,
+        KeyboardKey::KeyL,
+        KeyboardKey::KeySemicolon,
+        KeyboardKey::KeyApostrophe,
+        KeyboardKey::KeyGrave,
+        KeyboardKey::KeyLShift,
+        KeyboardKey::KeyBackSlash,
+        KeyboardKey::KeyZ,
+        KeyboardKey::KeyX,
+        KeyboardKey::KeyC,
+        KeyboardKey::KeyV,
+        KeyboardKey::KeyB,
+        KeyboardKey::KeyN,
+        KeyboardKey::KeyM,
+        KeyboardKey::KeyComma,
+        KeyboardKey::KeyPeriod,
+        KeyboardKey::KeySlash,
+        KeyboardKey::KeyRShift,
+        KeyboardKey::KeyMultiply,
+        KeyboardKey::KeyLAlt,
+        KeyboardKey::KeySpace,
+        KeyboardKey::KeyCapital,
+        KeyboardKey::KeyF1,
+        KeyboardKey::KeyF2,
+        KeyboardKey::KeyF3,
+        KeyboardKey::KeyF4,
+        KeyboardKey::KeyF5,
+        KeyboardKey::KeyF6,
+        KeyboardKey::KeyF7,
+        KeyboardKey::KeyF8,
+        KeyboardKey::KeyF9,
+        KeyboardKey::KeyF10,
+        KeyboardKey::KeyNumLock,
+        KeyboardKey::KeyScroll,
+        KeyboardKey::KeyNumpad7,
+        KeyboardKey::KeyNumpad8,
+        KeyboardKey::KeyNumpad9,
+        KeyboardKey::KeySubtract,
+        KeyboardKey::KeyNumpad4,
+        KeyboardKey::KeyNumpad5,
+        KeyboardKey::KeyNumpad6,
+        KeyboardKey::KeyAdd,
+        KeyboardKey::KeyNumpad1,
+        KeyboardKey::KeyNumpad2,
+        KeyboardKey::KeyNumpad3,
+        KeyboardKey::KeyNumpad0,
+        KeyboardKey::KeyF11,
+        KeyboardKey::KeyF12,
+        KeyboardKey::KeyF13,
+        KeyboardKey::KeyF14,
+        KeyboardKey::KeyF15,
+        KeyboardKey::KeyF16,
+        KeyboardKey::KeyF17,
+        KeyboardKey::KeyF18,
+        KeyboardKey::KeyF19,
+        KeyboardKey::KeyNumpadEquals,
+        KeyboardKey::KeyNumpadEnter,
+        KeyboardKey::KeyRControl,
+        KeyboardKey::KeyNumpadComma,
+        KeyboardKey::KeyDivide,
+        KeyboardKey::KeyPause,
+        KeyboardKey::KeyHome,
+        KeyboardKey::KeyUp,
+        KeyboardKey::KeyLeft,
+        KeyboardKey::KeyRight,
+        KeyboardKey::KeyEnd,
+        KeyboardKey::KeyDown,
+        KeyboardKey::KeyNext,
+        KeyboardKey::KeyInsert,
+        KeyboardKey::KeyDelete,
+    ];
+
+    /// Human readable name of the key, e.g. "Left Ctrl" for `KeyLControl`
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            KeyboardKey::KeyNone => "None",
+            KeyboardKey::KeyEscape => "Escape",
+            KeyboardKey::Key1 => "1",
+            KeyboardKey::Key2 => "2",
+            KeyboardKey::Key3 => "3",
+            KeyboardKey::Key4 => "4",
+            KeyboardKey::Key5 => "5",
+            KeyboardKey::Key6 => "6",
+            KeyboardKey::Key7 => "7",
+            KeyboardKey::Key8 => "8",
+            KeyboardKey::Key9 => "9",
+            KeyboardKey::Key0 => "0",
+            KeyboardKey::KeyMinus => "-",
+            KeyboardKey::KeyEquals => "=",
+            KeyboardKey::KeyBack => "Backspace",
+            KeyboardKey::KeyTab => "Tab",
+            KeyboardKey::KeyQ => "Q",
+            KeyboardKey::KeyW => "W",
+            KeyboardKey::KeyE => "E",
+            KeyboardKey::KeyR => "R",
+            KeyboardKey::KeyT => "T",
+            KeyboardKey::KeyY => "Y",
+            KeyboardKey::KeyU => "U",
+            KeyboardKey::KeyI => "I",
+            KeyboardKey::KeyO => "O",
+            KeyboardKey::KeyP => "P",
+            KeyboardKey::KeyLBracket => "[",
+            KeyboardKey::KeyRBracket => "]",
+            KeyboardKey::KeyReturn => "Enter",
+            KeyboardKey::KeyLControl => "Left Ctrl",
+            KeyboardKey::KeyA => "A",
+            KeyboardKey::KeyS => "S",
+            KeyboardKey::KeyD => "D",
+            KeyboardKey::KeyF => "F",
+            KeyboardKey::KeyG => "G",
+            KeyboardKey::KeyH => "H",
+            KeyboardKey::KeyJ => "J",
+            KeyboardKey::KeyK => "K",
+            KeyboardKey::KeyL => "L",
+            KeyboardKey::KeySemicolon => ";",
+            KeyboardKey::KeyApostrophe => "'",
+            KeyboardKey::KeyGrave => "`",
+            KeyboardKey::KeyLShift => "Left Shift",
+            KeyboardKey::KeyBackSlash => "\\",
+            KeyboardKey::KeyZ => "Z",
+            KeyboardKey::KeyX => "X",
+            KeyboardKey::KeyC => "C",
+            KeyboardKey::KeyV => "V",
+            KeyboardKey::KeyB => "B",
+            KeyboardKey::KeyN => "N",
+            KeyboardKey::KeyM => "M",
+            KeyboardKey::KeyComma => ",",
+            KeyboardKey::KeyPeriod => ".",
+            KeyboardKey::KeySlash => "/",
+            KeyboardKey::KeyRShift => "Right Shift",
+            KeyboardKey::KeyMultiply => "Numpad *",
+            KeyboardKey::KeyLAlt => "Left Alt",
+            KeyboardKey::KeySpace => "Space",
+            KeyboardKey::KeyCapital => "Caps Lock",
+            KeyboardKey::KeyF1 => "F1",
+            KeyboardKey::KeyF2 => "F2",
+            KeyboardKey::KeyF3 => "F3",
+            KeyboardKey::KeyF4 => "F4",
+            KeyboardKey::KeyF5 => "F5",
+            KeyboardKey::KeyF6 => "F6",
+            KeyboardKey::KeyF7 => "F7",
+            KeyboardKey::KeyF8 => "F8",
+            KeyboardKey::KeyF9 => "F9",
+            KeyboardKey::KeyF10 => "F10",
+            KeyboardKey::KeyNumLock => "Num Lock",
+            KeyboardKey::KeyScroll => "Scroll Lock",
+            KeyboardKey::KeyNumpad7 => "Numpad 7",
+            KeyboardKey::KeyNumpad8 => "Numpad 8",
+            KeyboardKey::KeyNumpad9 => "Numpad 9",
+            KeyboardKey::KeySubtract => "Numpad -",
+            KeyboardKey::KeyNumpad4 => "Numpad 4",
+            KeyboardKey::KeyNumpad5 => "Numpad 5",
+            KeyboardKey::KeyNumpad6 => "Numpad 6",
+            KeyboardKey::KeyAdd => "Numpad +",
+            KeyboardKey::KeyNumpad1 => "Numpad 1",
+            KeyboardKey::KeyNumpad2 => "Numpad 2",
+            KeyboardKey::KeyNumpad3 => "Numpad 3",
+            KeyboardKey::KeyNumpad0 => "Numpad 0",
+            KeyboardKey::KeyF11 => "F11",
+            KeyboardKey::KeyF12 => "F12",
+            KeyboardKey::KeyF13 => "F13",
+            KeyboardKey::KeyF14 => "F14",
+            KeyboardKey::KeyF15 => "F15",
+            KeyboardKey::KeyF16 => "F16",
+            KeyboardKey::KeyF17 => "F17",
+            KeyboardKey::KeyF18 => "F18",
+            KeyboardKey::KeyF19 => "F19",
+            KeyboardKey::KeyNumpadEquals => "Numpad =",
+            KeyboardKey::KeyNumpadEnter => "Numpad Enter",
+            KeyboardKey::KeyRControl => "Right Ctrl",
+            KeyboardKey::KeyNumpadComma => "Numpad ,",
+            KeyboardKey::KeyDivide => "Numpad /",
+            KeyboardKey::KeyPause => "Pause",
+            KeyboardKey::KeyHome => "Home",
+            KeyboardKey::KeyUp => "Up",
+            KeyboardKey::KeyLeft => "Left",
+            KeyboardKey::KeyRight => "Right",
+            KeyboardKey::KeyEnd => "End",
+            KeyboardKey::KeyDown => "Down",
+            KeyboardKey::KeyNext => "Page Down",
+            KeyboardKey::KeyInsert => "Insert",
+            KeyboardKey::KeyDelete => "Delete",
+        }
+    }
+
+    /// Key of a GLFW key code, the reverse of `key as i32`
+    pub fn from_i32(code: i32) -> Option<KeyboardKey> {
+        Some(match code {
+            -1 => KeyboardKey::KeyNone,
+            256 => KeyboardKey::KeyEscape,
+            49 => KeyboardKey::Key1,
+            50 => KeyboardKey::Key2,
+            51 => KeyboardKey::Key3,
+            52 => KeyboardKey::Key4,
+            53 => KeyboardKey::Key5,
+            54 => KeyboardKey::Key6,
+            55 => KeyboardKey::Key7,
+            56 => KeyboardKey::Key8,
+            57 => KeyboardKey::Key9,
+            48 => KeyboardKey::Key0,
+            45 => KeyboardKey::KeyMinus,
+            61 => KeyboardKey::KeyEquals,
+            259 => KeyboardKey::KeyBack,
+            258 => KeyboardKey::KeyTab,
+            81 => KeyboardKey::KeyQ,
+            87 => KeyboardKey::KeyW,
+            69 => KeyboardKey::KeyE,
+            82 => KeyboardKey::KeyR,
+            84 => KeyboardKey::KeyT,
+            89 => KeyboardKey::KeyY,
+            85 => KeyboardKey::KeyU,
+            73 => KeyboardKey::KeyI,
+            79 => KeyboardKey::KeyO,
+            80 => KeyboardKey::KeyP,
+            91 => KeyboardKey::KeyLBracket,
+            93 => KeyboardKey::KeyRBracket,
+            257 => KeyboardKey::KeyReturn,
+            341 => KeyboardKey::KeyLControl,
+            65 => KeyboardKey::KeyA,
+            83 => KeyboardKey::KeyS,
+            68 => KeyboardKey::KeyD,
+            70 => KeyboardKey::KeyF,
+            71 => KeyboardKey::KeyG,
+            72 => KeyboardKey::KeyH,
+            74 => KeyboardKey::KeyJ,
+            75 => KeyboardKey::KeyK,
+            76 => KeyboardKey::KeyL,
+            59 => KeyboardKey::KeySemicolon,
+            39 => KeyboardKey::KeyApostrophe,
+            96 => KeyboardKey::KeyGrave,
+            340 => KeyboardKey::KeyLShift,
+            92 => KeyboardKey::KeyBackSlash,
+            90 => KeyboardKey::KeyZ,
+            88 => KeyboardKey::KeyX,
+            67 => KeyboardKey::KeyC,
+            86 => KeyboardKey::KeyV,
+            66 => KeyboardKey::KeyB,
+            78 => KeyboardKey::KeyN,
+            77 => KeyboardKey::KeyM,
+            44 => KeyboardKey::KeyComma,
+            46 => KeyboardKey::KeyPeriod,
+            47 => KeyboardKey::KeySlash,
+            344 => KeyboardKey::KeyRShift,
+            332 => KeyboardKey::KeyMultiply,
+            342 => KeyboardKey::KeyLAlt,
+            32 => KeyboardKey::KeySpace,
+            280 => KeyboardKey::KeyCapital,
+            290 => KeyboardKey::KeyF1,
+            291 => KeyboardKey::KeyF2,
+            292 => KeyboardKey::KeyF3,
+            293 => KeyboardKey::KeyF4,
+            294 => KeyboardKey::KeyF5,
+            295 => KeyboardKey::KeyF6,
+            296 => KeyboardKey::KeyF7,
+            297 => KeyboardKey::KeyF8,
+            298 => KeyboardKey::KeyF9,
+            299 => KeyboardKey::KeyF10,
+            282 => KeyboardKey::KeyNumLock,
+            281 => KeyboardKey::KeyScroll,
+            327 => KeyboardKey::KeyNumpad7,
+            328 => KeyboardKey::KeyNumpad8,
+            329 => KeyboardKey::KeyNumpad9,
+            333 => KeyboardKey::KeySubtract,
+            324 => KeyboardKey::KeyNumpad4,
+            325 => KeyboardKey::KeyNumpad5,
+            326 => KeyboardKey::KeyNumpad6,
+            334 => KeyboardKey::KeyAdd,
+            321 => KeyboardKey::KeyNumpad1,
+            322 => KeyboardKey::KeyNumpad2,
+            323 => KeyboardKey::KeyNumpad3,
+            320 => KeyboardKey::KeyNumpad0,
+            300 => KeyboardKey::KeyF11,
+            301 => KeyboardKey::KeyF12,
+            302 => KeyboardKey::KeyF13,
+            303 => KeyboardKey::KeyF14,
+            304 => KeyboardKey::KeyF15,
+            305 => KeyboardKey::KeyF16,
+            306 => KeyboardKey::KeyF17,
+            307 => KeyboardKey::KeyF18,
+            308 => KeyboardKey::KeyF19,
+            336 => KeyboardKey::KeyNumpadEquals,
+            335 => KeyboardKey::KeyNumpadEnter,
+            345 => KeyboardKey::KeyRControl,
+            330 => KeyboardKey::KeyNumpadComma,
+            331 => KeyboardKey::KeyDivide,
+            284 => KeyboardKey::KeyPause,
+            268 => KeyboardKey::KeyHome,
+            265 => KeyboardKey::KeyUp,
+            263 => KeyboardKey::KeyLeft,
+            262 => KeyboardKey::KeyRight,
+            269 => KeyboardKey::KeyEnd,
+            264 => KeyboardKey::KeyDown,
+            267 => KeyboardKey::KeyNext,
+            260 => KeyboardKey::KeyInsert,
+            261 => KeyboardKey::KeyDelete,
+            _ => return None,
+        })
+    }
+}
+
+/// Every module the client comes with, in registration order, accessing the game
+/// through `jvm`
+pub fn built_in_modules(jvm: &'static dyn JvmAccess) -> Vec<Arc<Mutex<ModuleType>>> {
+    vec![
+        Arc::new(Mutex::new(fly::FlyModule::new(jvm))),
+        Arc::new(Mutex::new(hud::ModuleListHud::new())),
+        Arc::new(Mutex::new(reach::ReachModule::new(jvm))),
+        Arc::new(Mutex::new(auto_reconnect::AutoReconnectModule::new(jvm))),
+        Arc::new(Mutex::new(blink::BlinkModule::new(jvm))),
+        Arc::new(Mutex::new(brand_spoof::BrandSpoofModule::new(jvm))),
+        Arc::new(Mutex::new(fast_place::FastPlaceModule::new(jvm))),
+        Arc::new(Mutex::new(auto_clicker::AutoClickerModule::new(jvm))),
+    ]
+}
+
+/// Modules bound to the same key as another module, with the names of those other
+/// modules. A key press toggles all of them at once
+pub fn keybind_conflicts<'a>(
+    key_binds: &[(&'a str, KeyboardKey)],
+) -> HashMap<&'a str, Vec<&'a str>> {
+    let mut by_key: HashMap<KeyboardKey, Vec<&str>> = HashMap::new();
+    for (name, key_bind) in key_binds {
+        if *key_bind != KeyboardKey::KeyNone {
+            by_key.entry(*key_bind).or_default().push(name);
+        }
+    }
+
+    let mut conflicts = HashMap::new();
+    for names in by_key.values().filter(|names| names.len() > 1) {
+        for name in names {
+            let others = names
+                .iter()
+                .copied()
+                .filter(|other| other != name)
+                .collect();
+            conflicts.insert(*name, others);
+        }
+    }
+    conflicts
+}
+
+// Stored as the GLFW key code, which stays stable across versions
+impl Serialize for KeyboardKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+// Accepts the key code or a name like "KeyF" or "Left Ctrl", anything else is unbound
+// instead of failing the whole config
+impl<'de> Deserialize<'de> for KeyboardKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum StoredKey {
+            Code(i32),
+            Name(String),
+            Other(serde::de::IgnoredAny),
+        }
+
+        let key = match StoredKey::deserialize(deserializer)? {
+            StoredKey::Code(code) => KeyboardKey::from_i32(code),
+            StoredKey::Name(name) => KeyboardKey::ALL.into_iter().find(|key| {
+                format!("{:?}", key) == name || key.display_name().eq_ignore_ascii_case(&name)
+            }),
+            StoredKey::Other(_) => None,
+        };
+        Ok(key.unwrap_or(KeyboardKey::KeyNone))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::jvm_access::fake::FakeJvm;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_key_display_name() {
+        assert_eq!(KeyboardKey::KeyF.display_name(), "F");
+        assert_eq!(KeyboardKey::KeyLControl.display_name(), "Left Ctrl");
+        assert_eq!(KeyboardKey::KeySpace.display_name(), "Space");
+        assert_eq!(KeyboardKey::KeyF12.display_name(), "F12");
+        assert_eq!(KeyboardKey::KeyNumpad7.display_name(), "Numpad 7");
+        assert_eq!(KeyboardKey::KeyBackSlash.display_name(), "\\");
+    }
+
+    #[test]
+    fn test_key_from_i32_round_trip() {
+        for key in KeyboardKey::ALL {
+            assert_eq!(KeyboardKey::from_i32(key as i32), Some(key));
+        }
+        assert_eq!(KeyboardKey::from_i32(-1), Some(KeyboardKey::KeyNone));
+        assert_eq!(KeyboardKey::from_i32(1000), None);
+    }
+
+    #[test]
+    fn test_key_serde() {
+        assert_eq!(serde_json::to_string(&KeyboardKey::KeyF).unwrap(), "70");
+
+        let parse = |json: &str| serde_json::from_str::<KeyboardKey>(json).unwrap();
+        assert_eq!(parse("70"), KeyboardKey::KeyF);
+        assert_eq!(parse("\"KeyLControl\""), KeyboardKey::KeyLControl);
+        assert_eq!(parse("\"left ctrl\""), KeyboardKey::KeyLControl);
+        assert_eq!(parse("1000"), KeyboardKey::KeyNone);
+        assert_eq!(parse("\"KeyHyper\""), KeyboardKey::KeyNone);
+        assert_eq!(parse("null"), KeyboardKey::KeyNone);
+    }
+
+    #[test]
+    fn test_category_serde_falls_back_to_misc() {
+        let parse = |json: &str| serde_json::from_str::<ModuleCategory>(json).unwrap();
+        assert_eq!(parse("\"RENDER\""), ModuleCategory::RENDER);
+        assert_eq!(parse("\"NETWORK\""), ModuleCategory::MISC);
+
+        let custom = ModuleCategory::Custom("Favorites".to_string());
+        let json = serde_json::to_string(&custom).unwrap();
+        assert_eq!(parse(&json), custom);
+    }
+
+    #[test]
+    fn test_category_from_name() {
+        assert_eq!(ModuleCategory::from_name(" world "), ModuleCategory::WORLD);
+        assert_eq!(ModuleCategory::from_name(""), ModuleCategory::MISC);
+        assert_eq!(
+            ModuleCategory::from_name("Favorites"),
+            ModuleCategory::Custom("Favorites".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sort_by_category() {
+        let favorites = ModuleCategory::Custom("Favorites".to_string());
+        let mut categories = vec![
+            ModuleCategory::MISC,
+            favorites.clone(),
+            ModuleCategory::Custom("Building".to_string()),
+            ModuleCategory::WORLD,
+            ModuleCategory::COMBAT,
+        ];
+
+        sort_by_category(&mut categories, &[], |category| category);
+        assert_eq!(
+            categories[..3],
+            [
+                ModuleCategory::COMBAT,
+                ModuleCategory::WORLD,
+                ModuleCategory::MISC
+            ]
+        );
+        assert_eq!(categories[4], favorites);
+
+        // Categories ordered by the user come first
+        sort_by_category(
+            &mut categories,
+            &[favorites.clone(), ModuleCategory::WORLD],
+            |category| category,
+        );
+        assert_eq!(
+            categories[..3],
+            [favorites, ModuleCategory::WORLD, ModuleCategory::COMBAT]
+        );
+    }
+
+    #[test]
+    fn test_keybind_conflicts() {
+        let conflicts = keybind_conflicts(&[
+            ("Fly", KeyboardKey::KeyF),
+            ("Reach", KeyboardKey::KeyR),
+            ("Blink", KeyboardKey::KeyF),
+            ("ModuleList", KeyboardKey::KeyNone),
+            ("BrandSpoof", KeyboardKey::KeyNone),
+        ]);
+
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts["Fly"], vec!["Blink"]);
+        assert_eq!(conflicts["Blink"], vec!["Fly"]);
+    }
+
+    // The built-in modules on a fake game, leaked as modules keep it for good
+    fn fake_built_in_modules() -> Vec<Arc<Mutex<ModuleType>>> {
+        built_in_modules(Box::leak(Box::new(FakeJvm::new())))
+    }
+
+    #[test]
+    fn test_module_ids_are_unique() {
+        let modules = fake_built_in_modules();
+        let ids: HashSet<&str> = modules
+            .iter()
+            .map(|module| module.lock().unwrap().get_module_data().id)
+            .collect();
+        assert_eq!(ids.len(), modules.len());
+    }
+
+    #[test]
+    fn test_built_in_modules_dry_run() {
+        for module in fake_built_in_modules() {
+            let module = module.lock().unwrap();
+            if let Err(e) = dry_run::dry_run(&*module, 2) {
+                panic!("{} failed: {:#}", module.get_module_data().id, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_only_user_toggles_notify() {
+        let modules = fake_built_in_modules();
+        let mut module = modules[0].lock().unwrap();
+        let message = module.get_toggle_message(true);
+        let shown = || {
+            notification::active_notifications()
+                .iter()
+                .filter(|notification| notification.message == message)
+                .count()
+        };
+        let before = shown();
+
+        set_enabled_by_user(&mut *module, true);
+        set_enabled_by_user(&mut *module, false);
+        assert_eq!(shown(), before);
+
+        module.get_module_data_mut().notify_on_toggle = true;
+        module.get_module_data_mut().set_enabled(true);
+        module.get_module_data_mut().set_enabled(false);
+        assert_eq!(shown(), before);
+        set_enabled_by_user(&mut *module, true);
+        assert_eq!(shown(), before + 1);
+    }
+}
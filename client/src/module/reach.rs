@@ -0,0 +1,166 @@
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::entity::attribute::{Attribute, AttributeInstance};
+use crate::mapping::jvm_access::JvmAccess;
+use crate::module::{
+    KeyboardKey, Module, ModuleCategory, ModuleData, ModuleSetting, DEFAULT_PRIORITY,
+};
+use std::sync::Mutex;
+
+// Vanilla base values, used when the original ones were never read
+const DEFAULT_ENTITY_RANGE: f64 = 3.0;
+const DEFAULT_BLOCK_RANGE: f64 = 4.5;
+
+/// Increases the entity (and optionally block) interaction range by writing the
+/// base value of the player's interaction range attributes.
+///
+/// Vanilla servers check interactions against their own copy of the attribute, with
+/// a small tolerance, so on most servers hits beyond ~3 blocks are silently dropped
+/// and anti-cheats will flag them.
+#[derive(Debug)]
+pub struct ReachModule {
+    pub module: ModuleData,
+    jvm: &'static dyn JvmAccess,
+    // Base values of (entity range, block range) before the module was enabled
+    original: Mutex<Option<(f64, f64)>>,
+}
+
+impl ReachModule {
+    pub fn new(jvm: &'static dyn JvmAccess) -> Self {
+        Self {
+            jvm,
+            module: ModuleData {
+                id: "Reach",
+                name: "Reach".to_string(),
+                description: "Increases the interaction range".to_string(),
+                category: ModuleCategory::COMBAT,
+                key_bind: KeyboardKey::KeyR,
+                enabled: false,
+                priority: DEFAULT_PRIORITY,
+                notify_on_toggle: false,
+                settings: vec![
+                    ModuleSetting::Slider {
+                        name: "Range".to_string(),
+                        value: 4.0,
+                        min: 3.0,
+                        max: 6.0,
+                        step: Some(0.1),
+                    },
+                    ModuleSetting::Toggle {
+                        name: "Blocks".to_string(),
+                        value: false,
+                    },
+                ],
+            },
+            original: Mutex::new(None),
+        }
+    }
+
+    pub fn get_range(&self) -> f32 {
+        self.module
+            .get_setting("Range")
+            .and_then(|s| s.get_slider_value())
+            .unwrap_or(4.0)
+    }
+
+    pub fn get_blocks(&self) -> bool {
+        self.module
+            .get_setting("Blocks")
+            .and_then(|s| s.get_toggle_value())
+            .unwrap_or(false)
+    }
+
+    fn original(&self) -> (f64, f64) {
+        self.original
+            .lock()
+            .unwrap()
+            .unwrap_or((DEFAULT_ENTITY_RANGE, DEFAULT_BLOCK_RANGE))
+    }
+
+    fn apply(&self) -> anyhow::Result<()> {
+        let (original_entity_range, original_block_range) = self.original();
+        let range = self.get_range() as f64;
+
+        // Blocks keep their larger default range, extended by the same amount
+        let block_range = if self.get_blocks() {
+            original_block_range + (range - original_entity_range).max(0.0)
+        } else {
+            original_block_range
+        };
+        self.write_ranges(range, block_range)
+    }
+
+    // Base values of the player's (entity range, block range) attributes
+    fn read_ranges(&self) -> anyhow::Result<(f64, f64)> {
+        let player = self.jvm.player()?;
+        let read = |attribute| -> anyhow::Result<f64> {
+            let instance = AttributeInstance::find(self.jvm, &player, attribute)?;
+            AttributeInstance::get_base_value(self.jvm, &instance)
+        };
+        Ok((
+            read(Attribute::EntityInteractionRange)?,
+            read(Attribute::BlockInteractionRange)?,
+        ))
+    }
+
+    fn write_ranges(&self, entity_range: f64, block_range: f64) -> anyhow::Result<()> {
+        let player = self.jvm.player()?;
+        for (attribute, value) in [
+            (Attribute::EntityInteractionRange, entity_range),
+            (Attribute::BlockInteractionRange, block_range),
+        ] {
+            let instance = AttributeInstance::find(self.jvm, &player, attribute)?;
+            AttributeInstance::set_base_value(self.jvm, &instance, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Module for ReachModule {
+    fn on_start(&self) -> anyhow::Result<()> {
+        *self.original.lock().unwrap() = Some(self.read_ranges()?);
+        self.apply()
+    }
+
+    fn on_stop(&self) -> anyhow::Result<()> {
+        // Restore the values the game had before enabling
+        let (entity_range, block_range) = self.original();
+        *self.original.lock().unwrap() = None;
+
+        // The attributes went away with the world, the next player has the vanilla ones
+        if self.jvm.player().is_err() {
+            return Ok(());
+        }
+        self.write_ranges(entity_range, block_range)
+    }
+
+    fn on_tick(&self) -> anyhow::Result<()> {
+        // Reapply every tick so setting changes take effect immediately
+        self.apply()
+    }
+
+    // The new player has the vanilla ranges again, read them before extending them
+    fn on_player_changed(&self) -> anyhow::Result<()> {
+        self.on_start()
+    }
+
+    fn requires_player(&self) -> bool {
+        true
+    }
+
+    fn required_classes(&self) -> &'static [MinecraftClassType] {
+        &[
+            MinecraftClassType::LivingEntity,
+            MinecraftClassType::Attributes,
+            MinecraftClassType::AttributeInstance,
+            MinecraftClassType::Holder,
+        ]
+    }
+
+    fn get_module_data(&self) -> &ModuleData {
+        &self.module
+    }
+
+    fn get_module_data_mut(&mut self) -> &mut ModuleData {
+        &mut self.module
+    }
+}
@@ -0,0 +1,102 @@
+use crate::render::RenderContext;
+use log::{error, info};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How long a notification stays visible
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(4);
+// Older notifications are dropped past this count
+const MAX_NOTIFICATIONS: usize = 5;
+// Distance of the in-game notifications from the screen border
+const MARGIN: i32 = 4;
+
+static NOTIFICATIONS: Mutex<Vec<Notification>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationLevel {
+    Info,
+    Error,
+}
+
+/// Short message shown to the user for a few seconds
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub message: String,
+    pub level: NotificationLevel,
+    pub created_at: Instant,
+}
+
+impl Notification {
+    /// Remaining visibility, from 1.0 when created to 0.0 when it expires
+    pub fn remaining(&self) -> f32 {
+        1.0 - self.created_at.elapsed().as_secs_f32() / NOTIFICATION_DURATION.as_secs_f32()
+    }
+}
+
+/// Shows a notification to the user, it is also logged
+pub fn notify(level: NotificationLevel, message: impl Into<String>) {
+    let message = message.into();
+    match level {
+        NotificationLevel::Info => info!("{}", message),
+        NotificationLevel::Error => error!("{}", message),
+    }
+
+    let mut notifications = NOTIFICATIONS.lock().unwrap();
+    notifications.push(Notification {
+        message,
+        level,
+        created_at: Instant::now(),
+    });
+    if notifications.len() > MAX_NOTIFICATIONS {
+        let excess = notifications.len() - MAX_NOTIFICATIONS;
+        notifications.drain(..excess);
+    }
+}
+
+pub fn notify_info(message: impl Into<String>) {
+    notify(NotificationLevel::Info, message);
+}
+
+pub fn notify_error(message: impl Into<String>) {
+    notify(NotificationLevel::Error, message);
+}
+
+/// Notifications that haven't expired yet, oldest first
+pub fn active_notifications() -> Vec<Notification> {
+    let mut notifications = NOTIFICATIONS.lock().unwrap();
+    notifications.retain(|notification| notification.created_at.elapsed() < NOTIFICATION_DURATION);
+    notifications.clone()
+}
+
+/// Draws the active notifications in the bottom right corner of the game, newest at
+/// the bottom, so they show without the GUI window
+pub fn render(context: &RenderContext) -> anyhow::Result<()> {
+    let notifications = active_notifications();
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    let line_height = context.line_height()? + 2;
+    let screen_width = context.gui_width()?;
+    let screen_height = context.gui_height()?;
+
+    for (index, notification) in notifications.iter().rev().enumerate() {
+        // Fade out during the last second
+        let alpha = ((notification.remaining() * 4.0).clamp(0.0, 1.0) * 255.0) as u32;
+        // The game draws text with almost no alpha fully opaque
+        if alpha < 8 {
+            continue;
+        }
+        let color = match notification.level {
+            NotificationLevel::Info => 0x55FF55,
+            NotificationLevel::Error => 0xFF5555,
+        };
+
+        let width = context.text_width(&notification.message)?;
+        let x = screen_width - width - MARGIN;
+        let y = screen_height - MARGIN - line_height * (index as i32 + 1);
+        context.draw_string(&notification.message, x, y, alpha << 24 | color, true)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,277 @@
+use crate::client::DarkClient;
+use crate::mapping::client::minecraft::Minecraft;
+use crate::module::{sort_by_category, KeyboardKey, ModuleCategory};
+use crate::render::RenderContext;
+use log::{error, warn};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Key opening and closing the in-game module menu
+pub const OVERLAY_KEY: KeyboardKey = KeyboardKey::KeyRShift;
+// Keys moving the selection and toggling the selected module while the menu is open
+const UP_KEY: KeyboardKey = KeyboardKey::KeyUp;
+const DOWN_KEY: KeyboardKey = KeyboardKey::KeyDown;
+const TOGGLE_KEY: KeyboardKey = KeyboardKey::KeyReturn;
+
+// Position of the menu and the space around its text, in scaled pixels
+const MARGIN: i32 = 4;
+const PADDING: i32 = 3;
+
+const BACKGROUND_COLOR: u32 = 0xA0000000;
+const SELECTION_COLOR: u32 = 0x80005C80;
+const HEADER_COLOR: u32 = 0xFFAAAAAA;
+const ENABLED_COLOR: u32 = 0xFF55FF55;
+const DISABLED_COLOR: u32 = 0xFFFFFFFF;
+
+const HINT: &str = "Up/Down select, Enter toggle";
+
+static OVERLAY: Mutex<OverlayState> = Mutex::new(OverlayState::new());
+
+#[derive(Debug)]
+struct OverlayState {
+    open: bool,
+    // Index of the selected module in the menu order
+    selected: usize,
+    // Keys down at the last poll, a key only acts when it's pressed
+    held: Vec<i32>,
+}
+
+impl OverlayState {
+    const fn new() -> Self {
+        Self {
+            open: false,
+            selected: 0,
+            held: Vec::new(),
+        }
+    }
+
+    // Applies the keys pressed since the last poll, returns the index of the module
+    // to toggle if there is one. Keys pressed while a game screen is open, e.g. typed
+    // in the chat, are for that screen and ignored
+    fn handle_keys(
+        &mut self,
+        pressed: &HashSet<i32>,
+        module_count: usize,
+        screen_open: bool,
+    ) -> Option<usize> {
+        let just_pressed: Vec<i32> = pressed
+            .iter()
+            .copied()
+            .filter(|key| !self.held.contains(key))
+            .collect();
+        self.held = pressed.iter().copied().collect();
+
+        if screen_open {
+            return None;
+        }
+        if just_pressed.contains(&(OVERLAY_KEY as i32)) {
+            self.open = !self.open;
+            return None;
+        }
+        if !self.open || module_count == 0 {
+            return None;
+        }
+
+        self.selected = self.selected.min(module_count - 1);
+        if just_pressed.contains(&(UP_KEY as i32)) {
+            self.selected = (self.selected + module_count - 1) % module_count;
+        }
+        if just_pressed.contains(&(DOWN_KEY as i32)) {
+            self.selected = (self.selected + 1) % module_count;
+        }
+        just_pressed
+            .contains(&(TOGGLE_KEY as i32))
+            .then_some(self.selected)
+    }
+}
+
+/// Keys the keyboard thread reads for the menu, the navigation keys only while it's open
+pub fn watched_keys() -> Vec<i32> {
+    if OVERLAY.lock().unwrap().open {
+        vec![
+            OVERLAY_KEY as i32,
+            UP_KEY as i32,
+            DOWN_KEY as i32,
+            TOGGLE_KEY as i32,
+        ]
+    } else {
+        vec![OVERLAY_KEY as i32]
+    }
+}
+
+/// Opens or closes the menu and moves its selection from the keys read by the keyboard
+/// thread, only while no game screen is open
+pub fn handle_keys(pressed: &HashSet<i32>) {
+    let (client, screen_open) = match DarkClient::instance().and_then(|client| {
+        let screen = Minecraft::instance()?.get_screen()?;
+        Ok((client, screen.is_some()))
+    }) {
+        Ok(state) => state,
+        Err(e) => {
+            error!("Failed to handle the overlay keys: {}", e);
+            return;
+        }
+    };
+    let modules = menu_entries(client);
+    let toggled = OVERLAY
+        .lock()
+        .unwrap()
+        .handle_keys(pressed, modules.len(), screen_open);
+
+    if let Some(index) = toggled {
+        if let Err(e) = client.toggle_module(modules[index].id) {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Draws the menu over the game when it's open. The menu closes if it fails to draw,
+/// so an error isn't logged every frame
+pub fn render(context: &RenderContext) {
+    if !OVERLAY.lock().unwrap().open {
+        return;
+    }
+
+    if let Err(e) = draw_menu(context) {
+        error!("Failed to draw the module menu, closing it. {}", e);
+        OVERLAY.lock().unwrap().open = false;
+    }
+}
+
+fn draw_menu(context: &RenderContext) -> anyhow::Result<()> {
+    let modules = menu_entries(DarkClient::instance()?);
+    let selected = OVERLAY.lock().unwrap().selected;
+    let line_height = context.line_height()? + 2;
+
+    // A header line is drawn before the first module of each category
+    let mut lines: Vec<(String, u32, bool)> = Vec::new();
+    let mut category = None;
+    for (index, entry) in modules.iter().enumerate() {
+        if category != Some(&entry.category) {
+            category = Some(&entry.category);
+            lines.push((
+                entry.category.display_name().to_string(),
+                HEADER_COLOR,
+                false,
+            ));
+        }
+        let color = if entry.enabled {
+            ENABLED_COLOR
+        } else {
+            DISABLED_COLOR
+        };
+        lines.push((format!("  {}", entry.name), color, index == selected));
+    }
+    lines.push((HINT.to_string(), HEADER_COLOR, false));
+
+    let mut width = 0;
+    for (text, _, _) in &lines {
+        width = width.max(context.text_width(text)?);
+    }
+    let right = MARGIN + width + PADDING * 2;
+    let bottom = MARGIN + line_height * lines.len() as i32 + PADDING * 2;
+    context.fill(MARGIN, MARGIN, right, bottom, BACKGROUND_COLOR)?;
+
+    for (index, (text, color, highlighted)) in lines.iter().enumerate() {
+        let y = MARGIN + PADDING + line_height * index as i32;
+        if *highlighted {
+            context.fill(MARGIN, y - 1, right, y + line_height - 1, SELECTION_COLOR)?;
+        }
+        context.draw_string(text, MARGIN + PADDING, y, *color, true)?;
+    }
+
+    Ok(())
+}
+
+struct MenuEntry {
+    category: ModuleCategory,
+    id: &'static str,
+    name: String,
+    enabled: bool,
+}
+
+// Modules in the order of the menu: by category in tab order, then registration order
+fn menu_entries(client: &DarkClient) -> Vec<MenuEntry> {
+    let mut entries: Vec<MenuEntry> = client
+        .modules
+        .read()
+        .unwrap()
+        .values()
+        .map(|module| {
+            let module = module.lock().unwrap();
+            let module_data = module.get_module_data();
+            MenuEntry {
+                category: module_data.category.clone(),
+                id: module_data.id,
+                name: module_data.name.clone(),
+                enabled: module_data.enabled,
+            }
+        })
+        .collect();
+    sort_by_category(&mut entries, &client.category_order(), |entry| {
+        &entry.category
+    });
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(state: &mut OverlayState, keys: &[KeyboardKey], module_count: usize) -> Option<usize> {
+        let pressed = keys.iter().map(|key| *key as i32).collect();
+        state.handle_keys(&pressed, module_count, false)
+    }
+
+    #[test]
+    fn test_menu_keys_only_act_when_pressed() {
+        let mut state = OverlayState::new();
+        assert_eq!(press(&mut state, &[DOWN_KEY], 3), None);
+        assert_eq!(state.selected, 0);
+
+        press(&mut state, &[OVERLAY_KEY], 3);
+        assert!(state.open);
+        // Still held, the menu stays open
+        press(&mut state, &[OVERLAY_KEY], 3);
+        assert!(state.open);
+        press(&mut state, &[], 3);
+
+        press(&mut state, &[DOWN_KEY], 3);
+        press(&mut state, &[DOWN_KEY], 3);
+        assert_eq!(state.selected, 1);
+        assert_eq!(press(&mut state, &[TOGGLE_KEY], 3), Some(1));
+        assert_eq!(press(&mut state, &[TOGGLE_KEY], 3), None);
+
+        press(&mut state, &[OVERLAY_KEY], 3);
+        assert!(!state.open);
+    }
+
+    #[test]
+    fn test_menu_selection_wraps_around() {
+        let mut state = OverlayState::new();
+        press(&mut state, &[OVERLAY_KEY], 3);
+
+        press(&mut state, &[UP_KEY], 3);
+        assert_eq!(state.selected, 2);
+        press(&mut state, &[DOWN_KEY], 3);
+        assert_eq!(state.selected, 0);
+
+        // The selection stays on a module when modules are removed
+        state.selected = 5;
+        assert_eq!(press(&mut state, &[TOGGLE_KEY], 3), Some(2));
+    }
+
+    #[test]
+    fn test_menu_ignores_keys_while_a_screen_is_open() {
+        let mut state = OverlayState::new();
+        press(&mut state, &[OVERLAY_KEY], 3);
+        press(&mut state, &[], 3);
+
+        let pressed = [DOWN_KEY as i32].into_iter().collect();
+        assert_eq!(state.handle_keys(&pressed, 3, true), None);
+        assert_eq!(state.selected, 0);
+        let pressed = [TOGGLE_KEY as i32].into_iter().collect();
+        assert_eq!(state.handle_keys(&pressed, 3, true), None);
+        assert!(state.open);
+    }
+}
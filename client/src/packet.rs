@@ -0,0 +1,549 @@
+use crate::client::DarkClient;
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::client::minecraft::Minecraft;
+use crate::mapping::entity::player::LocalPlayer;
+use crate::mapping::java::native_class::NativeClass;
+use jni::objects::{GlobalRef, JObject, JValue};
+use jni::JNIEnv;
+use log::{debug, error, info, warn};
+use std::ffi::c_void;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The packet hook is a netty duplex handler added right before the connection's own
+// handler, so it sees every packet the game writes before it gets encoded and every
+// decoded packet before the game handles it
+static PACKET_HOOK: Mutex<Option<PacketHook>> = Mutex::new(None);
+// Whether movement packets are held back instead of being sent
+static HOLDING: AtomicBool = AtomicBool::new(false);
+// Movement packets held back, in the order they were written
+static HELD_PACKETS: Mutex<Vec<GlobalRef>> = Mutex::new(Vec::new());
+// Called with the text of every chat message received
+static CHAT_LISTENERS: RwLock<Vec<ChatListener>> = RwLock::new(Vec::new());
+// Brand sent to servers instead of the real one, if any
+static SPOOFED_BRAND: RwLock<Option<String>> = RwLock::new(None);
+
+const HANDLER_NAME: &str = "darkclient_packet_hook";
+// Name of the Connection in the pipeline, the game handles the packets it reads there
+const CONNECTION_HANDLER_NAME: &str = "packet_handler";
+
+/// Receives the plain text of a chat message, on the netty thread
+pub type ChatListener = Box<dyn Fn(&str) + Send + Sync>;
+
+struct PacketHook {
+    class: GlobalRef,
+    handler: GlobalRef,
+    pipeline: GlobalRef,
+    // Hooked Connection, also used to send the held packets
+    connection: GlobalRef,
+    move_packet_class: GlobalRef,
+    system_chat_class: GlobalRef,
+    player_chat_class: GlobalRef,
+    custom_payload_class: GlobalRef,
+    brand_payload_class: GlobalRef,
+}
+
+/// Installs the packet hook on the player's current connection, replacing the hook
+/// of a previous connection if there is one
+pub fn install_packet_hook(player: &LocalPlayer) -> anyhow::Result<()> {
+    let connection = player_connection(player)?;
+    install_connection_hook(connection.as_obj())
+}
+
+/// Installs the packet hook on a `Connection`, e.g. one still logging in, replacing the
+/// hook of a previous connection if there is one
+pub fn install_connection_hook(connection: &JObject) -> anyhow::Result<()> {
+    uninstall_packet_hook();
+
+    let minecraft = Minecraft::instance()?;
+    let mapping = minecraft.get_mapping();
+    let mut env = DarkClient::instance()?.get_env()?;
+
+    // The channel is a netty type, it isn't in the mapping
+    let channel_field = mapping
+        .get_class(MinecraftClassType::Connection.get_name())?
+        .get_field("channel")?;
+    let channel = env
+        .get_field(
+            connection,
+            &channel_field.name,
+            "Lio/netty/channel/Channel;",
+        )?
+        .l()?;
+    if channel.is_null() {
+        return Err(anyhow::anyhow!("Connection has no channel"));
+    }
+    let pipeline = env
+        .call_method(
+            &channel,
+            "pipeline",
+            "()Lio/netty/channel/ChannelPipeline;",
+            &[],
+        )?
+        .l()?;
+
+    let move_packet_class =
+        mapping.find_class(&mut env, MinecraftClassType::ServerboundMovePlayerPacket)?;
+    let system_chat_class =
+        mapping.find_class(&mut env, MinecraftClassType::ClientboundSystemChatPacket)?;
+    let player_chat_class =
+        mapping.find_class(&mut env, MinecraftClassType::ClientboundPlayerChatPacket)?;
+    let custom_payload_class =
+        mapping.find_class(&mut env, MinecraftClassType::ServerboundCustomPayloadPacket)?;
+    let brand_payload_class = mapping.find_class(&mut env, MinecraftClassType::BrandPayload)?;
+
+    // Same class loader as the game, it also loads netty
+    let minecraft_class = env.get_object_class(minecraft.as_obj())?;
+    let loader = env
+        .call_method(
+            minecraft_class,
+            "getClassLoader",
+            "()Ljava/lang/ClassLoader;",
+            &[],
+        )?
+        .l()?;
+
+    // A class can't be defined twice in the same loader, so every hook needs its own name
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let class = NativeClass::new(&format!("darkclient/PacketHook{}", timestamp))
+        .super_class("io/netty/channel/ChannelDuplexHandler")
+        .method(
+            "write",
+            "(Lio/netty/channel/ChannelHandlerContext;Ljava/lang/Object;Lio/netty/channel/ChannelPromise;)V",
+            native_write as *mut c_void,
+        )
+        .method(
+            "channelRead",
+            "(Lio/netty/channel/ChannelHandlerContext;Ljava/lang/Object;)V",
+            native_channel_read as *mut c_void,
+        )
+        .define(&mut env, &loader)?;
+    let handler = env.new_object(&class, "()V", &[])?;
+
+    // Writes start at the tail of the pipeline so they pass the hook wherever it is, but
+    // reads stop at the connection, the hook must come before it to see them
+    let base_name = mapping.new_string(CONNECTION_HANDLER_NAME)?;
+    let name = mapping.new_string(HANDLER_NAME)?;
+    env.call_method(
+        &pipeline,
+        "addBefore",
+        "(Ljava/lang/String;Ljava/lang/String;Lio/netty/channel/ChannelHandler;)Lio/netty/channel/ChannelPipeline;",
+        &[
+            JValue::Object(&base_name),
+            JValue::Object(&name),
+            JValue::Object(&handler),
+        ],
+    )?;
+
+    *PACKET_HOOK.lock().unwrap() = Some(PacketHook {
+        class,
+        handler: mapping.new_global_ref(handler)?,
+        pipeline: mapping.new_global_ref(pipeline)?,
+        connection: env.new_global_ref(connection)?,
+        move_packet_class: mapping.new_global_ref(JObject::from(move_packet_class))?,
+        system_chat_class: mapping.new_global_ref(JObject::from(system_chat_class))?,
+        player_chat_class: mapping.new_global_ref(JObject::from(player_chat_class))?,
+        custom_payload_class: mapping.new_global_ref(JObject::from(custom_payload_class))?,
+        brand_payload_class: mapping.new_global_ref(JObject::from(brand_payload_class))?,
+    });
+    info!("Packet hook installed");
+
+    Ok(())
+}
+
+/// Installs the packet hook unless it's already on the player's current connection
+pub fn ensure_packet_hook(player: &LocalPlayer) -> anyhow::Result<()> {
+    let connection = player_connection(player)?;
+    ensure_connection_hook(connection.as_obj())
+}
+
+/// Installs the packet hook unless it's already on `connection`
+pub fn ensure_connection_hook(connection: &JObject) -> anyhow::Result<()> {
+    let hooked = PACKET_HOOK
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|hook| hook.connection.clone());
+    if let Some(hooked) = hooked {
+        if DarkClient::instance()?
+            .get_env()?
+            .is_same_object(&hooked, connection)?
+        {
+            return Ok(());
+        }
+    }
+
+    install_connection_hook(connection)
+}
+
+// The Connection wrapped by the player's packet listener
+fn player_connection(player: &LocalPlayer) -> anyhow::Result<GlobalRef> {
+    let mapping = Minecraft::instance()?.get_mapping();
+    let listener = player.get_connection()?;
+    let connection = mapping
+        .call_method(
+            MinecraftClassType::ClientPacketListener,
+            listener.as_obj(),
+            "getConnection",
+            &[],
+        )?
+        .l()?;
+
+    mapping.new_global_ref(connection)
+}
+
+/// Removes the packet hook unless something still needs it: chat listeners, a spoofed
+/// brand or held packets
+pub fn uninstall_unused_packet_hook() {
+    if has_chat_listeners()
+        || SPOOFED_BRAND.read().unwrap().is_some()
+        || HOLDING.load(Ordering::SeqCst)
+    {
+        return;
+    }
+    uninstall_packet_hook();
+}
+
+/// Removes the packet hook, sending any held packet first
+pub fn uninstall_packet_hook() {
+    if let Err(e) = release_held_packets() {
+        error!("Failed to release held packets: {}", e);
+    }
+
+    let Some(hook) = PACKET_HOOK.lock().unwrap().take() else {
+        return;
+    };
+
+    match DarkClient::instance().and_then(|client| Ok(client.get_env()?)) {
+        Ok(mut env) => {
+            let removed = env.call_method(
+                &hook.pipeline,
+                "remove",
+                "(Lio/netty/channel/ChannelHandler;)Lio/netty/channel/ChannelPipeline;",
+                &[JValue::Object(hook.handler.as_obj())],
+            );
+            // The handler is already gone if the connection was closed
+            if removed.is_err() {
+                let _ = env.exception_clear();
+            }
+
+            // Unbind the natives so a late write fails in Java instead of jumping into unloaded code
+            if let Err(e) = env.unregister_native_methods(&hook.class) {
+                error!("Failed to unregister packet hook natives: {}", e);
+            }
+        }
+        Err(e) => error!("Failed to get env to uninstall packet hook: {}", e),
+    }
+
+    info!("Packet hook uninstalled");
+}
+
+/// Starts or stops holding back movement packets, held packets stay queued until
+/// `release_held_packets` is called
+pub fn set_holding(holding: bool) {
+    HOLDING.store(holding, Ordering::SeqCst);
+}
+
+pub fn held_packet_count() -> usize {
+    HELD_PACKETS.lock().unwrap().len()
+}
+
+/// Stops holding packets and sends the held ones through the hooked connection,
+/// returning how many were sent
+pub fn release_held_packets() -> anyhow::Result<usize> {
+    let packets = {
+        let mut held = HELD_PACKETS.lock().unwrap();
+        HOLDING.store(false, Ordering::SeqCst);
+        std::mem::take(&mut *held)
+    };
+    if packets.is_empty() {
+        return Ok(0);
+    }
+
+    let connection = match PACKET_HOOK.lock().unwrap().as_ref() {
+        Some(hook) => hook.connection.clone(),
+        None => {
+            warn!("Dropping {} held packets, no packet hook", packets.len());
+            return Ok(0);
+        }
+    };
+
+    // Packets of a closed connection are silently dropped by the game
+    let mapping = Minecraft::instance()?.get_mapping();
+    for packet in &packets {
+        mapping.call_void_method(
+            MinecraftClassType::Connection,
+            connection.as_obj(),
+            "send",
+            &[JValue::Object(packet.as_obj())],
+        )?;
+    }
+
+    Ok(packets.len())
+}
+
+/// Adds a listener called with the plain text of every chat message received while
+/// the packet hook is installed, both player and system messages.
+///
+/// Chat is read from the decoded packets in the netty pipeline instead of swapping the
+/// game's `ChatComponent` or hooking `addMessage` natively, the pipeline is already hooked
+/// and that works without replacing any game object. Listeners run on the netty thread,
+/// before the message is shown, and must not block.
+pub fn register_chat_listener(listener: ChatListener) {
+    CHAT_LISTENERS.write().unwrap().push(listener);
+}
+
+pub fn has_chat_listeners() -> bool {
+    !CHAT_LISTENERS.read().unwrap().is_empty()
+}
+
+/// Replaces the client brand the game sends to servers when logging in, None sends the
+/// real one. Only connections hooked before the login finishes are affected
+pub fn set_spoofed_brand(brand: Option<String>) {
+    *SPOOFED_BRAND.write().unwrap() = brand;
+}
+
+// ChannelOutboundHandler.write(ChannelHandlerContext, Object, ChannelPromise), called on
+// the netty event loop for every packet written by the game
+extern "system" fn native_write(
+    mut env: JNIEnv,
+    _this: JObject,
+    context: JObject,
+    message: JObject,
+    promise: JObject,
+) {
+    // Whether the packet was handed on, held or written, an error before that must not
+    // swallow the game's packet
+    let mut handed_on = false;
+    // Never unwind into the JVM
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| -> anyhow::Result<()> {
+        let spoofed = spoof_brand(&mut env, &message)?;
+        let packet = spoofed.as_ref().unwrap_or(&message);
+
+        if HOLDING.load(Ordering::SeqCst) && is_movement_packet(&mut env, packet)? {
+            let packet = env.new_global_ref(packet)?;
+            if hold_packet(packet) {
+                handed_on = true;
+                // Nobody waits for movement packets, complete the write so it isn't left pending
+                env.call_method(&promise, "trySuccess", "()Z", &[])?;
+                return Ok(());
+            }
+        }
+
+        handed_on = true;
+        forward_write(&mut env, &context, packet, &promise)
+    }));
+
+    match result {
+        Ok(Ok(_)) => return,
+        Ok(Err(e)) => error!("Error in packet hook: {}", e),
+        Err(_) => error!("Panic in packet hook"),
+    }
+    if handed_on {
+        // A pending Java exception is rethrown to the caller when returning
+        return;
+    }
+
+    // Send the game's packet untouched, the failed lookup may have left an exception
+    let _ = env.exception_clear();
+    if let Err(e) = forward_write(&mut env, &context, &message, &promise) {
+        error!("Failed to forward packet after a packet hook error: {}", e);
+    }
+}
+
+// Passes a packet on to the next handler of the pipeline
+fn forward_write(
+    env: &mut JNIEnv,
+    context: &JObject,
+    message: &JObject,
+    promise: &JObject,
+) -> anyhow::Result<()> {
+    env.call_method(
+        context,
+        "write",
+        "(Ljava/lang/Object;Lio/netty/channel/ChannelPromise;)Lio/netty/channel/ChannelFuture;",
+        &[JValue::Object(message), JValue::Object(promise)],
+    )?;
+    Ok(())
+}
+
+// Queues a movement packet, false if holding stopped since it was checked. Checked
+// under the queue lock, so nothing is queued after `release_held_packets` emptied it
+fn hold_packet(packet: GlobalRef) -> bool {
+    let mut held = HELD_PACKETS.lock().unwrap();
+    if !HOLDING.load(Ordering::SeqCst) {
+        return false;
+    }
+    held.push(packet);
+    true
+}
+
+// Brand packet carrying the spoofed brand to write instead of `message`, if it's a brand
+// packet and a brand is spoofed
+fn spoof_brand<'local>(
+    env: &mut JNIEnv<'local>,
+    message: &JObject,
+) -> anyhow::Result<Option<JObject<'local>>> {
+    let Some(brand) = SPOOFED_BRAND.read().unwrap().clone() else {
+        return Ok(None);
+    };
+    let (custom_payload_class, brand_payload_class) = match PACKET_HOOK.lock().unwrap().as_ref() {
+        Some(hook) => (
+            hook.custom_payload_class.clone(),
+            hook.brand_payload_class.clone(),
+        ),
+        None => return Ok(None),
+    };
+    if !env.is_instance_of(message, &custom_payload_class)? {
+        return Ok(None);
+    }
+
+    let mapping = Minecraft::instance()?.get_mapping();
+    let payload = mapping
+        .call_method(
+            MinecraftClassType::ServerboundCustomPayloadPacket,
+            message,
+            "payload",
+            &[],
+        )?
+        .l()?;
+    if !env.is_instance_of(&payload, &brand_payload_class)? {
+        return Ok(None);
+    }
+
+    let brand = env.new_string(&brand)?;
+    let payload = mapping.new_object(
+        env,
+        MinecraftClassType::BrandPayload,
+        &[JValue::Object(&brand)],
+    )?;
+    let packet = mapping.new_object(
+        env,
+        MinecraftClassType::ServerboundCustomPayloadPacket,
+        &[JValue::Object(&payload)],
+    )?;
+    debug!("Spoofed the client brand");
+
+    Ok(Some(packet))
+}
+
+fn is_movement_packet(env: &mut JNIEnv, message: &JObject) -> anyhow::Result<bool> {
+    let class = match PACKET_HOOK.lock().unwrap().as_ref() {
+        Some(hook) => hook.move_packet_class.clone(),
+        None => return Ok(false),
+    };
+
+    Ok(env.is_instance_of(message, &class)?)
+}
+
+// ChannelInboundHandler.channelRead(ChannelHandlerContext, Object), called on the netty
+// event loop for every packet read by the game
+extern "system" fn native_channel_read(
+    mut env: JNIEnv,
+    _this: JObject,
+    context: JObject,
+    message: JObject,
+) {
+    // Never unwind into the JVM
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| -> anyhow::Result<()> {
+        // A broken listener must not stop the game from receiving packets
+        if let Err(e) = dispatch_chat(&mut env, &message) {
+            error!("Failed to read chat message: {}", e);
+        }
+
+        env.call_method(
+            &context,
+            "fireChannelRead",
+            "(Ljava/lang/Object;)Lio/netty/channel/ChannelHandlerContext;",
+            &[JValue::Object(&message)],
+        )?;
+        Ok(())
+    }));
+
+    // A pending Java exception is rethrown to the caller when returning
+    match result {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => error!("Error in packet hook: {}", e),
+        Err(_) => error!("Panic in packet hook"),
+    }
+}
+
+fn dispatch_chat(env: &mut JNIEnv, message: &JObject) -> anyhow::Result<()> {
+    if !has_chat_listeners() {
+        return Ok(());
+    }
+    let Some(text) = chat_text(env, message)? else {
+        return Ok(());
+    };
+
+    // Listeners are module code, they must not run while the client unloads
+    let client = DarkClient::instance()?;
+    let Some(_guard) = client.begin_module_calls() else {
+        return Ok(());
+    };
+    for listener in CHAT_LISTENERS.read().unwrap().iter() {
+        listener(&text);
+    }
+
+    Ok(())
+}
+
+// Plain text of a chat packet, None for any other packet and for action bar messages
+fn chat_text(env: &mut JNIEnv, message: &JObject) -> anyhow::Result<Option<String>> {
+    let (system_chat_class, player_chat_class) = match PACKET_HOOK.lock().unwrap().as_ref() {
+        Some(hook) => (
+            hook.system_chat_class.clone(),
+            hook.player_chat_class.clone(),
+        ),
+        None => return Ok(None),
+    };
+    let mapping = Minecraft::instance()?.get_mapping();
+
+    let text = if env.is_instance_of(message, &system_chat_class)? {
+        let overlay = mapping
+            .call_method(
+                MinecraftClassType::ClientboundSystemChatPacket,
+                message,
+                "overlay",
+                &[],
+            )?
+            .z()?;
+        if overlay {
+            return Ok(None);
+        }
+        let content = mapping
+            .call_method(
+                MinecraftClassType::ClientboundSystemChatPacket,
+                message,
+                "content",
+                &[],
+            )?
+            .l()?;
+        mapping
+            .call_method(MinecraftClassType::Component, &content, "getString", &[])?
+            .l()?
+    } else if env.is_instance_of(message, &player_chat_class)? {
+        let body = mapping
+            .call_method(
+                MinecraftClassType::ClientboundPlayerChatPacket,
+                message,
+                "body",
+                &[],
+            )?
+            .l()?;
+        mapping
+            .call_method(
+                MinecraftClassType::SignedMessageBodyPacked,
+                &body,
+                "content",
+                &[],
+            )?
+            .l()?
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(env.get_string(&text.into())?.into()))
+}
@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// xorshift64 state shared by every module, 0 until the first draw seeds it. Threads
+// drawing at the same time may get the same value, harmless for timing noise
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+fn next_u64() -> u64 {
+    let mut state = STATE.load(Ordering::Relaxed);
+    if state == 0 {
+        // xorshift never leaves 0
+        state = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default()
+            | 1;
+    }
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    STATE.store(state, Ordering::Relaxed);
+    state
+}
+
+// Uniform in [0, 1)
+fn random_unit() -> f64 {
+    (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// `base` moved by a random amount up to `spread` either way, so timed actions such
+/// as clicks or rotations aren't perfectly periodic, which anticheats flag. Not
+/// suitable for anything security related
+pub fn jitter(base: f64, spread: f64) -> f64 {
+    base + spread.abs() * (random_unit() * 2.0 - 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_stays_within_spread() {
+        let values: Vec<f64> = (0..1000).map(|_| jitter(10.0, 2.0)).collect();
+        assert!(values.iter().all(|value| (8.0..=12.0).contains(value)));
+        // Both sides of the base are reached
+        assert!(values.iter().any(|value| *value < 9.0));
+        assert!(values.iter().any(|value| *value > 11.0));
+
+        assert_eq!(jitter(10.0, 0.0), 10.0);
+        assert!((8.0..=12.0).contains(&jitter(10.0, -2.0)));
+    }
+}
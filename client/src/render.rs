@@ -0,0 +1,573 @@
+use crate::client::DarkClient;
+use crate::logging::log_limited;
+use crate::mapping::class_type::MinecraftClassType;
+use crate::mapping::client::minecraft::Minecraft;
+use crate::mapping::java::native_class::NativeClass;
+use crate::mapping::{FieldType, GameContext};
+use crate::module::ModuleSetting;
+use crate::notification;
+use crate::overlay;
+use jni::objects::{GlobalRef, JObject, JValue};
+use jni::sys::{jlong, jobject};
+use jni::JNIEnv;
+use log::{error, info, warn, Level};
+use std::ffi::c_void;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// The render hook is a toast that never hides itself: the game's ToastManager
+// calls its render method every frame on the render thread, after the HUD
+static RENDER_HOOK: OnceLock<Mutex<Option<RenderHook>>> = OnceLock::new();
+// Whether the hook toast should keep being shown
+static HOOK_VISIBLE: AtomicBool = AtomicBool::new(false);
+
+// Closures waiting to be run on the render thread
+static RENDER_TASKS: Mutex<Vec<RenderTask>> = Mutex::new(Vec::new());
+// Runnables submitted to the game that haven't run yet, they must all run before unloading
+static PENDING_RUNNABLES: AtomicUsize = AtomicUsize::new(0);
+
+type RenderTask = Box<dyn FnOnce() + Send>;
+
+// How long to wait for the game to remove the hook toast before unloading
+const HOOK_REMOVAL_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn render_hook() -> &'static Mutex<Option<RenderHook>> {
+    RENDER_HOOK.get_or_init(|| Mutex::new(None))
+}
+
+struct RenderHook {
+    class: GlobalRef,
+    runnable_class: GlobalRef,
+    runnable: GlobalRef,
+    toast: GlobalRef,
+    toast_manager: GlobalRef,
+    show: GlobalRef,
+    hide: GlobalRef,
+}
+
+/// Visual settings shared by the ESP-style render modules, edited in the GUI and saved
+/// with the config. Kept as module settings so they are drawn and restored the same way
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub settings: Vec<ModuleSetting>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            settings: vec![
+                ModuleSetting::Color {
+                    name: "Box color".to_string(),
+                    value: [1.0, 0.0, 0.0, 1.0],
+                },
+                ModuleSetting::Slider {
+                    name: "Line width".to_string(),
+                    value: 1.0,
+                    min: 0.5,
+                    max: 5.0,
+                    step: Some(0.5),
+                },
+                ModuleSetting::Toggle {
+                    name: "Tracers".to_string(),
+                    value: false,
+                },
+            ],
+        }
+    }
+}
+
+impl RenderSettings {
+    fn setting(&self, name: &str) -> Option<&ModuleSetting> {
+        self.settings.iter().find(|setting| setting.name() == name)
+    }
+
+    /// Color of the boxes drawn around entities, RGBA
+    pub fn box_color(&self) -> [f32; 4] {
+        self.setting("Box color")
+            .and_then(|s| s.get_color_value())
+            .unwrap_or([1.0, 0.0, 0.0, 1.0])
+    }
+
+    /// Width of the box and tracer lines, in pixels
+    pub fn line_width(&self) -> f32 {
+        self.setting("Line width")
+            .and_then(|s| s.get_slider_value())
+            .unwrap_or(1.0)
+    }
+
+    /// Whether lines are drawn from the crosshair to the entities
+    pub fn tracers(&self) -> bool {
+        self.setting("Tracers")
+            .and_then(|s| s.get_toggle_value())
+            .unwrap_or(false)
+    }
+}
+
+/// Context passed to `Module::on_render`, only valid on the render thread during the frame
+pub struct RenderContext<'a> {
+    graphics: &'a JObject<'a>,
+    font: &'a JObject<'a>,
+    enabled_modules: Vec<String>,
+    settings: RenderSettings,
+}
+
+impl GameContext for RenderContext<'_> {}
+
+#[allow(dead_code)]
+impl<'a> RenderContext<'a> {
+    fn new(
+        graphics: &'a JObject<'a>,
+        font: &'a JObject<'a>,
+        enabled_modules: Vec<String>,
+        settings: RenderSettings,
+    ) -> RenderContext<'a> {
+        RenderContext {
+            graphics,
+            font,
+            enabled_modules,
+            settings,
+        }
+    }
+
+    /// Names of the modules enabled when the frame started
+    pub fn enabled_modules(&self) -> &[String] {
+        &self.enabled_modules
+    }
+
+    /// Shared render settings, as they were when the frame started
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Scaled width of the screen
+    pub fn gui_width(&self) -> anyhow::Result<i32> {
+        Ok(self
+            .mapping()?
+            .call_method(
+                MinecraftClassType::GuiGraphics,
+                self.graphics,
+                "guiWidth",
+                &[],
+            )?
+            .i()?)
+    }
+
+    /// Scaled height of the screen
+    pub fn gui_height(&self) -> anyhow::Result<i32> {
+        Ok(self
+            .mapping()?
+            .call_method(
+                MinecraftClassType::GuiGraphics,
+                self.graphics,
+                "guiHeight",
+                &[],
+            )?
+            .i()?)
+    }
+
+    pub fn line_height(&self) -> anyhow::Result<i32> {
+        Ok(self
+            .mapping()?
+            .get_field(
+                MinecraftClassType::Font,
+                self.font,
+                "lineHeight",
+                FieldType::Int,
+            )?
+            .i()?)
+    }
+
+    pub fn text_width(&self, text: &str) -> anyhow::Result<i32> {
+        let mapping = self.mapping()?;
+        let text = mapping.new_string(text)?;
+
+        Ok(mapping
+            .call_method(
+                MinecraftClassType::Font,
+                self.font,
+                "width",
+                &[JValue::Object(&text)],
+            )?
+            .i()?)
+    }
+
+    /// Draws a string at the given screen position, `color` is ARGB
+    pub fn draw_string(
+        &self,
+        text: &str,
+        x: i32,
+        y: i32,
+        color: u32,
+        shadow: bool,
+    ) -> anyhow::Result<()> {
+        let mapping = self.mapping()?;
+        let text = mapping.new_string(text)?;
+
+        mapping.call_void_method(
+            MinecraftClassType::GuiGraphics,
+            self.graphics,
+            "drawString",
+            &[
+                JValue::Object(self.font),
+                JValue::Object(&text),
+                JValue::Int(x),
+                JValue::Int(y),
+                JValue::Int(color as i32),
+                JValue::Bool(shadow as u8),
+            ],
+        )
+    }
+
+    /// Fills a rectangle between the two corners, `color` is ARGB
+    pub fn fill(&self, x1: i32, y1: i32, x2: i32, y2: i32, color: u32) -> anyhow::Result<()> {
+        self.mapping()?.call_void_method(
+            MinecraftClassType::GuiGraphics,
+            self.graphics,
+            "fill",
+            &[
+                JValue::Int(x1),
+                JValue::Int(y1),
+                JValue::Int(x2),
+                JValue::Int(y2),
+                JValue::Int(color as i32),
+            ],
+        )
+    }
+
+    /// Runs `f` with the pose reset, so coordinates are relative to the screen
+    /// instead of the toast slot the hook is rendered in
+    fn with_screen_pose<F>(&self, f: F) -> anyhow::Result<()>
+    where
+        F: FnOnce() -> anyhow::Result<()>,
+    {
+        let mut env = DarkClient::instance()?.get_env()?;
+        let pose = self
+            .mapping()?
+            .call_method(MinecraftClassType::GuiGraphics, self.graphics, "pose", &[])?
+            .l()?;
+
+        env.call_method(&pose, "pushMatrix", "()Lorg/joml/Matrix3x2fStack;", &[])?;
+        env.call_method(&pose, "identity", "()Lorg/joml/Matrix3x2f;", &[])?;
+        let result = f();
+        env.call_method(&pose, "popMatrix", "()Lorg/joml/Matrix3x2fStack;", &[])?;
+
+        result
+    }
+}
+
+/// Converts an RGBA color setting to the ARGB format used by the game
+pub fn to_argb(color: [f32; 4]) -> u32 {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0) as u32;
+    channel(color[3]) << 24 | channel(color[0]) << 16 | channel(color[1]) << 8 | channel(color[2])
+}
+
+/// Installs the render hook, after this `Module::on_render` is called every frame
+pub fn install_render_hook() -> anyhow::Result<()> {
+    let minecraft = Minecraft::instance()?;
+    let mapping = minecraft.get_mapping();
+    let mut env = DarkClient::instance()?.get_env()?;
+
+    let toast_class = mapping.get_class(MinecraftClassType::Toast.get_name())?;
+    let get_wanted_visibility = toast_class.get_method("getWantedVisibility")?;
+    let update = toast_class.get_method("update")?;
+    let render = toast_class.get_method("render")?;
+
+    // The class must be defined in the game's class loader to see the Toast interface
+    let minecraft_class = env.get_object_class(minecraft.as_obj())?;
+    let loader = env
+        .call_method(
+            minecraft_class,
+            "getClassLoader",
+            "()Ljava/lang/ClassLoader;",
+            &[],
+        )?
+        .l()?;
+
+    // A class can't be defined twice in the same loader, so every load needs its own name
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let class = NativeClass::new(&format!("darkclient/RenderHook{}", timestamp))
+        .interface(&toast_class.name)
+        .method(
+            &get_wanted_visibility.name,
+            &get_wanted_visibility.signature,
+            native_get_wanted_visibility as *mut c_void,
+        )
+        .method(
+            &update.name,
+            &update.signature,
+            native_update as *mut c_void,
+        )
+        .method(
+            &render.name,
+            &render.signature,
+            native_render as *mut c_void,
+        )
+        .define(&mut env, &loader)?;
+
+    let toast = env.new_object(&class, "()V", &[])?;
+    let toast = mapping.new_global_ref(toast)?;
+
+    // Runnable submitted to the game's task queue to run closures on the render thread
+    let runnable_class = NativeClass::new(&format!("darkclient/RenderTask{}", timestamp))
+        .interface("java/lang/Runnable")
+        .method("run", "()V", native_run as *mut c_void)
+        .define(&mut env, &loader)?;
+    let runnable = env.new_object(&runnable_class, "()V", &[])?;
+    let runnable = mapping.new_global_ref(runnable)?;
+
+    let visibility = |name: &str| -> anyhow::Result<GlobalRef> {
+        let value = mapping
+            .get_static_field(
+                MinecraftClassType::ToastVisibility,
+                name,
+                FieldType::Object(MinecraftClassType::ToastVisibility, mapping),
+            )?
+            .l()?;
+        mapping.new_global_ref(value)
+    };
+    let show = visibility("SHOW")?;
+    let hide = visibility("HIDE")?;
+
+    let toast_manager = mapping
+        .call_method(
+            MinecraftClassType::Minecraft,
+            minecraft.as_obj(),
+            "getToastManager",
+            &[],
+        )?
+        .l()?;
+    let toast_manager = mapping.new_global_ref(toast_manager)?;
+
+    *render_hook().lock().unwrap() = Some(RenderHook {
+        class,
+        runnable_class,
+        runnable,
+        toast: toast.clone(),
+        toast_manager: toast_manager.clone(),
+        show,
+        hide,
+    });
+    HOOK_VISIBLE.store(true, Ordering::SeqCst);
+
+    // The toast queue isn't thread safe, add the toast from the render thread
+    run_on_render_thread(move || {
+        let result = minecraft.get_mapping().call_void_method(
+            MinecraftClassType::ToastManager,
+            toast_manager.as_obj(),
+            "addToast",
+            &[JValue::Object(toast.as_obj())],
+        );
+        match result {
+            Ok(_) => info!("Render hook installed"),
+            Err(e) => error!("Failed to add render hook toast: {}", e),
+        }
+    })
+}
+
+/// Runs `f` on the game's render thread, during the next frame.
+///
+/// Anything touching rendering state must run there: `GuiGraphics`, `Font`, the
+/// `ToastManager`, the `Window` and any OpenGL call. `Module::on_render` and every
+/// `RenderContext` method already run on the render thread, while `Module::on_tick`,
+/// keyboard handling and the GUI run on their own threads and must go through this.
+///
+/// Fails if the render hook is not installed. If the task can't be submitted to the
+/// game it stays queued and runs with the next submitted one.
+pub fn run_on_render_thread<F>(f: F) -> anyhow::Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let runnable = match render_hook().lock().unwrap().as_ref() {
+        Some(hook) if HOOK_VISIBLE.load(Ordering::SeqCst) => hook.runnable.clone(),
+        _ => return Err(anyhow::anyhow!("Render hook is not installed")),
+    };
+
+    RENDER_TASKS.lock().unwrap().push(Box::new(f));
+
+    let minecraft = Minecraft::instance()?;
+    PENDING_RUNNABLES.fetch_add(1, Ordering::SeqCst);
+    let result = minecraft.get_mapping().call_void_method(
+        MinecraftClassType::BlockableEventLoop,
+        minecraft.as_obj(),
+        "execute",
+        &[JValue::Object(runnable.as_obj())],
+    );
+    if result.is_err() {
+        PENDING_RUNNABLES.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    result
+}
+
+/// Removes the render hook, waiting for the game to stop calling it
+pub fn uninstall_render_hook() {
+    if !HOOK_VISIBLE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    let (class, runnable_class, toast, toast_manager) = match render_hook().lock().unwrap().as_ref()
+    {
+        Some(hook) => (
+            hook.class.clone(),
+            hook.runnable_class.clone(),
+            hook.toast.clone(),
+            hook.toast_manager.clone(),
+        ),
+        None => return,
+    };
+
+    // The toast now asks to be hidden, wait for the game to remove it
+    let start = Instant::now();
+    loop {
+        match is_hook_toast_present(&class, &toast, &toast_manager) {
+            Ok(false) => break,
+            Ok(true) if start.elapsed() < HOOK_REMOVAL_TIMEOUT => {
+                thread::sleep(Duration::from_millis(50))
+            }
+            Ok(true) => {
+                warn!(
+                    "Render hook toast still present after {:?}",
+                    HOOK_REMOVAL_TIMEOUT
+                );
+                break;
+            }
+            Err(e) => {
+                error!("Failed to check the render hook toast: {}", e);
+                break;
+            }
+        }
+    }
+
+    // Runnables already submitted would call into unloaded code, wait for them to run
+    while PENDING_RUNNABLES.load(Ordering::SeqCst) > 0 {
+        if start.elapsed() >= HOOK_REMOVAL_TIMEOUT {
+            warn!(
+                "{} render thread tasks still pending after {:?}",
+                PENDING_RUNNABLES.load(Ordering::SeqCst),
+                HOOK_REMOVAL_TIMEOUT
+            );
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // Unbind the natives so a late call fails in Java instead of jumping into unloaded code
+    match DarkClient::instance().and_then(|client| Ok(client.get_env()?)) {
+        Ok(mut env) => {
+            for class in [&class, &runnable_class] {
+                if let Err(e) = env.unregister_native_methods(class) {
+                    error!("Failed to unregister render hook natives: {}", e);
+                }
+            }
+        }
+        Err(e) => error!("Failed to get env to unregister render hook: {}", e),
+    }
+
+    RENDER_TASKS.lock().unwrap().clear();
+    *render_hook().lock().unwrap() = None;
+    info!("Render hook uninstalled");
+}
+
+fn is_hook_toast_present(
+    class: &GlobalRef,
+    toast: &GlobalRef,
+    toast_manager: &GlobalRef,
+) -> anyhow::Result<bool> {
+    let mapping = Minecraft::instance()?.get_mapping();
+
+    let token = mapping
+        .call_method(MinecraftClassType::Toast, toast.as_obj(), "getToken", &[])?
+        .l()?;
+    let found = mapping
+        .call_method(
+            MinecraftClassType::ToastManager,
+            toast_manager.as_obj(),
+            "getToast",
+            &[JValue::Object(class.as_obj()), JValue::Object(&token)],
+        )?
+        .l()?;
+
+    Ok(!found.is_null())
+}
+
+// Toast.getWantedVisibility()
+extern "system" fn native_get_wanted_visibility(env: JNIEnv, _this: JObject) -> jobject {
+    let guard = render_hook().lock().unwrap();
+    let Some(hook) = guard.as_ref() else {
+        return std::ptr::null_mut();
+    };
+
+    let visibility = if HOOK_VISIBLE.load(Ordering::SeqCst) {
+        &hook.show
+    } else {
+        &hook.hide
+    };
+
+    match env.new_local_ref(visibility) {
+        Ok(visibility) => visibility.into_raw(),
+        Err(e) => {
+            error!("Failed to create visibility reference: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// Runnable.run(), drains the closures queued by run_on_render_thread
+extern "system" fn native_run(_env: JNIEnv, _this: JObject) {
+    let tasks = std::mem::take(&mut *RENDER_TASKS.lock().unwrap());
+    for task in tasks {
+        // Never unwind into the JVM
+        if std::panic::catch_unwind(AssertUnwindSafe(task)).is_err() {
+            error!("Panic in render thread task");
+        }
+    }
+
+    PENDING_RUNNABLES.fetch_sub(1, Ordering::SeqCst);
+}
+
+// Toast.update(ToastManager, long)
+extern "system" fn native_update(_env: JNIEnv, _this: JObject, _manager: JObject, _time: jlong) {}
+
+// Toast.render(GuiGraphics, Font, long)
+extern "system" fn native_render(
+    _env: JNIEnv,
+    _this: JObject,
+    graphics: JObject,
+    font: JObject,
+    _time: jlong,
+) {
+    if !HOOK_VISIBLE.load(Ordering::SeqCst) {
+        return;
+    }
+
+    // Never unwind into the JVM
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let client = DarkClient::instance()?;
+        let context = RenderContext::new(
+            &graphics,
+            &font,
+            client.enabled_module_names(),
+            client.render_settings(),
+        );
+        context.with_screen_pose(|| {
+            client.render(&context);
+            overlay::render(&context);
+            if let Err(e) = notification::render(&context) {
+                log_limited(
+                    Level::Error,
+                    &format!("Failed to draw the notifications: {}", e),
+                );
+            }
+            Ok(())
+        })
+    }));
+
+    match result {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => error!("Error in render hook: {}", e),
+        Err(_) => error!("Panic in render hook"),
+    }
+}
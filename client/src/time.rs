@@ -0,0 +1,66 @@
+use crate::mapping::client::minecraft::Minecraft;
+use std::time::{Duration, Instant};
+
+/// Nominal game tick rate, the real one drops when the client or server lags
+pub const TICKS_PER_SECOND: u32 = 20;
+/// Interval of the client tick thread
+pub const TICK_INTERVAL: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+
+/// Timer measuring both wall-clock time and game ticks since it was started.
+///
+/// Wall-clock time suits delays meant for the user (e.g. reconnecting after 5 seconds),
+/// ticks suit delays meant for the game, which slow down with it during lag spikes.
+#[derive(Debug, Clone)]
+pub struct GameTime {
+    started_at: Instant,
+    // None if the tick count couldn't be read when the timer started
+    start_tick: Option<i32>,
+}
+
+#[allow(dead_code)]
+impl GameTime {
+    pub fn start() -> GameTime {
+        GameTime {
+            started_at: Instant::now(),
+            start_tick: current_tick().ok(),
+        }
+    }
+
+    /// Restarts the timer from now
+    pub fn reset(&mut self) {
+        *self = GameTime::start();
+    }
+
+    /// Wall-clock time since the timer started
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn has_elapsed(&self, duration: Duration) -> bool {
+        self.elapsed() >= duration
+    }
+
+    /// Client ticks since the timer started
+    pub fn elapsed_ticks(&self) -> anyhow::Result<i32> {
+        match self.start_tick {
+            Some(start_tick) => Ok(current_tick()?.wrapping_sub(start_tick)),
+            None => Err(anyhow::anyhow!(
+                "Tick count unavailable when the timer started"
+            )),
+        }
+    }
+
+    pub fn has_elapsed_ticks(&self, ticks: i32) -> anyhow::Result<bool> {
+        Ok(self.elapsed_ticks()? >= ticks)
+    }
+}
+
+/// Current client tick count
+pub fn current_tick() -> anyhow::Result<i32> {
+    Minecraft::instance()?.get_client_tick_count()
+}
+
+/// Wall-clock duration of the given number of ticks at the nominal tick rate
+pub fn ticks_to_duration(ticks: u32) -> Duration {
+    TICK_INTERVAL * ticks
+}
@@ -16,6 +16,17 @@ fn main() {
     )
     .unwrap();
 
+    let args: Vec<String> = std::env::args().collect();
+    // Don't inject anything, so they don't need to be elevated
+    if args.contains(&"--dry-run".to_string()) {
+        dry_run();
+        return;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--remap") {
+        remap(args.get(index + 1));
+        return;
+    }
+
     if !is_elevated() {
         #[cfg(target_family = "unix")]
         eprintln!("❌ Please run this program with sudo: `sudo ./injector`");
@@ -28,7 +39,6 @@ fn main() {
         return; // Exit the program if not elevated
     }
 
-    let args: Vec<String> = std::env::args().collect();
     if args.contains(&"--tui".to_string()) {
         tui::run_tui();
         return;
@@ -49,6 +59,56 @@ fn main() {
     .expect("Failed to run the GUI");
 }
 
+// Prints what an injection would do without injecting, exits with 1 when it would fail
+fn dry_run() {
+    let Some(pid) = platform::find_pid() else {
+        eprintln!("❌ No Minecraft process found");
+        std::process::exit(1);
+    };
+
+    match platform::inject_dry(pid) {
+        Ok(report) => println!("{}", report),
+        Err(e) => {
+            eprintln!("❌ {}. {}", e, e.hint());
+            std::process::exit(1);
+        }
+    }
+}
+
+// Asks the agent to reload the mappings from `path` without reloading the client library,
+// exits with 1 when the agent reports an error or doesn't reply
+fn remap(path: Option<&String>) {
+    let Some(path) = path else {
+        eprintln!("❌ Usage: injector --remap <path to mappings.json>");
+        std::process::exit(1);
+    };
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.into());
+    if !path.is_file() {
+        eprintln!("❌ {} not found", path.display());
+        std::process::exit(1);
+    }
+    let Some(pid) = platform::find_pid() else {
+        eprintln!("❌ No Minecraft process found");
+        std::process::exit(1);
+    };
+
+    match platform::request(pid, &format!("remap {}", path.display())) {
+        Ok(reply) if reply.trim().is_empty() => {
+            eprintln!("❌ No reply from the agent, it may not support remap");
+            std::process::exit(1);
+        }
+        Ok(reply) if reply.starts_with("Error") => {
+            eprintln!("❌ {}", reply.trim());
+            std::process::exit(1);
+        }
+        Ok(reply) => println!("{}", reply.trim()),
+        Err(e) => {
+            eprintln!("❌ {}. {}", e, e.hint());
+            std::process::exit(1);
+        }
+    }
+}
+
 pub struct InjectorGUI {
     status: String,
     pid: Option<u32>,
@@ -72,10 +132,9 @@ impl eframe::App for InjectorGUI {
 
             if ui.button("find").clicked() {
                 self.pid = platform::find_pid();
-                if self.pid.is_none() {
-                    self.status = "Failed to find PID".to_owned();
-                } else {
-                    self.status = format!("Found PID: {}", self.pid.unwrap());
+                match self.pid {
+                    Some(pid) => self.status = format!("Found PID: {}", pid),
+                    None => self.status = "Failed to find PID".to_owned(),
                 }
             }
 
@@ -88,7 +147,7 @@ impl eframe::App for InjectorGUI {
                     Ok(_) => self.status = "Injected successfully!".to_owned(),
                     Err(e) => {
                         log::error!("Error during injection: {:?}", e);
-                        self.status = format!("Failed to inject: {}", e)
+                        self.status = format!("Failed to inject: {}. {}", e, e.hint())
                     }
                 }
             }
@@ -0,0 +1,103 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Why an injection failed, shared by every platform so the GUI and the TUI can tell
+/// the user what to do about it
+#[derive(Debug)]
+pub enum InjectError {
+    /// The target process doesn't exist anymore, or can't be opened
+    ProcessNotFound { pid: u32, reason: String },
+    /// The injector couldn't attach to the target process
+    AttachFailed { pid: u32, reason: String },
+    /// A library to inject is missing, `var` overrides its file name
+    LibraryNotFound { path: PathBuf, var: &'static str },
+    /// The agent loader couldn't be loaded in the target process
+    InjectionFailed(String),
+    /// The command server of the agent loader didn't accept the connection
+    SocketUnreachable {
+        pid: u32,
+        address: String,
+        agent_loaded: bool,
+        source: io::Error,
+    },
+}
+
+impl InjectError {
+    /// What the user can do about the error
+    pub fn hint(&self) -> &'static str {
+        match self {
+            InjectError::ProcessNotFound { .. } => {
+                "Make sure Minecraft is running and find it again"
+            }
+            InjectError::AttachFailed { .. } => {
+                if cfg!(windows) {
+                    "Run the injector as Administrator"
+                } else {
+                    "Run the injector as root and check kernel.yama.ptrace_scope"
+                }
+            }
+            InjectError::LibraryNotFound { .. } => {
+                "Build the workspace and run the injector from the output directory"
+            }
+            InjectError::InjectionFailed(_) => "Check app.log and the game log for details",
+            InjectError::SocketUnreachable {
+                agent_loaded: true, ..
+            } => "Free the port or wait a minute and inject again, without restarting the game",
+            InjectError::SocketUnreachable { .. } => {
+                "Check that no firewall blocks loopback connections and inject again"
+            }
+        }
+    }
+}
+
+impl fmt::Display for InjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InjectError::ProcessNotFound { pid, reason } => {
+                write!(f, "Process {} not found: {}", pid, reason)
+            }
+            InjectError::AttachFailed { pid, reason } => {
+                write!(f, "Unable to attach to process {}: {}", pid, reason)
+            }
+            InjectError::LibraryNotFound { path, var } => write!(
+                f,
+                "{} not found, build it or set {} to its file name",
+                path.display(),
+                var
+            ),
+            InjectError::InjectionFailed(reason) => {
+                write!(f, "Unable to load Agent Loader: {}", reason)
+            }
+            // An agent that is loaded but unreachable couldn't bind its socket, usually
+            // because a stale one still holds the port, and keeps retrying for a while
+            InjectError::SocketUnreachable {
+                pid,
+                address,
+                agent_loaded: true,
+                source,
+            } => write!(
+                f,
+                "Agent Loader present in process {} but its socket {} is unreachable: {}. \
+                 It keeps retrying to bind for about a minute",
+                pid, address, source
+            ),
+            InjectError::SocketUnreachable {
+                address, source, ..
+            } => write!(
+                f,
+                "Unable to connect to Agent Loader at {}: {}",
+                address, source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InjectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InjectError::SocketUnreachable { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -1,21 +1,222 @@
 pub const AGENT_NAME: &str = "libagent_loader";
 pub const LIBRARY_NAME: &str = "libclient";
-pub const SOCKET_ADDRESS: SocketAddr =
-    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7878);
+// Override the library file names (without extension), for renamed builds
+pub const AGENT_NAME_VAR: &str = "DARKCLIENT_AGENT_NAME";
+pub const LIBRARY_NAME_VAR: &str = "DARKCLIENT_LIBRARY_NAME";
+// How long to wait for the agent command server, a freshly loaded agent binds it once
+// it's initialized
+pub const AGENT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Port of the agent command server when its discovery file can't be read, the agent
+// binds the first free port from there up to 7888. Only assumed for an agent that was
+// already loaded, it may predate the discovery file
+pub const DEFAULT_PORT: u16 = 7878;
+// Named pipe used instead of the socket when DARKCLIENT_TRANSPORT=pipe (Windows only)
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\darkclient";
+#[cfg(windows)]
+pub const TRANSPORT_VAR: &str = "DARKCLIENT_TRANSPORT";
 
+mod error;
 #[cfg(unix)]
 mod unix;
 
 #[cfg(windows)]
 mod windows;
 
+pub use self::error::InjectError;
 #[cfg(unix)]
 pub use self::unix::find_pid;
 #[cfg(unix)]
 pub use self::unix::inject;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+#[cfg(unix)]
+pub use self::unix::inject_dry;
+#[cfg(unix)]
+pub use self::unix::request;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::path::{self, Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[cfg(windows)]
 pub use self::windows::find_pid;
 #[cfg(windows)]
 pub use self::windows::inject;
+#[cfg(windows)]
+pub use self::windows::inject_dry;
+#[cfg(windows)]
+pub use self::windows::request;
+
+// Agent loader file name without extension, from DARKCLIENT_AGENT_NAME or the default
+pub fn agent_name() -> String {
+    name_from_env(AGENT_NAME_VAR, AGENT_NAME)
+}
+
+// Client library file name without extension, from DARKCLIENT_LIBRARY_NAME or the default
+pub fn library_name() -> String {
+    name_from_env(LIBRARY_NAME_VAR, LIBRARY_NAME)
+}
+
+fn name_from_env(var: &str, default: &str) -> String {
+    match std::env::var(var) {
+        Ok(name) if !name.trim().is_empty() => name.trim().to_string(),
+        _ => default.to_string(),
+    }
+}
+
+// Absolute path of a library next to the injector binary, or in the working directory
+// if it's not there, so the injector works wherever it's run from
+pub fn resolve_library_path(file_name: &str) -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+
+    let path = match exe_dir.map(|dir| dir.join(file_name)) {
+        Some(path) if path.is_file() => path,
+        _ => PathBuf::from(file_name),
+    };
+    let path = path::absolute(&path).unwrap_or(path);
+
+    log::info!("Resolved {} to {}", file_name, path.display());
+    path
+}
+
+// Fails with a clear error if a library to inject is missing
+pub fn check_library_exists(path: &Path, var: &'static str) -> Result<(), InjectError> {
+    if path.is_file() {
+        return Ok(());
+    }
+
+    Err(InjectError::LibraryNotFound {
+        path: path.to_path_buf(),
+        var,
+    })
+}
+
+// What an injection into `pid` would do, printed by --dry-run. `method` is how the agent
+// would be loaded and `transport` where the reload command would be sent
+pub fn dry_run_report(
+    pid: u32,
+    loader_path: &Path,
+    lib_path: &Path,
+    agent_loaded: bool,
+    method: &str,
+    transport: &str,
+) -> String {
+    let agent = if agent_loaded {
+        "already loaded, would not be injected again".to_string()
+    } else {
+        format!("would be loaded with {}", method)
+    };
+
+    format!(
+        "Target process: {}\n\
+         Agent Loader: {} ({})\n\
+         Client library: {}\n\
+         Reload command: would be sent to {}",
+        pid,
+        loader_path.display(),
+        agent,
+        lib_path.display(),
+        transport
+    )
+}
+
+// Address of the command server of the agent in `pid`, read from the discovery file the
+// agent writes in the temporary directory: `darkclient_<pid>.port` holding the port number.
+// None until the file is written, or without it for an agent predating the port range
+fn discovered_address(pid: u32) -> Option<SocketAddr> {
+    let path = std::env::temp_dir().join(format!("darkclient_{}.port", pid));
+    let content = std::fs::read_to_string(&path).ok()?;
+    match content.trim().parse() {
+        Ok(port) => Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)),
+        Err(e) => {
+            log::warn!("Invalid discovery file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Address of the command server of the agent in `pid`, the default port without a
+// discovery file. For reports and errors, connect with `connect_agent`
+pub fn socket_address(pid: u32) -> SocketAddr {
+    discovered_address(pid)
+        .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DEFAULT_PORT))
+}
+
+// Connects to the command server of the agent in `pid`, polling until it accepts the
+// connection or `timeout` elapses. The address is read again on every attempt, as the
+// agent only writes its discovery file once the socket is bound.
+//
+// Without the file the default port is only tried if `agent_loaded`, i.e. the agent was
+// there before this injection and may predate the file. A freshly loaded agent always
+// writes it, and another instance may already hold the default port
+pub fn connect_agent(pid: u32, timeout: Duration, agent_loaded: bool) -> Result<TcpStream, Error> {
+    let start = Instant::now();
+    loop {
+        let address = match discovered_address(pid) {
+            Some(address) => address,
+            None if agent_loaded => socket_address(pid),
+            None if start.elapsed() < timeout => {
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            None => {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("darkclient_{}.port was never written", pid),
+                ))
+            }
+        };
+        match TcpStream::connect_timeout(&address, Duration::from_millis(250)) {
+            Ok(stream) => {
+                log::info!(
+                    "Connected to {} after {} ms",
+                    address,
+                    start.elapsed().as_millis()
+                );
+                return Ok(stream);
+            }
+            Err(_) if start.elapsed() < timeout => thread::sleep(Duration::from_millis(100)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Sends a command to the agent in `pid` over TCP and returns its reply, which is empty for
+// commands without one. An agent that isn't loaded gets a single attempt
+pub fn tcp_request(pid: u32, command: &str, agent_loaded: bool) -> Result<String, InjectError> {
+    let timeout = if agent_loaded {
+        AGENT_CONNECT_TIMEOUT
+    } else {
+        Duration::ZERO
+    };
+
+    let exchange = || -> Result<String, Error> {
+        let mut stream = connect_agent(pid, timeout, agent_loaded)?;
+        stream.write_all(format!("{}\n", command).as_bytes())?;
+        // The agent replies once it reads the whole line
+        stream.shutdown(Shutdown::Write)?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        Ok(reply)
+    };
+    exchange().map_err(|e| connection_error(pid, socket_address(pid), agent_loaded, e))
+}
+
+// Error for a failed connection to the agent, telling apart an agent that is loaded but
+// couldn't bind its socket, as reinjecting won't help then
+pub fn connection_error(
+    pid: u32,
+    address: SocketAddr,
+    agent_loaded: bool,
+    error: Error,
+) -> InjectError {
+    InjectError::SocketUnreachable {
+        pid,
+        address: address.to_string(),
+        agent_loaded,
+        source: error,
+    }
+}
@@ -1,66 +1,62 @@
-use crate::platform::{AGENT_NAME, LIBRARY_NAME, SOCKET_ADDRESS};
+use crate::platform::{
+    agent_name, check_library_exists, connect_agent, connection_error, dry_run_report,
+    library_name, resolve_library_path, socket_address, tcp_request, InjectError,
+    AGENT_CONNECT_TIMEOUT, AGENT_NAME_VAR, LIBRARY_NAME_VAR,
+};
 use log::{error, info};
 use proc_maps::get_process_maps;
-use std::io::{Error, Write};
-use std::net::TcpStream;
-use std::path::PathBuf;
+use ptrace_inject::{Injector, Process};
+use std::io::Write;
 use std::process::{Command, Stdio};
 use std::time::Duration;
 use std::{path, thread};
-use ptrace_inject::{Injector, Process};
 
-pub fn inject(pid: u32) -> Result<(), Error> {
+const PTRACE_SCOPE_PATH: &str = "/proc/sys/kernel/yama/ptrace_scope";
+// Delay before attaching again to a process that failed for another reason than EPERM
+const ATTACH_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+pub fn inject(pid: u32) -> Result<(), InjectError> {
     // First time: load the agent_loader
-    let loader_path = PathBuf::from(format!("{}.so", AGENT_NAME));
-    let lib_path = PathBuf::from(format!("{}.so", LIBRARY_NAME));
+    let loader_name = format!("{}.so", agent_name());
+    let loader_path = resolve_library_path(&loader_name);
+    let lib_path = resolve_library_path(&format!("{}.so", library_name()));
+
+    check_library_exists(&lib_path, LIBRARY_NAME_VAR)?;
 
-    if !find_library(pid, format!("{}.so", AGENT_NAME).as_str()) {
+    let agent_loaded = find_library(pid, &loader_name)?;
+    if !agent_loaded {
+        check_library_exists(&loader_path, AGENT_NAME_VAR)?;
         info!("Loading Agent Loader");
 
-        let proc = match Process::get(pid) {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Failed to get Process for pid {}: {:?}", pid, e);
-                return Err(Error::new(std::io::ErrorKind::Other, format!("Process::get failed: {:?}", e)));
-            }
-        };
-
-        match Injector::attach(proc) {
-            Ok(mut injector) => {
-                match injector.inject(&loader_path) {
-                    Ok(_) => {
-                        info!("Successfully injected library: {}", loader_path.to_string_lossy());
-                    }
-                    Err(e) => {
-                        error!("Injection failed: {:?}", e);
-                        return Err(Error::new(std::io::ErrorKind::Other, e.to_string()));
-                    }
-                }
+        let mut injector = attach(pid)?;
+        match injector.inject(&loader_path) {
+            Ok(_) => {
+                info!(
+                    "Successfully injected library: {}",
+                    loader_path.to_string_lossy()
+                );
             }
             Err(e) => {
-                error!("Failed to attach to pid {}: {:?}", pid, e);
-                return Err(Error::new(std::io::ErrorKind::Other, e.to_string()));
+                error!("Injection failed: {:?}", e);
+                return Err(InjectError::InjectionFailed(e.to_string()));
             }
         }
-
-        // Wait a moment for complete initialization
-        thread::sleep(Duration::from_millis(500));
     } else {
         info!("Agent Loader already loaded");
     }
 
     // Send a reload command to agent_loader
-    match TcpStream::connect_timeout(&SOCKET_ADDRESS, Duration::from_secs(5)) {
+    match connect_agent(pid, AGENT_CONNECT_TIMEOUT, agent_loaded) {
         Ok(mut stream) => {
             let lib_abs_path = match path::absolute(&lib_path) {
                 Ok(p) => p,
                 Err(e) => {
                     error!("Unable to get absolute path: {:?}", e);
-                    return Err(e);
+                    return Err(InjectError::InjectionFailed(e.to_string()));
                 }
             };
 
-            info!("Connected to {}. Sending reload command", SOCKET_ADDRESS);
+            info!("Sending reload command");
 
             let lib_abs_path = lib_abs_path.to_string_lossy();
             let lib_abs_path = lib_abs_path.trim_matches(|c| c == '"' || c == '\'');
@@ -73,13 +69,105 @@ pub fn inject(pid: u32) -> Result<(), Error> {
             }
         }
         Err(e) => {
-            error!("Unable to connect to server: {:?}", e);
+            let e = connection_error(pid, socket_address(pid), agent_loaded, e);
+            error!("{}", e);
+            return Err(e);
         }
     }
 
     Ok(())
 }
 
+// Attaches to the process with ptrace. A denied attach is reported with what denied it,
+// other failures are retried once as they can come from a process that just started and
+// is still spawning threads
+fn attach(pid: u32) -> Result<Injector, InjectError> {
+    let mut retried = false;
+    loop {
+        let proc = Process::get(pid).map_err(|e| {
+            error!("Failed to get Process for pid {}: {:?}", pid, e);
+            InjectError::ProcessNotFound {
+                pid,
+                reason: e.to_string(),
+            }
+        })?;
+
+        let e = match Injector::attach(proc) {
+            Ok(injector) => return Ok(injector),
+            Err(e) => e,
+        };
+        error!("Failed to attach to pid {}: {:?}", pid, e);
+
+        // The ptrace error ends the chain, e.g. "EPERM: Operation not permitted"
+        let denied = e
+            .chain()
+            .any(|cause| cause.to_string().starts_with("EPERM"));
+        if denied {
+            return Err(InjectError::AttachFailed {
+                pid,
+                reason: attach_denied_reason(),
+            });
+        }
+        if retried {
+            return Err(InjectError::AttachFailed {
+                pid,
+                reason: format!("{:#}", e),
+            });
+        }
+
+        info!("Retrying to attach to pid {}", pid);
+        retried = true;
+        thread::sleep(ATTACH_RETRY_DELAY);
+    }
+}
+
+// Explains an EPERM from ptrace from the Yama ptrace_scope setting, which restricts
+// ptrace to parent processes (1), to CAP_SYS_PTRACE (2) or disables it (3)
+fn attach_denied_reason() -> String {
+    let scope = std::fs::read_to_string(PTRACE_SCOPE_PATH)
+        .ok()
+        .and_then(|scope| scope.trim().parse::<u32>().ok());
+
+    match scope {
+        Some(3) => "ptrace is disabled until reboot (kernel.yama.ptrace_scope = 3)".to_string(),
+        Some(scope) if scope > 0 => format!(
+            "ptrace denied (kernel.yama.ptrace_scope = {}), run the injector as root or \
+             allow it until reboot with `echo 0 | sudo tee {}`",
+            scope, PTRACE_SCOPE_PATH
+        ),
+        _ => "ptrace denied, run the injector as root".to_string(),
+    }
+}
+
+/// Sends a command to the agent already injected in `pid` and returns its reply
+pub fn request(pid: u32, command: &str) -> Result<String, InjectError> {
+    let agent_loaded = find_library(pid, &format!("{}.so", agent_name()))?;
+    tcp_request(pid, command, agent_loaded)
+}
+
+// Finds the libraries and checks the agent like `inject`, without calling ptrace or
+// sending any command, and describes what `inject` would do
+pub fn inject_dry(pid: u32) -> Result<String, InjectError> {
+    let loader_name = format!("{}.so", agent_name());
+    let loader_path = resolve_library_path(&loader_name);
+    let lib_path = resolve_library_path(&format!("{}.so", library_name()));
+
+    check_library_exists(&lib_path, LIBRARY_NAME_VAR)?;
+    let agent_loaded = find_library(pid, &loader_name)?;
+    if !agent_loaded {
+        check_library_exists(&loader_path, AGENT_NAME_VAR)?;
+    }
+
+    Ok(dry_run_report(
+        pid,
+        &loader_path,
+        &lib_path,
+        agent_loaded,
+        "ptrace",
+        &socket_address(pid).to_string(),
+    ))
+}
+
 pub fn find_pid() -> Option<u32> {
     let output = Command::new("ps")
         .arg("ax")
@@ -133,21 +221,23 @@ pub fn find_pid() -> Option<u32> {
     }
 }
 
-fn find_library(pid: u32, lib_name: &str) -> bool {
-    let maps = get_process_maps(pid as i32).ok();
-    if maps.is_none() {
-        error!("Failed to get process maps");
-        return false;
-    }
-    let maps = maps.unwrap();
+// Whether the library is loaded in the process, which fails if the process can't be read
+fn find_library(pid: u32, lib_name: &str) -> Result<bool, InjectError> {
+    let maps = get_process_maps(pid as i32).map_err(|e| {
+        error!("Failed to get process maps: {}", e);
+        InjectError::ProcessNotFound {
+            pid,
+            reason: e.to_string(),
+        }
+    })?;
 
     for map in maps {
         if let Some(path) = map.filename() {
             if path.ends_with(lib_name) {
                 // Library loaded
-                return true;
+                return Ok(true);
             }
         }
     }
-    false
+    Ok(false)
 }
@@ -1,79 +1,173 @@
-use crate::platform::{AGENT_NAME, LIBRARY_NAME, SOCKET_ADDRESS};
+use crate::platform::{
+    agent_name, check_library_exists, connect_agent, connection_error, dry_run_report,
+    library_name, resolve_library_path, socket_address, tcp_request, InjectError,
+    AGENT_CONNECT_TIMEOUT, AGENT_NAME_VAR, LIBRARY_NAME_VAR, PIPE_NAME, TRANSPORT_VAR,
+};
 use log::{error, info};
 use proc_maps::get_process_maps;
-use std::io::Write;
-use std::net::TcpStream;
-use std::path::PathBuf;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
 use std::process::Command;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{io, path, thread};
 
-pub fn inject(pid: u32) -> Result<(), io::Error> {
-    let loader_path = PathBuf::from(format!("{}.dll", AGENT_NAME));
-    let lib_path = PathBuf::from(format!("{}.dll", LIBRARY_NAME));
+pub fn inject(pid: u32) -> Result<(), InjectError> {
+    let agent_name = agent_name();
+    let loader_path = resolve_library_path(&format!("{}.dll", agent_name));
+    let lib_path = resolve_library_path(&format!("{}.dll", library_name()));
+
+    check_library_exists(&lib_path, LIBRARY_NAME_VAR)?;
 
     // Check if agent_loader is already loaded
-    if !find_library(pid, "agent_loader") {
+    let agent_loaded = find_library(pid, &agent_name)?;
+    if !agent_loaded {
+        check_library_exists(&loader_path, AGENT_NAME_VAR)?;
         info!("Loading Agent Loader");
 
+        let loader_abs_path = path::absolute(&loader_path)
+            .map_err(|e| InjectError::InjectionFailed(e.to_string()))?;
         // Load agent_loader via JVMTI
         match Command::new("jcmd")
             .arg(pid.to_string())
             .arg("JVMTI.agent_load")
-            .arg(format!("{:?}", path::absolute(&loader_path)?))
+            .arg(format!("{:?}", loader_abs_path))
             .output()
         {
             Ok(output) if output.status.success() => {
                 info!("Agent Loader loaded via jcmd: {:?}", loader_path);
             }
+            // jcmd fails when it can't attach to the JVM
             Ok(output) => {
-                error!(
-                    "jcmd failed (stderr): {}",
-                    String::from_utf8_lossy(&output.stderr)
-                );
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                error!("jcmd failed (stderr): {}", stderr);
+                return Err(InjectError::AttachFailed {
+                    pid,
+                    reason: format!("jcmd failed: {}", stderr.trim()),
+                });
             }
             Err(e) => {
                 error!("Unable to execute jcmd: {:?}", e);
+                return Err(InjectError::InjectionFailed(format!(
+                    "unable to execute jcmd, is a JDK in PATH? {}",
+                    e
+                )));
             }
         }
-
-        // Wait a moment for complete initialization
-        thread::sleep(Duration::from_millis(500));
     } else {
         info!("Agent Loader already loaded");
     }
 
     // Send a reload command to agent_loader
-    match TcpStream::connect_timeout(&SOCKET_ADDRESS, Duration::from_secs(5)) {
-        Ok(mut stream) => {
-            let lib_abs_path = match path::absolute(&lib_path) {
-                Ok(p) => p,
-                Err(e) => {
-                    error!("Unable to get absolute path: {:?}", e);
-                    return Err(e);
-                }
-            };
-
-            info!("Connected to {}. Sending reload command", SOCKET_ADDRESS);
-
-            let lib_abs_path = lib_abs_path.to_string_lossy();
-            let lib_abs_path = lib_abs_path.trim_matches(|c| c == '"' || c == '\'');
-            // Send the command with the absolute path of the library
-            let command = format!("reload {}", lib_abs_path);
-            info!("Command: {}", command);
-
-            if let Err(e) = stream.write(command.as_bytes()) {
-                error!("Unable to send reload command: {:?}", e);
-            }
-        }
+    let lib_abs_path = match path::absolute(&lib_path) {
+        Ok(p) => p,
         Err(e) => {
-            error!("Unable to connect to server: {:?}", e);
+            error!("Unable to get absolute path: {:?}", e);
+            return Err(InjectError::InjectionFailed(e.to_string()));
+        }
+    };
+
+    let lib_abs_path = lib_abs_path.to_string_lossy();
+    let lib_abs_path = lib_abs_path.trim_matches(|c| c == '"' || c == '\'');
+    // Send the command with the absolute path of the library
+    let command = format!("reload {}", lib_abs_path);
+    info!("Command: {}", command);
+
+    if let Err(e) = send_command(pid, &command, agent_loaded) {
+        if uses_pipe() {
+            error!("Unable to send reload command: {:?}", e);
+        } else {
+            let e = connection_error(pid, socket_address(pid), agent_loaded, e);
+            error!("{}", e);
+            return Err(e);
         }
     }
 
     Ok(())
 }
 
+/// Sends a command to the agent already injected in `pid` and returns its reply
+pub fn request(pid: u32, command: &str) -> Result<String, InjectError> {
+    let agent_loaded = find_library(pid, &agent_name())?;
+    if !uses_pipe() {
+        return tcp_request(pid, command, agent_loaded);
+    }
+
+    // The agent replies once it reads the whole line, then closes the pipe
+    let exchange = || -> Result<String, io::Error> {
+        let mut pipe = open_pipe(AGENT_CONNECT_TIMEOUT)?;
+        pipe.write_all(format!("{}\n", command).as_bytes())?;
+        let mut reply = String::new();
+        pipe.read_to_string(&mut reply)?;
+        Ok(reply)
+    };
+    exchange().map_err(|e| InjectError::SocketUnreachable {
+        pid,
+        address: PIPE_NAME.to_string(),
+        agent_loaded,
+        source: e,
+    })
+}
+
+// Finds the libraries and checks the agent like `inject`, without running jcmd or
+// sending any command, and describes what `inject` would do
+pub fn inject_dry(pid: u32) -> Result<String, InjectError> {
+    let agent_name = agent_name();
+    let loader_path = resolve_library_path(&format!("{}.dll", agent_name));
+    let lib_path = resolve_library_path(&format!("{}.dll", library_name()));
+
+    check_library_exists(&lib_path, LIBRARY_NAME_VAR)?;
+    let agent_loaded = find_library(pid, &agent_name)?;
+    if !agent_loaded {
+        check_library_exists(&loader_path, AGENT_NAME_VAR)?;
+    }
+
+    let transport = if uses_pipe() {
+        PIPE_NAME.to_string()
+    } else {
+        socket_address(pid).to_string()
+    };
+    Ok(dry_run_report(
+        pid,
+        &loader_path,
+        &lib_path,
+        agent_loaded,
+        "jcmd JVMTI.agent_load",
+        &transport,
+    ))
+}
+
+fn uses_pipe() -> bool {
+    std::env::var(TRANSPORT_VAR)
+        .map(|transport| transport.eq_ignore_ascii_case("pipe"))
+        .unwrap_or(false)
+}
+
+// Sends a command to agent_loader over the transport selected by DARKCLIENT_TRANSPORT,
+// `agent_loaded` if it was there before this injection
+fn send_command(pid: u32, command: &str, agent_loaded: bool) -> Result<(), io::Error> {
+    if uses_pipe() {
+        let mut pipe = open_pipe(AGENT_CONNECT_TIMEOUT)?;
+        info!("Connected to {}. Sending command", PIPE_NAME);
+        pipe.write_all(command.as_bytes())
+    } else {
+        let mut stream = connect_agent(pid, AGENT_CONNECT_TIMEOUT, agent_loaded)?;
+        info!("Sending command");
+        stream.write_all(command.as_bytes())
+    }
+}
+
+// Opens the agent pipe, retrying while it doesn't exist yet or is busy with another client
+fn open_pipe(timeout: Duration) -> Result<std::fs::File, io::Error> {
+    let start = Instant::now();
+    loop {
+        match OpenOptions::new().read(true).write(true).open(PIPE_NAME) {
+            Ok(pipe) => return Ok(pipe),
+            Err(_) if start.elapsed() < timeout => thread::sleep(Duration::from_millis(100)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 pub fn find_pid() -> Option<u32> {
     let output = Command::new("powershell")
         .arg("-NoProfile")
@@ -107,20 +201,20 @@ pub fn find_pid() -> Option<u32> {
     None
 }
 
-fn find_library(pid: u32, lib_name: &str) -> bool {
-    let maps = get_process_maps(pid).ok();
-    if maps.is_none() {
-        return false;
-    }
-    let maps = maps.unwrap();
+// Whether the library is loaded in the process, which fails if the process can't be read
+fn find_library(pid: u32, lib_name: &str) -> Result<bool, InjectError> {
+    let maps = get_process_maps(pid).map_err(|e| InjectError::ProcessNotFound {
+        pid,
+        reason: e.to_string(),
+    })?;
 
     for map in maps {
         if let Some(path) = map.filename() {
             if path.ends_with(format!("{}.dll", lib_name)) {
                 // Library loaded
-                return true;
+                return Ok(true);
             }
         }
     }
-    false
+    Ok(false)
 }
@@ -35,7 +35,7 @@ pub fn run_tui() {
                         if let Some(p) = pid {
                             match super::platform::inject(p) {
                                 Ok(_) => status = "Injection successful!".to_string(),
-                                Err(e) => status = format!("Injection error: {}", e),
+                                Err(e) => status = format!("Injection error: {}. {}", e, e.hint()),
                             }
                         } else {
                             status = "Find the PID first.".to_string();